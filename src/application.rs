@@ -225,7 +225,7 @@ impl EuphonicaApplication {
 
     fn update_db(&self) {
         let sender = &self.imp().sender;
-        let _ = sender.send_blocking(MpdMessage::Update);
+        let _ = sender.send_blocking(MpdMessage::Update(None));
     }
 
     fn show_about(&self) {
@@ -2,24 +2,27 @@
 // you name it.
 // This helps avoid having to query the same thing multiple times,
 // whether from MPD or from Last.fm.
-// Images are stored as resized PNG files on disk.
+// Images are stored as resized PNG files on disk, least recently used ones being
+// evicted once the cache folder grows past its size limit (see disk.rs).
 // - Album arts are named with hashes of their URIs (down to the album's
 //   folder). This is because all albums have URIs, but not all have
-//   MusicBrainz IDs.
+//   MusicBrainz IDs. The size each image was resized to follows the hash
+//   (see images.rs).
 // - Artist avatars are named with hashes of their names. Artist names can be substrings
 //   of artist tags instead of the full tags.
 // - Text data is stored as BSON in PoloDB as most of the time we'll be querying
-//   from Last.fm.
+//   from Last.fm. The database's layout is versioned & migrated (see schema.rs).
 extern crate stretto;
 extern crate fasthash;
 extern crate bson;
 extern crate polodb_core;
 use async_channel::{Sender, Receiver};
-use image::{io::Reader, DynamicImage};
+use image::io::Reader;
 use once_cell::sync::Lazy;
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
 use std::{
-    cell::OnceCell, fmt, fs::create_dir_all, path::PathBuf, rc::Rc, sync::{Arc, RwLock}
+    cell::{Cell, OnceCell, RefCell}, collections::VecDeque, fmt, fs::create_dir_all, path::{Path, PathBuf}, rc::Rc, sync::{atomic::{AtomicU64, Ordering as AtomicOrdering}, Arc, RwLock}, time::{SystemTime, UNIX_EPOCH}
 };
 use gtk::{
     glib,
@@ -32,31 +35,299 @@ use fasthash::murmur2;
 
 use crate::{
     client::MpdMessage,
-    common::{AlbumInfo, ArtistInfo},
+    common::{AlbumInfo, AlbumQuality, ArtistInfo, SmartPlaylist},
     meta_providers::{
         prelude::*,
         models,
-        utils::get_best_image,
+        utils::{get_best_image, get_best_image_with_bytes},
+        http,
+        external,
+        local::music_root,
         Metadata,
-        MetadataChain
+        MetadataChain,
+        ProviderResult
     },
-    utils::{resize_convert_image, settings_manager}
+    utils::{self, meta_provider_settings, read_image_from_bytes, resize_convert_image, settings_manager}
 };
 use crate::meta_providers::{
     models::ArtistMeta,
-    get_provider_with_priority
+    get_provider_with_priority,
+    get_provider_name,
+    get_provider_order
 };
 
-use super::CacheState;
+use super::{
+    disk::{touch, CacheCategory, DiskCache},
+    export::{export_covers, ExportItem, ExportPolicy, ExportReport},
+    images,
+    schema::{self, find_cached},
+    CacheState
+};
 
+#[derive(Clone)]
 enum CacheTask {
     // Separate task since we might just need the textual metadata
     // (album art can be provided locally)
     AlbumArt(String, bson::Document, PathBuf, PathBuf),
-    AlbumMeta(String, bson::Document),
+    // Folder URI, key, matching hints & whether to bypass the cache
+    AlbumMeta(String, bson::Document, bson::Document, bool),
     // Both meta and album art together, since for now we cannot provide artist avatars
-    // locally.
-    ArtistMeta(bson::Document, PathBuf, PathBuf)
+    // locally. The last field is whether to bypass the cache.
+    ArtistMeta(bson::Document, PathBuf, PathBuf, bool),
+    // Artist MBID
+    ArtistDiscography(String),
+    // URL & path to save to
+    RemoteImage(String, PathBuf),
+    // Folder URI & key with matching hints, to let the user pick a match manually
+    AlbumCandidates(String, bson::Document),
+    // Artist name & key
+    ArtistCandidates(String, bson::Document)
+}
+
+impl CacheTask {
+    /// What this task is about (such as a specific album) & what it does to it.
+    /// Tasks about the same thing are run one after another & identical ones are only
+    /// run once.
+    fn dedup_key(&self) -> (String, String) {
+        match self {
+            Self::AlbumArt(folder_uri, ..) => (format!("album:{folder_uri}"), "art".to_owned()),
+            Self::AlbumMeta(folder_uri, _, _, force) => (format!("album:{folder_uri}"), format!("meta:{force}")),
+            Self::ArtistMeta(key, _, _, force) => (
+                format!("artist:{}", key.get_str("name").unwrap_or_default()),
+                format!("meta:{force}")
+            ),
+            Self::ArtistDiscography(mbid) => (format!("discography:{mbid}"), "discography".to_owned()),
+            Self::RemoteImage(url, _) => (format!("image:{url}"), "image".to_owned()),
+            Self::AlbumCandidates(folder_uri, _) => (format!("album:{folder_uri}"), "candidates".to_owned()),
+            Self::ArtistCandidates(name, _) => (format!("artist:{name}"), "candidates".to_owned())
+        }
+    }
+}
+
+/// Local playback history of a single song. MPD does not track this itself.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayRecord {
+    pub uri: String,
+    pub play_count: u32,
+    pub last_played: i64  // UNIX timestamp
+}
+
+/// Quality range of all tracks of an album, keyed by folder URI.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct AlbumQualityRecord {
+    uri: String,
+    quality: AlbumQuality
+}
+
+/// All release groups of an artist, keyed by artist MBID. Refreshed once older
+/// than the discography-ttl-days setting.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct DiscographyRecord {
+    mbid: String,
+    fetched: i64,  // UNIX timestamp
+    release_groups: Vec<models::ReleaseGroupMeta>
+}
+
+// How often to look for expired metadata & how many items to refresh each time.
+const BACKGROUND_REFRESH_INTERVAL_S: u32 = 600;
+const BACKGROUND_REFRESH_BATCH: usize = 5;
+// MPD's update queue holds 32 jobs. Leave room for those of other clients.
+const MAX_FOLDER_UPDATES: usize = 16;
+
+/// When an album's or artist's metadata was last fetched & what each provider found,
+/// keyed by the kind ("album" or "artist") & cache key. Used to expire cached metadata,
+/// including the empty documents pushed when nothing was found.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct MetaStatusRecord {
+    id: String,
+    kind: String,
+    key: bson::Document,
+    // Folder URI of albums, needed to refresh them in the background
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
+    // Matching hints last given to providers, see Cache::get_album_hints
+    #[serde(default)]
+    hints: bson::Document,
+    found: bool,
+    fetched: i64,  // UNIX timestamp
+    providers: Vec<ProviderResult>
+}
+
+fn meta_status_id(kind: &str, key: &bson::Document) -> String {
+    format!("{kind}:{key}")
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Whether a result fetched at the given time has outlived its TTL. A TTL of 0 days
+/// means results never expire.
+fn is_expired(fetched: i64, found: bool) -> bool {
+    is_expired_after(fetched, ttl_days(found))
+}
+
+fn ttl_days(found: bool) -> i64 {
+    settings_manager()
+        .child("metaprovider")
+        .uint(if found { "positive-ttl-days" } else { "negative-ttl-days" }) as i64
+}
+
+fn is_expired_after(fetched: i64, ttl_days: i64) -> bool {
+    ttl_days > 0 && now_secs() - fetched >= ttl_days * 86400
+}
+
+/// Metadata cached before statuses were recorded are considered stale.
+fn is_meta_stale(status: Option<&MetaStatusRecord>) -> bool {
+    match status {
+        Some(status) => is_expired(status.fetched, status.found),
+        None => true
+    }
+}
+
+/// Whether the given provider found nothing the last time & that result hasn't expired yet.
+fn recently_not_found(status: Option<&MetaStatusRecord>, provider: &str) -> bool {
+    status.is_some_and(|status| status.providers.iter().any(|res| {
+        res.provider == provider && !res.found && !is_expired(res.fetched, false)
+    }))
+}
+
+fn load_meta_status(
+    doc_cache: &RwLock<polodb_core::Database>, kind: &str, key: &bson::Document
+) -> Option<MetaStatusRecord> {
+    find_cached(doc_cache, "meta_status", bson::doc! {"id": meta_status_id(kind, key)})
+}
+
+/// Record the results of a fetch. Results of providers skipped this time are carried
+/// over from the previous status. Albums also need their folder URI & matching hints
+/// to be refreshed in the background.
+fn store_meta_status(
+    doc_cache: &RwLock<polodb_core::Database>,
+    kind: &str,
+    key: bson::Document,
+    album: Option<(String, bson::Document)>,
+    found: bool,
+    mut results: Vec<ProviderResult>,
+    prev: Option<MetaStatusRecord>
+) {
+    if let Some(prev) = prev {
+        for old in prev.providers.into_iter() {
+            if !results.iter().any(|res| res.provider == old.provider) {
+                results.push(old);
+            }
+        }
+    }
+    let id = meta_status_id(kind, &key);
+    let (uri, hints) = match album {
+        Some((uri, hints)) => (Some(uri), hints),
+        None => (None, bson::Document::new())
+    };
+    let db = doc_cache.write().unwrap();
+    let coll = db.collection::<MetaStatusRecord>("meta_status");
+    let _ = coll.delete_many(bson::doc! {"id": &id});
+    let _ = coll.insert_one(MetaStatusRecord {
+        id,
+        kind: kind.to_owned(),
+        key,
+        uri,
+        hints,
+        found,
+        fetched: now_secs(),
+        providers: results
+    });
+}
+
+/// Choices made by the user for an album or artist, keyed like MetaStatusRecord.
+/// Unlike fetched metadata, these are never expired or replaced by refreshes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct MetaOverrideRecord {
+    id: String,
+    kind: String,
+    key: bson::Document,
+    // Fields to override in the key given to providers, such as a pinned MBID
+    #[serde(default)]
+    pin: bson::Document,
+    // Local image file to use as album art or avatar instead of downloaded ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image: Option<String>
+}
+
+fn load_meta_override(
+    doc_cache: &RwLock<polodb_core::Database>, kind: &str, key: &bson::Document
+) -> Option<MetaOverrideRecord> {
+    doc_cache
+        .read()
+        .unwrap()
+        .collection::<MetaOverrideRecord>("meta_override")
+        .find_one(bson::doc! {"id": meta_status_id(kind, key)})
+        .ok()
+        .flatten()
+}
+
+/// Apply changes to the override of an album or artist, creating one if needed.
+fn update_meta_override(
+    doc_cache: &RwLock<polodb_core::Database>,
+    kind: &str,
+    key: &bson::Document,
+    update: impl FnOnce(&mut MetaOverrideRecord)
+) {
+    let mut record = load_meta_override(doc_cache, kind, key).unwrap_or_else(|| MetaOverrideRecord {
+        id: meta_status_id(kind, key),
+        kind: kind.to_owned(),
+        key: key.clone(),
+        pin: bson::Document::new(),
+        image: None
+    });
+    update(&mut record);
+    let db = doc_cache.write().unwrap();
+    let coll = db.collection::<MetaOverrideRecord>("meta_override");
+    let _ = coll.delete_many(bson::doc! {"id": &record.id});
+    if !record.pin.is_empty() || record.image.is_some() {
+        let _ = coll.insert_one(record);
+    }
+}
+
+/// Put the fields we look metadata up by back in, in case providers returned slightly
+/// different names or the key was overridden. Else we won't find it again.
+fn keep_album_key(meta: &mut models::AlbumMeta, key: &bson::Document) {
+    if let Ok(name) = key.get_str("name") {
+        meta.name = name.to_owned();
+    }
+    if let Ok(artist) = key.get_str("artist") {
+        meta.artist = Some(artist.to_owned());
+    }
+    if let Ok(mbid) = key.get_str("mbid") {
+        meta.mbid = Some(mbid.to_owned());
+    }
+}
+
+fn keep_artist_key(meta: &mut ArtistMeta, key: &bson::Document) {
+    if let Ok(name) = key.get_str("name") {
+        meta.name = name.to_owned();
+    }
+    if let Ok(mbid) = key.get_str("mbid") {
+        meta.mbid = Some(mbid.to_owned());
+    }
+}
+
+/// Generate both cached sizes from a local image file chosen by the user.
+fn save_image_from_file(file: &str, path: &Path, thumbnail_path: &Path) -> bool {
+    let bytes = std::fs::read(file).unwrap_or_default();
+    match read_image_from_bytes(&bytes) {
+        Some(image) => images::save_resized(image, Some(&bytes), path, thumbnail_path),
+        None => {
+            println!("[Cache] Could not read image file {}", file);
+            false
+        }
+    }
+}
+
+/// Time of MPD's last database update as of the last time we checked.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct LibraryVersion {
+    version: i64
 }
 
 // In-memory image cache. Declared here to ease usage between threads as Stretto
@@ -69,11 +340,320 @@ enum CacheTask {
 // thrashing while quickly scrolling through like a million albums.
 // This cache's keys are uri:<folder-level URI>s (for album arts) or artist:<name>
 // (for avatars).
+// Costs are the textures' approximate size in bytes & the budget is user-selectable.
 fn init_image_cache() -> stretto::Cache<(String, bool), Texture> {
-    // TODO: figure out max cost based on user-selectable RAM limit
-    // TODO: figure out cost of textures based on user-selectable resolution
-    // let setting = settings_manager();
-    stretto::Cache::new(327680, 32768).unwrap()
+    let max_cost = image_cache_budget();
+    // Stretto recommends 10 counters per item we expect to keep. Assume most are thumbnails.
+    let num_counters = (max_cost / THUMBNAIL_COST_ESTIMATE).max(100) as usize * 10;
+    stretto::Cache::builder(num_counters, max_cost)
+        .set_metrics(true)
+        .finalize()
+        .expect("ERROR: cannot create image cache")
+}
+
+// Approximate size of a 128x128 RGBA thumbnail
+const THUMBNAIL_COST_ESTIMATE: i64 = 128 * 128 * 4;
+
+/// RAM budget of the image cache in bytes, from the image-cache-mb setting.
+fn image_cache_budget() -> i64 {
+    settings_manager().child("library").uint("image-cache-mb").max(1) as i64 * 1024 * 1024
+}
+
+/// Approximate RAM usage of a decoded texture.
+fn texture_cost(tex: &Texture) -> i64 {
+    tex.width() as i64 * tex.height() as i64 * 4
+}
+
+/// Keep a texture in RAM, waiting until it can be retrieved.
+fn cache_texture(key: (String, bool), tex: Texture) {
+    let cost = texture_cost(&tex);
+    IMAGE_CACHE.insert(key, tex, cost);
+    IMAGE_CACHE.wait().unwrap();
+}
+
+// Stretto's metrics only count costs added & evicted to make room, so the cost of
+// textures removed by us is tracked here & subtracted from them.
+static COST_REMOVED: AtomicU64 = AtomicU64::new(0);
+
+/// Drop a texture from RAM, such as when its image has changed.
+fn remove_texture(key: &(String, bool)) {
+    let cost = IMAGE_CACHE.get(key).map(|tex| texture_cost(tex.value()));
+    if let Some(cost) = cost {
+        COST_REMOVED.fetch_add(cost as u64, AtomicOrdering::Relaxed);
+        IMAGE_CACHE.remove(key);
+    }
+}
+
+/// Drop all textures from RAM.
+fn clear_textures() {
+    let _ = IMAGE_CACHE.clear();
+    let metrics = &IMAGE_CACHE.metrics;
+    COST_REMOVED.store(metrics.get_cost_added().saturating_sub(metrics.get_cost_evicted()), AtomicOrdering::Relaxed);
+}
+
+/// Counters of the in-memory image cache since startup, for diagnostics.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    // Approximate RAM currently taken up by cached textures, in bytes
+    pub used: u64,
+    pub budget: u64,
+    pub entries: u64
+}
+
+/// Fetch & store whatever a task asks for, then notify the main thread.
+async fn run_task(
+    request: CacheTask,
+    fg_sender: Sender<Metadata>,
+    doc_cache: Arc<RwLock<polodb_core::Database>>,
+    providers: Arc<MetadataChain>
+) {
+    match request {
+        CacheTask::AlbumMeta(folder_uri, key, hints, force) => {
+            let _ = gio::spawn_blocking(clone!(
+                #[strong]
+                fg_sender,
+                #[strong]
+                doc_cache,
+                #[strong]
+                providers,
+                move || {
+                    // Check whether there is a fresh one already
+                    let existing: Option<models::AlbumMeta> = find_cached(&doc_cache, "album", key.clone());
+                    let status = load_meta_status(&doc_cache, "album", &key);
+                    let needed = match &existing {
+                        None => true,
+                        Some(_) => force || is_meta_stale(status.as_ref())
+                    };
+                    if needed {
+                        // Local providers need to know where the album is & remote ones can
+                        // use hints to pick the right match. These are only passed to
+                        // providers & are not part of the cache key.
+                        let mut provider_key = key.clone();
+                        provider_key.extend(hints.clone());
+                        provider_key.insert("uri", folder_uri.clone());
+                        // Matches picked by the user take precedence over our own searches.
+                        if let Some(pinned) = load_meta_override(&doc_cache, "album", &key) {
+                            provider_key.extend(pinned.pin);
+                        }
+                        let skip = |provider: &str| !force && recently_not_found(status.as_ref(), provider);
+                        let (mut res, results) = providers.get_album_meta_skipping(provider_key, None, &skip);
+                        if let Some(album) = res.as_mut() {
+                            keep_album_key(album, &key);
+                        }
+                        let found = res.is_some();
+                        {
+                            let db = doc_cache.write().unwrap();
+                            let coll = db.collection::<models::AlbumMeta>("album");
+                            if let Some(album) = res {
+                                let _ = coll.delete_many(key.clone());
+                                let _ = coll.insert_one(album);
+                            }
+                            else if existing.is_none() {
+                                // Push an empty AlbumMeta to block further calls for this album
+                                // until the negative TTL expires.
+                                println!("No album meta could be found for {}. Pushing empty document...", &folder_uri);
+                                let _ = coll.insert_one(models::AlbumMeta::from_key(&key));
+                            }
+                            // Else keep what we had. The provider might just be unreachable.
+                        }
+                        store_meta_status(&doc_cache, "album", key, Some((folder_uri.clone(), hints)), found, results, status);
+                        if found {
+                            let _ = fg_sender.send_blocking(Metadata::AlbumMeta(folder_uri));
+                        }
+                    }
+                }
+            )).await;
+        },
+        CacheTask::ArtistMeta(key, path, thumbnail_path, force) => {
+            let _ = gio::spawn_blocking(clone!(
+                #[strong]
+                fg_sender,
+                #[strong]
+                doc_cache,
+                #[strong]
+                providers,
+                move || {
+                    // Check whether there is a fresh one already
+                    let existing: Option<models::ArtistMeta> = find_cached(&doc_cache, "artist", key.clone());
+                    let status = load_meta_status(&doc_cache, "artist", &key);
+                    let needed = match &existing {
+                        None => true,
+                        Some(_) => force || is_meta_stale(status.as_ref())
+                    };
+                    if needed {
+                        // Guaranteed to have this field so just unwrap it
+                        let name = key.get("name").unwrap().as_str().unwrap().to_owned();
+                        // Matches picked by the user take precedence over our own searches.
+                        let pinned = load_meta_override(&doc_cache, "artist", &key);
+                        let mut provider_key = key.clone();
+                        if let Some(pinned) = pinned.as_ref() {
+                            provider_key.extend(pinned.pin.clone());
+                        }
+                        let skip = |provider: &str| !force && recently_not_found(status.as_ref(), provider);
+                        let (mut res, results) = providers.get_artist_meta_skipping(provider_key, None, &skip);
+                        if let Some(artist) = res.as_mut() {
+                            keep_artist_key(artist, &key);
+                        }
+                        let found = res.is_some();
+                        if let Some(artist) = res {
+                            // Try to download artist avatar too. Replace the existing one
+                            // only when explicitly refreshing. Avatars chosen by the user
+                            // are never replaced by downloaded ones.
+                            if force || !path.exists() || !thumbnail_path.exists() {
+                                let saved = match pinned.as_ref().and_then(|pinned| pinned.image.as_ref()) {
+                                    Some(file) => save_image_from_file(file, &path, &thumbnail_path),
+                                    None => match get_best_image_with_bytes(&artist.image) {
+                                        Ok((image, bytes)) => images::save_resized(image, Some(&bytes), &path, &thumbnail_path),
+                                        Err(e) => {
+                                            println!("[Cache] Failed to download artist avatar: {:?}", e);
+                                            false
+                                        }
+                                    }
+                                };
+                                if saved {
+                                    remove_texture(&(format!("artist:{}", &name), false));
+                                    remove_texture(&(format!("artist:{}", &name), true));
+                                    let _ = fg_sender.send_blocking(Metadata::ArtistAvatar(name.clone(), false));
+                                }
+                            }
+                            let db = doc_cache.write().unwrap();
+                            let coll = db.collection::<models::ArtistMeta>("artist");
+                            let _ = coll.delete_many(key.clone());
+                            let _ = coll.insert_one(artist);
+                        }
+                        else if existing.is_none() {
+                            // Push an empty ArtistMeta to block further calls for this artist
+                            // until the negative TTL expires.
+                            println!("No artist meta could be found for {:?}. Pushing empty document...", &key);
+                            let _ = doc_cache.write().unwrap().collection::<models::ArtistMeta>("artist").insert_one(
+                                models::ArtistMeta::from_key(&key)
+                            );
+                        }
+                        store_meta_status(&doc_cache, "artist", key, None, found, results, status);
+                        if found {
+                            let _ = fg_sender.send_blocking(Metadata::ArtistMeta(name));
+                        }
+                    }
+                    else if let Some(artist) = existing {
+                        // The avatar might have been evicted or cleared from disk since. Get it
+                        // back from what we already know about this artist.
+                        if !artist.image.is_empty() && (!path.exists() || !thumbnail_path.exists()) {
+                            if let Ok((image, bytes)) = get_best_image_with_bytes(&artist.image) {
+                                if images::save_resized(image, Some(&bytes), &path, &thumbnail_path) {
+                                    let _ = fg_sender.send_blocking(Metadata::ArtistAvatar(artist.name, false));
+                                }
+                            }
+                        }
+                    }
+                }
+            )).await;
+        },
+        CacheTask::ArtistDiscography(mbid) => {
+            let _ = gio::spawn_blocking(clone!(
+                #[strong]
+                fg_sender,
+                #[strong]
+                doc_cache,
+                #[strong]
+                providers,
+                move || {
+                    let res = providers.get_artist_discography(&mbid);
+                    if let Some(release_groups) = res {
+                        let fetched = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |d| d.as_secs() as i64);
+                        {
+                            let db = doc_cache.write().unwrap();
+                            let coll = db.collection::<DiscographyRecord>("discography");
+                            let _ = coll.delete_many(bson::doc! {"mbid": &mbid});
+                            let _ = coll.insert_one(DiscographyRecord {
+                                mbid: mbid.clone(),
+                                fetched,
+                                release_groups
+                            });
+                        }
+                        let _ = fg_sender.send_blocking(Metadata::ArtistDiscography(mbid));
+                    }
+                    else {
+                        println!("No discography could be found for artist {}", &mbid);
+                    }
+                }
+            )).await;
+        },
+        CacheTask::RemoteImage(url, path) => {
+            let _ = gio::spawn_blocking(clone!(
+                #[strong]
+                fg_sender,
+                move || {
+                    let res = get_best_image(&[models::ImageMeta {
+                        size: models::ImageSize::Large,
+                        url: url.clone()
+                    }]);
+                    if let Ok(image) = res {
+                        let (_, thumbnail) = resize_convert_image(image);
+                        if images::save_thumbnail(&thumbnail, &path) {
+                            let _ = fg_sender.send_blocking(Metadata::RemoteImage(url));
+                        }
+                    }
+                    else {
+                        println!("[Cache] Failed to download {}: {:?}", &url, res.err());
+                    }
+                }
+            )).await;
+        },
+        CacheTask::AlbumCandidates(folder_uri, key) => {
+            let _ = gio::spawn_blocking(clone!(
+                #[strong]
+                fg_sender,
+                #[strong]
+                providers,
+                move || {
+                    let candidates = providers.search_album_candidates(key);
+                    let _ = fg_sender.send_blocking(Metadata::AlbumCandidates(folder_uri, candidates));
+                }
+            )).await;
+        },
+        CacheTask::ArtistCandidates(name, key) => {
+            let _ = gio::spawn_blocking(clone!(
+                #[strong]
+                fg_sender,
+                #[strong]
+                providers,
+                move || {
+                    let candidates = providers.search_artist_candidates(key);
+                    let _ = fg_sender.send_blocking(Metadata::ArtistCandidates(name, candidates));
+                }
+            )).await;
+        },
+        CacheTask::AlbumArt(folder_uri, bson_key, path, thumbnail_path) => {
+            let _ = gio::spawn_blocking(clone!(
+                #[strong]
+                fg_sender,
+                #[strong]
+                doc_cache,
+                move || {
+                    if let Some(meta) = find_cached::<models::AlbumMeta>(&doc_cache, "album", bson_key) {
+                            let res = get_best_image_with_bytes(&meta.image);
+                            if let Ok((image, bytes)) = res {
+                                if !path.exists() || !thumbnail_path.exists() {
+                                    if images::save_resized(image, Some(&bytes), &path, &thumbnail_path) {
+                                        let _ = fg_sender.send_blocking(Metadata::AlbumArt(folder_uri, false));
+                                    }
+                                }
+                            }
+                        }
+                    else {
+                        println!("Cannot download album art: no local album meta could be found for {folder_uri}");
+                    }
+                }
+            )).await;
+            // let thumbnail_path = this.get_path_for(folder_uri, Metadata::AlbumArt(true));
+            // let path = this.get_path_for(folder_uri, Metadata::AlbumArt(false));
+        },
+    }
 }
 
 static IMAGE_CACHE: Lazy<stretto::Cache<(String, bool), Texture>> = Lazy::new(|| {
@@ -83,13 +663,46 @@ static IMAGE_CACHE: Lazy<stretto::Cache<(String, bool), Texture>> = Lazy::new(||
 pub struct Cache {
     albumart_path: PathBuf,
     avatar_path: PathBuf,
+    // Images not tied to any album or artist in the library
+    remote_image_path: PathBuf,
+    // All of the above plus the document database's location, for disk usage accounting
+    disk_cache: DiskCache,
     // Embedded document database for caching responses from metadata providers.
     // Think MongoDB x SQLite x Rust.
     doc_cache: Arc<RwLock<polodb_core::Database>>,
     mpd_sender: OnceCell<Sender<MpdMessage>>,
     fg_sender: Sender<Metadata>,
+    // For items on screen
     bg_sender: Sender<CacheTask>,
-    meta_providers: Arc<RwLock<MetadataChain>>,
+    // For background refreshes & prefetching, only handled when there's nothing on screen to fetch
+    prefetch_sender: Sender<CacheTask>,
+    // Tasks being run or waiting for another task about the same album or artist, keyed by
+    // the first half of CacheTask::dedup_key. The front task of each queue is the one running.
+    in_flight: Rc<RefCell<FxHashMap<String, VecDeque<(String, CacheTask)>>>>,
+    // Tasks take a snapshot of the chain when they start, so that replacing it never
+    // waits for them to finish.
+    meta_providers: Arc<RwLock<Arc<MetadataChain>>>,
+    // Album grids look up quality grades for every visible cell, so all of them are
+    // loaded into memory once in the background. None until loaded.
+    album_quality: RefCell<Option<FxHashMap<String, AlbumQuality>>>,
+    // Albums whose grades were asked for before they were loaded, to be aggregated
+    // from MPD afterwards if still missing
+    album_quality_waiting: RefCell<Vec<AlbumInfo>>,
+    // Folder URIs whose quality grades have been requested from MPD but not yet received
+    album_quality_pending: RefCell<FxHashSet<String>>,
+    // Artist MBIDs & image URLs that have been requested in this session.
+    // Failed requests are not retried until restart.
+    discography_pending: RefCell<FxHashSet<String>>,
+    remote_image_pending: RefCell<FxHashSet<String>>,
+    // Match candidates received but not yet taken by a dialog, keyed by
+    // "album:{folder URI}" or "artist:{name}"
+    candidates: RefCell<FxHashMap<String, Vec<models::MatchCandidate>>>,
+    // URIs of played songs, most recent first. Auto-DJ checks these on every queue
+    // top-up, so they are loaded once in the background and kept up to date here.
+    // None until loaded.
+    recent_plays: RefCell<Option<VecDeque<String>>>,
+    // Whether cached images are being regenerated to match new size settings
+    regenerating_images: Cell<bool>,
     state: CacheState
 }
 
@@ -104,13 +717,10 @@ impl fmt::Debug for Cache {
 
 fn init_meta_provider_chain() -> MetadataChain {
     let mut providers = MetadataChain::new(0);
-    providers.providers = settings_manager()
-        .child("metaprovider")
-        .value("order")
-        .array_iter_str()
-        .unwrap()
+    providers.providers = get_provider_order()
+        .iter()
         .enumerate()
-        .map(|(prio, key)| get_provider_with_priority(key, prio as u32))
+        .filter_map(|(prio, key)| get_provider_with_priority(key, prio as u32))
         .collect();
     providers
 }
@@ -127,6 +737,10 @@ impl Cache {
             bg_sender,
             bg_receiver
         ): (Sender<CacheTask>, Receiver<CacheTask>) = async_channel::unbounded();
+        let (
+            prefetch_sender,
+            prefetch_receiver
+        ): (Sender<CacheTask>, Receiver<CacheTask>) = async_channel::unbounded();
         let mut albumart_path = app_cache_path.clone();
         albumart_path.push("albumart");
         create_dir_all(&albumart_path)
@@ -137,32 +751,318 @@ impl Cache {
         create_dir_all(&avatar_path)
             .expect("ERROR: cannot create albumart cache folder");
 
+        let mut remote_image_path = app_cache_path.clone();
+        remote_image_path.push("remote");
+        create_dir_all(&remote_image_path)
+            .expect("ERROR: cannot create remote image cache folder");
+
         let mut doc_path = app_cache_path.clone();
 
         let providers = init_meta_provider_chain();
 
         doc_path.push("metadata.polodb");
+        let disk_cache = DiskCache {
+            albumart_path: albumart_path.clone(),
+            avatar_path: avatar_path.clone(),
+            remote_image_path: remote_image_path.clone(),
+            doc_path: doc_path.clone()
+        };
         let cache = Self {
             albumart_path,
             avatar_path,
-            doc_cache: Arc::new(RwLock::new(
-                polodb_core::Database::open_file(doc_path).expect("ERROR: cannot create a metadata database")
-            )),
-            meta_providers: Arc::new(RwLock::new(providers)),
+            remote_image_path,
+            disk_cache,
+            doc_cache: Arc::new(RwLock::new(schema::open_database(&doc_path))),
+            meta_providers: Arc::new(RwLock::new(Arc::new(providers))),
             mpd_sender: OnceCell::new(),
             fg_sender: fg_sender.clone(),
             bg_sender,
+            prefetch_sender,
+            in_flight: Rc::new(RefCell::new(FxHashMap::default())),
+            album_quality: RefCell::new(None),
+            album_quality_waiting: RefCell::new(Vec::new()),
+            album_quality_pending: RefCell::new(FxHashSet::default()),
+            discography_pending: RefCell::new(FxHashSet::default()),
+            remote_image_pending: RefCell::new(FxHashSet::default()),
+            candidates: RefCell::new(FxHashMap::default()),
+            recent_plays: RefCell::new(None),
+            regenerating_images: Cell::new(false),
             state: CacheState::default()
         };
+        // Let providers make conditional requests using validators stored alongside metadata
+        http::set_doc_cache(cache.doc_cache.clone());
+        external::set_error_sender(fg_sender.clone());
         let res = Rc::new(cache);
 
-        res.clone().setup_channel(bg_receiver, fg_sender, fg_receiver);
+        res.clone().setup_channel(bg_receiver, prefetch_receiver, fg_sender, fg_receiver);
+        res.clone().setup_background_refresh();
+        res.clone().load_play_history();
+        res.clone().load_album_qualities();
+        settings_manager().child("library").connect_changed(
+            Some("image-cache-mb"),
+            |_, _| {
+                IMAGE_CACHE.update_max_cost(image_cache_budget());
+            }
+        );
+        settings_manager().child("library").connect_changed(
+            Some("disk-cache-mb"),
+            clone!(
+                #[weak]
+                res,
+                move |_, _| {
+                    res.enforce_disk_cache_limit();
+                }
+            )
+        );
+        for key in ["hires-image-size", "thumbnail-image-size"] {
+            settings_manager().child("library").connect_changed(
+                Some(key),
+                clone!(
+                    #[weak]
+                    res,
+                    move |_, _| {
+                        res.regenerate_images();
+                    }
+                )
+            );
+        }
+        // Thumbnails for the new scale are made as they get loaded, like for display scale
+        // changes.
+        settings_manager().child("library").connect_changed(
+            Some("hidpi-thumbnails"),
+            |_, _| {
+                clear_textures();
+            }
+        );
+        res.enforce_disk_cache_limit();
         res
     }
+
+    /// Let thumbnails follow the main window's scale factor. Those on disk are left as-is,
+    /// with thumbnails for the new scale made as they get loaded.
+    pub fn set_display_scale(&self, scale: f64) {
+        if utils::set_display_scale(scale) && settings_manager().child("library").boolean("hidpi-thumbnails") {
+            // Thumbnails in RAM are still at the old scale.
+            clear_textures();
+        }
+    }
+
+    /// Bring cached images on disk up to the current size settings & format in the background.
+    /// Images needed before the job gets to them are regenerated on the spot instead.
+    /// Only the size settings themselves trigger this, not display scale changes.
+    fn regenerate_images(self: Rc<Self>) {
+        if self.regenerating_images.get() || !images::needs_regeneration() {
+            return;
+        }
+        self.regenerating_images.set(true);
+        let this = self;
+        glib::MainContext::default().spawn_local(async move {
+            // Settings might change again while we're at it.
+            while images::needs_regeneration() {
+                let unscaled_sizes = utils::unscaled_image_sizes();
+                let sizes = utils::cached_image_sizes();
+                let folders = [
+                    this.albumart_path.clone(),
+                    this.avatar_path.clone(),
+                    this.remote_image_path.clone()
+                ];
+                let regenerated = gio::spawn_blocking(move || {
+                    folders
+                        .iter()
+                        .map(|folder| images::regenerate_folder(folder, sizes))
+                        .sum::<usize>()
+                }).await.unwrap_or(0);
+                println!("[Cache] Regenerated {} images at sizes {:?}", regenerated, sizes);
+                images::store_generation(unscaled_sizes);
+                // Textures loaded at the old sizes will be reloaded from disk.
+                clear_textures();
+            }
+            this.regenerating_images.set(false);
+        });
+    }
+
+    /// Locations of cached files, to compute disk usage from a thread pool.
+    pub fn get_disk_cache(&self) -> DiskCache {
+        self.disk_cache.clone()
+    }
+
+    /// Evict the least recently used images in the background if the cache folder has
+    /// grown past the disk-cache-mb setting.
+    fn enforce_disk_cache_limit(&self) {
+        let disk_cache = self.disk_cache.clone();
+        let max_bytes = settings_manager().child("library").uint("disk-cache-mb") as u64 * 1024 * 1024;
+        gio::spawn_blocking(move || {
+            disk_cache.enforce_limit(max_bytes);
+        });
+    }
+
+    /// Delete everything cached of the given category from disk. Clearing metadata keeps
+    /// play history, smart playlists & matches picked by the user, as those cannot be
+    /// fetched again. Await the returned handle to know when it's done.
+    pub fn clear_disk_cache(&self, category: CacheCategory) -> gio::JoinHandle<()> {
+        // Textures already on screen stay there until their widgets are rebound.
+        clear_textures();
+        match category {
+            CacheCategory::AlbumArt => {
+                self.remote_image_pending.borrow_mut().clear();
+            }
+            CacheCategory::Metadata => {
+                if let Some(qualities) = self.album_quality.borrow_mut().as_mut() {
+                    qualities.clear();
+                }
+                self.discography_pending.borrow_mut().clear();
+            }
+            _ => {}
+        }
+        let disk_cache = self.disk_cache.clone();
+        let doc_cache = self.doc_cache.clone();
+        gio::spawn_blocking(move || {
+            if category == CacheCategory::Metadata {
+                let db = doc_cache.write().unwrap();
+                for name in ["album", "artist", "meta_status", "discography", "album_quality", "http_cache"] {
+                    if let Err(e) = db.collection::<bson::Document>(name).delete_many(bson::doc! {}) {
+                        println!("[Cache] Could not clear {}: {:?}", name, e);
+                    }
+                }
+            }
+            else {
+                disk_cache.clear_images(category);
+            }
+        })
+    }
+
+    /// Write the cached arts of the given albums into their folders under the music root
+    /// as cover.jpg (see export.rs), then have MPD update those folders. With dry_run,
+    /// nothing is written & the report only says what would be. Await the returned handle
+    /// for the report.
+    pub fn export_album_arts(&self, albums: &[AlbumInfo], policy: ExportPolicy, dry_run: bool) -> gio::JoinHandle<ExportReport> {
+        let items: Vec<ExportItem> = albums
+            .iter()
+            .map(|album| ExportItem {
+                title: album.title.clone(),
+                folder_uri: album.uri.clone(),
+                hires_path: self.get_path_for(&Metadata::AlbumArt(album.uri.clone(), false))
+            })
+            .collect();
+        let mpd_sender = self.mpd_sender.get().cloned();
+        gio::spawn_blocking(move || {
+            let report = export_covers(music_root().as_deref(), &items, policy, dry_run);
+            let written: Vec<&str> = report.entries
+                .iter()
+                .filter(|entry| entry.outcome.is_write())
+                .map(|entry| entry.folder_uri.as_str())
+                .collect();
+            if !written.is_empty() {
+                println!("[Cache] Exported {} album arts into the music folder", written.len());
+                if let Some(sender) = mpd_sender {
+                    // MPD only queues so many updates, so update everything at once after
+                    // large batches.
+                    if written.len() > MAX_FOLDER_UPDATES {
+                        let _ = sender.send_blocking(MpdMessage::Update(None));
+                    }
+                    else {
+                        for folder_uri in written.into_iter() {
+                            let _ = sender.send_blocking(MpdMessage::Update(Some(folder_uri.to_owned())));
+                        }
+                    }
+                }
+            }
+            report
+        })
+    }
+
+    /// Counters of the in-memory image cache, for the diagnostics page.
+    pub fn get_image_cache_stats(&self) -> ImageCacheStats {
+        let metrics = &IMAGE_CACHE.metrics;
+        ImageCacheStats {
+            hits: metrics.get_hits(),
+            misses: metrics.get_misses(),
+            evictions: metrics.get_keys_evicted(),
+            used: metrics
+                .get_cost_added()
+                .saturating_sub(metrics.get_cost_evicted())
+                .saturating_sub(COST_REMOVED.load(AtomicOrdering::Relaxed)),
+            budget: IMAGE_CACHE.max_cost() as u64,
+            entries: IMAGE_CACHE.len() as u64
+        }
+    }
+
+    /// Periodically re-fetch a few expired albums & artists while the cache is idle, and
+    /// keep the disk cache within its size limit.
+    fn setup_background_refresh(self: Rc<Self>) {
+        let this = Rc::downgrade(&self);
+        glib::timeout_add_seconds_local(BACKGROUND_REFRESH_INTERVAL_S, move || {
+            let Some(this) = this.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            if settings_manager().child("metaprovider").boolean("background-refresh") && this.is_idle() {
+                this.clone().refresh_stale_meta();
+            }
+            this.enforce_disk_cache_limit();
+            glib::ControlFlow::Continue
+        });
+    }
+
+    fn is_idle(&self) -> bool {
+        self.bg_sender.is_empty() && self.prefetch_sender.is_empty() && self.in_flight.borrow().is_empty()
+    }
+
+    /// Queue a batch of expired albums & artists for refetching. The whole status
+    /// collection has to be scanned, so do that on a blocking thread.
+    fn refresh_stale_meta(self: Rc<Self>) {
+        let doc_cache = self.doc_cache.clone();
+        // Settings are best read once rather than for every record.
+        let (positive_ttl, negative_ttl) = (ttl_days(true), ttl_days(false));
+        let this = self;
+        glib::MainContext::default().spawn_local(async move {
+            let stale: Vec<MetaStatusRecord> = gio::spawn_blocking(move || {
+                match doc_cache
+                    .read()
+                    .unwrap()
+                    .collection::<MetaStatusRecord>("meta_status")
+                    .find(None)
+                {
+                    Ok(cursor) => cursor
+                        .filter_map(|res| res.ok())
+                        .filter(|status| is_expired_after(
+                            status.fetched,
+                            if status.found {positive_ttl} else {negative_ttl}
+                        ))
+                        .take(BACKGROUND_REFRESH_BATCH)
+                        .collect(),
+                    Err(e) => {
+                        println!("[Cache] Could not read metadata statuses: {:?}", e);
+                        Vec::new()
+                    }
+                }
+            }).await.unwrap_or_default();
+            this.queue_refreshes(stale);
+        });
+    }
+
+    fn queue_refreshes(&self, stale: Vec<MetaStatusRecord>) {
+        for status in stale.into_iter() {
+            match status.kind.as_str() {
+                "album" => {
+                    if let Some(uri) = status.uri {
+                        let _ = self.prefetch_sender.send_blocking(CacheTask::AlbumMeta(uri, status.key, status.hints, false));
+                    }
+                }
+                "artist" => {
+                    if let Ok(name) = status.key.get_str("name") {
+                        let path = self.get_path_for(&Metadata::ArtistAvatar(name.to_owned(), false));
+                        let thumbnail_path = self.get_path_for(&Metadata::ArtistAvatar(name.to_owned(), true));
+                        let _ = self.prefetch_sender.send_blocking(CacheTask::ArtistMeta(status.key, path, thumbnail_path, false));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
     /// Re-initialise list of providers when priority order is changed
     pub fn reinit_meta_providers(&self) {
-        let mut curr_providers = self.meta_providers.write().unwrap();
-        *curr_providers = init_meta_provider_chain();
+        let providers = Arc::new(init_meta_provider_chain());
+        *self.meta_providers.write().unwrap() = providers;
     }
 
     pub fn set_mpd_sender(&self, sender: Sender<MpdMessage>) {
@@ -176,147 +1076,86 @@ impl Cache {
     fn setup_channel(
         self: Rc<Self>,
         bg_receiver: Receiver<CacheTask>,
+        prefetch_receiver: Receiver<CacheTask>,
         fg_sender: Sender<Metadata>,
         fg_receiver: Receiver<Metadata>
     ) {
-        // Handle remote metadata fetching tasks in another thread
+        // Handle remote metadata fetching tasks in a pool of blocking threads. Tasks for
+        // different albums & artists run concurrently (each provider is rate-limited on
+        // its own by the HTTP scheduler), while tasks for the same one run in order.
         let doc_cache = self.clone().doc_cache.clone();
         let providers = self.clone().meta_providers.clone();
+        let workers = Arc::new(async_lock::Semaphore::new(
+            settings_manager().child("metaprovider").uint("worker-count").max(1) as usize
+        ));
+        let in_flight = self.in_flight.clone();
         glib::MainContext::default().spawn_local(
             async move {
-                use futures::prelude::*;
-                // Allow receiver to be mutated, but keep it at the same memory address.
-                // See Receiver::next doc for why this is needed.
-                let mut receiver = std::pin::pin!(bg_receiver);
-
-                while let Some(request) = receiver.next().await {
-                    match request {
-                        CacheTask::AlbumMeta(folder_uri, key) => {
-                            let _ = gio::spawn_blocking(clone!(
-                                #[strong]
-                                fg_sender,
-                                #[strong]
-                                doc_cache,
-                                #[strong]
-                                providers,
-                                move || {
-                                    // Check whether there is one already
-                                    let existing = doc_cache
-                                        .read()
-                                        .unwrap()
-                                        .collection::<models::AlbumMeta>("album")
-                                        .find_one(key.clone());
-                                    if let Ok(None) = existing {
-                                            let res = providers.read().unwrap().get_album_meta(key.clone(), None);
-                                            if let Some(album) = res {
-                                                let _ = doc_cache
-                                                    .write()
-                                                    .unwrap()
-                                                    .collection::<models::AlbumMeta>("album")
-                                                    .insert_one(album);
-                                                let _ = fg_sender.send_blocking(Metadata::AlbumMeta(folder_uri));
-                                            }
-                                            else {
-                                                // Push an empty AlbumMeta to block further calls for this album.
-                                                println!("No album meta could be found for {}. Pushing empty document...", &folder_uri);
-                                                let _ = doc_cache
-                                                    .write()
-                                                    .unwrap()
-                                                    .collection::<models::AlbumMeta>("album")
-                                                    .insert_one(models::AlbumMeta::from_key(&key));
-                                            }
-                                            sleep_after_request();
-                                        }
-                                }
-                            )).await;
-                        },
-                        CacheTask::ArtistMeta(key, path, thumbnail_path) => {
-                            let _ = gio::spawn_blocking(clone!(
-                                #[strong]
-                                fg_sender,
-                                #[strong]
-                                doc_cache,
-                                #[strong]
-                                providers,
-                                move || {
-                                    // Check whether there is one already
-                                    let existing = doc_cache
-                                        .read()
-                                        .unwrap()
-                                        .collection::<models::ArtistMeta>("artist")
-                                        .find_one(key.clone());
-                                    if let Ok(None) = existing {
-                                            // Guaranteed to have this field so just unwrap it
-                                            let name = key.get("name").unwrap().as_str().unwrap().to_owned();
-                                            let res = providers.read().unwrap().get_artist_meta(key.clone(), None);
-                                            if let Some(artist) = res {
-                                                // Try to download artist avatar too
-                                                let res = get_best_image(&artist.image);
-                                                if res.is_ok() {
-                                                    let (hires, thumbnail) = resize_convert_image(res.unwrap());
-                                                    if !path.exists() || !thumbnail_path.exists() {
-                                                        if let (Ok(_), Ok(_)) = (
-                                                            hires.save(path),
-                                                            thumbnail.save(thumbnail_path)
-                                                        ) {
-                                                            let _ = fg_sender.send_blocking(Metadata::ArtistAvatar(name.clone(), false));
-                                                        }
-                                                    }
-                                                }
-                                                else {
-                                                    println!("[Cache] Failed to download artist avatar: {:?}", res.err());
-                                                }
-                                                let _ = doc_cache.write().unwrap().collection::<models::ArtistMeta>("artist").insert_one(artist);
-                                                let _ = fg_sender.send_blocking(Metadata::ArtistMeta(name));
-                                            }
-
-                                            else {
-                                                // Push an empty ArtistMeta to block further calls for this album.
-                                                println!("No artist meta could be found for {:?}. Pushing empty document...", &key);
-                                                let _ = doc_cache.write().unwrap().collection::<models::ArtistMeta>("artist").insert_one(
-                                                    models::ArtistMeta::from_key(&key)
-                                                );
-                                            }
-                                            sleep_after_request();
-                                        }
-                                }
-                            )).await;
-                        },
-                        CacheTask::AlbumArt(folder_uri, bson_key, path, thumbnail_path) => {
-                            let _ = gio::spawn_blocking(clone!(
-                                #[strong]
-                                fg_sender,
-                                #[strong]
-                                doc_cache,
-                                move || {
-                                    if let Ok(Some(meta)) = doc_cache
-                                        .read()
-                                        .unwrap()
-                                        .collection::<models::AlbumMeta>("album")
-                                        .find_one(bson_key) {
-                                            let res = get_best_image(&meta.image);
-                                            if res.is_ok() {
-                                                let (hires, thumbnail) = resize_convert_image(res.unwrap());
-                                                if !path.exists() || !thumbnail_path.exists() {
-                                                    if let (Ok(_), Ok(_)) = (
-                                                        hires.save(path),
-                                                        thumbnail.save(thumbnail_path)
-                                                    ) {
-                                                        let _ = fg_sender.send_blocking(Metadata::AlbumArt(folder_uri, false));
-                                                    }
-                                                }
-                                            }
-                                            sleep_after_request();
-                                        }
-                                    else {
-                                        println!("Cannot download album art: no local album meta could be found for {folder_uri}");
+                use futures::future::{select, Either};
+                loop {
+                    let permit = workers.acquire_arc().await;
+                    // On-screen items take precedence over background prefetching.
+                    let request = match bg_receiver.try_recv().or_else(|_| prefetch_receiver.try_recv()) {
+                        Ok(request) => request,
+                        Err(_) => {
+                            let next = select(
+                                std::pin::pin!(bg_receiver.recv()),
+                                std::pin::pin!(prefetch_receiver.recv())
+                            ).await;
+                            match next {
+                                Either::Left((Ok(request), _)) | Either::Right((Ok(request), _)) => request,
+                                // Channels are closed, which only happens on shutdown.
+                                _ => break
+                            }
+                        }
+                    };
+                    let (subject, action) = request.dedup_key();
+                    {
+                        let mut queues = in_flight.borrow_mut();
+                        if let Some(queued) = queues.get_mut(&subject) {
+                            // Something is already being done for this album or artist. Run this
+                            // afterwards unless the same thing is already running or queued.
+                            if !queued.iter().any(|(queued_action, _)| queued_action == &action) {
+                                queued.push_back((action, request));
+                            }
+                            continue;
+                        }
+                        queues.insert(subject.clone(), VecDeque::from([(action, request)]));
+                    }
+                    glib::MainContext::default().spawn_local(clone!(
+                        #[strong]
+                        fg_sender,
+                        #[strong]
+                        doc_cache,
+                        #[strong]
+                        providers,
+                        #[strong]
+                        in_flight,
+                        async move {
+                            // The front of the queue is the task being run, so that duplicates
+                            // of it are dropped too.
+                            loop {
+                                let next = in_flight
+                                    .borrow()
+                                    .get(&subject)
+                                    .and_then(|queued| queued.front())
+                                    .map(|(_, request)| request.clone());
+                                let Some(request) = next else {
+                                    break;
+                                };
+                                let chain = providers.read().unwrap().clone();
+                                run_task(request, fg_sender.clone(), doc_cache.clone(), chain).await;
+                                let mut queues = in_flight.borrow_mut();
+                                if let Some(queued) = queues.get_mut(&subject) {
+                                    queued.pop_front();
+                                    if queued.is_empty() {
+                                        queues.remove(&subject);
                                     }
                                 }
-                            )).await;
-                            // let thumbnail_path = this.get_path_for(folder_uri, Metadata::AlbumArt(true));
-                            // let path = this.get_path_for(folder_uri, Metadata::AlbumArt(false));
-                        },
-                    };
+                            }
+                            drop(permit);
+                        }
+                    ));
                 }
             }
         );
@@ -339,17 +1178,42 @@ impl Cache {
                     Metadata::AlbumArt(folder_uri, _) => {
                         this.state.emit_with_param("album-art-downloaded", &folder_uri);
                     }
-                    Metadata::AlbumArtNotAvailable(folder_uri, key) => {
+                    Metadata::AlbumArtNotAvailable(folder_uri, key, prefetch) => {
                         println!("MPD does not have album art for {}, fetching remotely...", &folder_uri);
                         let path = self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), false));
                         let thumbnail_path = self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), true));
-                        let _ = this.bg_sender.send_blocking(
+                        let sender = if prefetch {&this.prefetch_sender} else {&this.bg_sender};
+                        let _ = sender.send_blocking(
                             CacheTask::AlbumArt(folder_uri, key, path, thumbnail_path)
                         );
                     }
                     Metadata::ArtistAvatar(name, _) => {
                         this.state.emit_with_param("artist-avatar-downloaded", &name);
                     }
+                    Metadata::AlbumQuality(folder_uri, quality) => {
+                        this.on_album_quality_downloaded(folder_uri, quality);
+                    }
+                    Metadata::LibraryVersion(version) => {
+                        this.on_library_version(version);
+                    }
+                    Metadata::ArtistDiscography(mbid) => {
+                        this.discography_pending.borrow_mut().remove(&mbid);
+                        this.state.emit_with_param("artist-discography-downloaded", &mbid);
+                    }
+                    Metadata::RemoteImage(url) => {
+                        this.state.emit_with_param("remote-image-downloaded", &url);
+                    }
+                    Metadata::AlbumCandidates(folder_uri, candidates) => {
+                        this.candidates.borrow_mut().insert(format!("album:{}", &folder_uri), candidates);
+                        this.state.emit_with_param("album-candidates-downloaded", &folder_uri);
+                    }
+                    Metadata::ArtistCandidates(name, candidates) => {
+                        this.candidates.borrow_mut().insert(format!("artist:{}", &name), candidates);
+                        this.state.emit_with_param("artist-candidates-downloaded", &name);
+                    }
+                    Metadata::ProviderError(key, message) => {
+                        this.state.emit_by_name::<()>("provider-error", &[&get_provider_name(&key), &message]);
+                    }
                 }
             }
         });
@@ -367,24 +1231,21 @@ impl Cache {
                 let hashed = murmur2::hash64(&folder_uri).to_string();
 
                 let mut path = self.albumart_path.clone();
-                if *thumbnail {
-                    path.push(hashed + "_thumb.png");
-                }
-                else {
-                    path.push(hashed + ".png");
-                }
+                path.push(images::file_name(&hashed, *thumbnail));
                 path
             },
             Metadata::ArtistAvatar(name, thumbnail) => {
                 let hashed = murmur2::hash64(&name).to_string();
 
                 let mut path = self.avatar_path.clone();
-                if *thumbnail {
-                    path.push(hashed + "_thumb.png");
-                }
-                else {
-                    path.push(hashed + ".png");
-                }
+                path.push(images::file_name(&hashed, *thumbnail));
+                path
+            },
+            Metadata::RemoteImage(url) => {
+                let hashed = murmur2::hash64(&url).to_string();
+
+                let mut path = self.remote_image_path.clone();
+                path.push(images::file_name(&hashed, true));
                 path
             },
             _ => unreachable!()
@@ -410,10 +1271,102 @@ impl Cache {
     }
 
 
+    /// Get the quality range of all tracks in an album, if known. On a miss, optionally
+    /// schedule aggregating it from MPD. Listen to album-quality-downloaded for the result.
+    pub fn load_album_quality(&self, album: &AlbumInfo, schedule: bool) -> Option<AlbumQuality> {
+        match self.album_quality.borrow().as_ref() {
+            Some(qualities) => {
+                if let Some(quality) = qualities.get(&album.uri) {
+                    return Some(*quality);
+                }
+            }
+            None => {
+                if schedule {
+                    self.album_quality_waiting.borrow_mut().push(album.clone());
+                }
+                return None;
+            }
+        }
+        if schedule && self.album_quality_pending.borrow_mut().insert(album.uri.clone()) {
+            if let Some(sender) = self.mpd_sender.get() {
+                let _ = sender.send_blocking(MpdMessage::AlbumQuality(
+                    album.title.clone(),
+                    album.uri.clone()
+                ));
+            }
+        }
+        None
+    }
+
+    /// Read all saved quality grades into memory in the background, then deal with
+    /// albums that were asked about in the meantime.
+    fn load_album_qualities(self: Rc<Self>) {
+        let doc_cache = self.doc_cache.clone();
+        let this = self;
+        glib::MainContext::default().spawn_local(async move {
+            let qualities: FxHashMap<String, AlbumQuality> = gio::spawn_blocking(move || {
+                let mut res = FxHashMap::default();
+                if let Ok(cursor) = doc_cache.read().unwrap().collection::<AlbumQualityRecord>("album_quality").find(None) {
+                    for record in cursor.flatten() {
+                        res.insert(record.uri, record.quality);
+                    }
+                }
+                res
+            }).await.unwrap_or_default();
+            let _ = this.album_quality.replace(Some(qualities));
+            let waiting = this.album_quality_waiting.take();
+            for album in waiting.iter() {
+                if this.load_album_quality(album, true).is_some() {
+                    this.state.emit_with_param("album-quality-downloaded", &album.uri);
+                }
+            }
+        });
+    }
+
+    fn on_album_quality_downloaded(&self, folder_uri: String, quality: AlbumQuality) {
+        self.album_quality_pending.borrow_mut().remove(&folder_uri);
+        // No songs found means the album has since been moved or deleted. Don't keep
+        // that around, so it is aggregated again next time.
+        if quality.track_count > 0 {
+            let db = self.doc_cache.write().unwrap();
+            let coll = db.collection::<AlbumQualityRecord>("album_quality");
+            let _ = coll.delete_one(bson::doc! {"uri": &folder_uri});
+            if let Err(e) = coll.insert_one(AlbumQualityRecord { uri: folder_uri.clone(), quality }) {
+                println!("[Cache] Failed to save quality grades of {}: {e:?}", &folder_uri);
+            }
+        }
+        if let Some(qualities) = self.album_quality.borrow_mut().as_mut() {
+            qualities.insert(folder_uri.clone(), quality);
+        }
+        self.state.emit_with_param("album-quality-downloaded", &folder_uri);
+    }
+
+    /// Discard data derived from the library's contents if MPD's database has
+    /// been updated since we last saw it.
+    fn on_library_version(&self, version: i64) {
+        let db = self.doc_cache.write().unwrap();
+        let coll = db.collection::<LibraryVersion>("library_version");
+        if coll.find_one(bson::doc! {}).ok().flatten().is_some_and(|curr| curr.version == version) {
+            return;
+        }
+        println!("[Cache] MPD database has changed. Discarding album quality grades...");
+        let _ = db.collection::<AlbumQualityRecord>("album_quality").delete_many(bson::doc! {});
+        let _ = coll.delete_many(bson::doc! {});
+        let _ = coll.insert_one(LibraryVersion { version });
+        if let Some(qualities) = self.album_quality.borrow_mut().as_mut() {
+            qualities.clear();
+        }
+    }
+
     /// Convenience method to check whether album art for a given album is locally available,
     /// and if not, queue its downloading from MPD.
     /// If MPD doesn't have one locally, we'll try fetching from all the enabled metadata providers.
     pub fn ensure_cached_album_art(&self, album: &AlbumInfo, thumbnail: bool) {
+        self.ensure_cached_album_art_inner(album, thumbnail, false);
+    }
+
+    /// Prefetched album arts are only downloaded remotely when nothing on screen needs fetching.
+    fn ensure_cached_album_art_inner(&self, album: &AlbumInfo, thumbnail: bool, prefetch: bool) {
         let folder_uri = album.uri.to_owned();
         let stretto_key = (format!("uri:{}", &folder_uri), thumbnail);
         if let Some(_) = IMAGE_CACHE.get(&stretto_key) {
@@ -423,40 +1376,48 @@ impl Cache {
         }
         let thumbnail_path = self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), true));
         let path = self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), false));
-        let bg_sender = self.bg_sender.clone();
+        let bg_sender = if prefetch {self.prefetch_sender.clone()} else {self.bg_sender.clone()};
         let fg_sender = self.fg_sender.clone();
         if let (Some(sender), Ok(bson_key)) = (self.mpd_sender.get().cloned(), self.get_album_key(album)) {
+            let hints = self.get_album_hints(album);
+            let image_override = load_meta_override(&self.doc_cache, "album", &bson_key).and_then(|record| record.image);
             gio::spawn_blocking(move || {
                 let settings = settings_manager().child("client");
                 // First, try to load from disk. Do this using the threadpool to avoid blocking UI.
                 let path_to_use = if thumbnail {&thumbnail_path} else {&path};
-                if path_to_use.exists() {
+                if path_to_use.exists() || images::regenerate_from_older(path_to_use) {
                     if let Ok(tex) = Texture::from_filename(&path_to_use) {
-                        IMAGE_CACHE.insert(stretto_key, tex.clone(), if thumbnail {1} else {16});
-                        IMAGE_CACHE.wait().unwrap();
+                        touch(path_to_use);
+                        cache_texture(stretto_key, tex);
                         let _ = fg_sender.send_blocking(Metadata::AlbumArt(folder_uri, thumbnail));
                     }
                 }
+                // Regenerate from the file chosen by the user, if any
+                else if let Some(file) = image_override {
+                    if save_image_from_file(&file, &path, &thumbnail_path) {
+                        let _ = fg_sender.send_blocking(Metadata::AlbumArt(folder_uri, false));
+                    }
+                }
                 // That failed, so try downloading it
                 else if settings.boolean("mpd-download-album-art") {
                     let _ = sender.send_blocking(MpdMessage::AlbumArt(
                         folder_uri.to_string(),
                         bson_key,
                         path,
-                        thumbnail_path
+                        thumbnail_path,
+                        prefetch
                     ));
                 }
                 else {
                     // Hop straight to remote providers. For this we'll need to have album metas ready,
                     // so schedule that first.
-                    let _ = bg_sender.send_blocking(CacheTask::AlbumMeta(folder_uri.clone(), bson_key.clone()));
+                    let _ = bg_sender.send_blocking(CacheTask::AlbumMeta(folder_uri.clone(), bson_key.clone(), hints, false));
                     let _ = bg_sender.send_blocking(CacheTask::AlbumArt(folder_uri, bson_key, path, thumbnail_path));
                 }
             });
         }
     }
 
-    // TODO: GUI for downloading album arts from external providers.
     /// Batched version of ensure_cached_album_art.
     /// The list of folder-level URIs will be deduplicated internally to avoid fetching the same
     /// album art multiple times. This is useful for fetching album arts of songs in the queue,
@@ -469,7 +1430,7 @@ impl Cache {
             let folder_uri = &album.uri;
             if seen.insert(folder_uri.to_owned()) {
                 // println!("ensure_cached_album_arts ({}): calling ensure_cached_album_art", &folder_uri);
-                self.ensure_cached_album_art(album, false);
+                self.ensure_cached_album_art_inner(album, false, true);
             }
         }
     }
@@ -499,6 +1460,21 @@ impl Cache {
         }
     }
 
+    /// Extra information about a local album that helps providers tell apart similar
+    /// releases when searching. Only includes what is already known.
+    fn get_album_hints(&self, album: &AlbumInfo) -> bson::Document {
+        let mut hints = bson::Document::new();
+        if let Some(date) = album.release_date.as_ref() {
+            hints.insert("year", date.year());
+        }
+        if let Some(quality) = self.load_album_quality(album, false) {
+            if quality.track_count > 0 {
+                hints.insert("track_count", quality.track_count as i32);
+            }
+        }
+        hints
+    }
+
     pub fn load_cached_album_meta(
         &self,
         album: &AlbumInfo,
@@ -506,22 +1482,36 @@ impl Cache {
         // Check whether we have this album cached
         if let Ok(key) = self.get_album_key(album) {
             println!("Key is valid");
-            let result = self.doc_cache.read().unwrap().collection::<models::AlbumMeta>("album").find_one(key);
-            if let Ok(res) = result {
-                if let Some(info) = res {
-                    println!("Album info cache hit!");
-                    return Some(info);
-                }
-                println!("Album info cache miss");
-                return None;
+            let result: Option<models::AlbumMeta> = find_cached(&self.doc_cache, "album", key);
+            if let Some(info) = result {
+                println!("Album info cache hit!");
+                return Some(info);
             }
-            println!("{:?}", result.err());
+            println!("Album info cache miss");
             return None;
         }
         println!("No key!");
         None
     }
 
+    /// Look up cached metadata of many albums at once on a blocking thread. Albums with
+    /// nothing cached are left out of the result, which pairs metadata with folder URIs.
+    pub fn load_cached_album_metas(
+        &self,
+        albums: &[AlbumInfo]
+    ) -> gio::JoinHandle<Vec<(String, models::AlbumMeta)>> {
+        let keys: Vec<(String, bson::Document)> = albums
+            .iter()
+            .filter_map(|album| self.get_album_key(album).ok().map(|key| (album.uri.clone(), key)))
+            .collect();
+        let doc_cache = self.doc_cache.clone();
+        gio::spawn_blocking(move || {
+            keys.into_iter()
+                .filter_map(|(uri, key)| find_cached::<models::AlbumMeta>(&doc_cache, "album", key).map(|meta| (uri, meta)))
+                .collect()
+        })
+    }
+
     pub fn ensure_cached_album_meta(
         &self,
         album: &AlbumInfo,
@@ -530,18 +1520,21 @@ impl Cache {
         let folder_uri: &str = &album.uri;
         // Check whether we have this album cached
         if let Ok(key) = self.get_album_key(album) {
-            let result = self.doc_cache.read().unwrap().collection::<models::AlbumMeta>("album").find_one(key.clone());
-            if let Ok(response) = result {
-                if response.is_none() {
-                    self.bg_sender.send_blocking(CacheTask::AlbumMeta(folder_uri.to_owned(), key)).expect("Cache child thread: unable to notify main thread of task completion");
-                }
-            }
-            else {
-                println!("{:?}", result.err());
+            let response: Option<models::AlbumMeta> = find_cached(&self.doc_cache, "album", key.clone());
+            if response.is_none() || is_meta_stale(load_meta_status(&self.doc_cache, "album", &key).as_ref()) {
+                self.bg_sender.send_blocking(CacheTask::AlbumMeta(folder_uri.to_owned(), key, self.get_album_hints(album), false)).expect("Cache child thread: unable to notify main thread of task completion");
             }
         }
     }
 
+    /// Fetch an album's metadata again, even if the cached one hasn't expired. Listen to
+    /// album-meta-downloaded for the result.
+    pub fn refresh_album_meta(&self, album: &AlbumInfo) {
+        if let Ok(key) = self.get_album_key(album) {
+            let _ = self.bg_sender.send_blocking(CacheTask::AlbumMeta(album.uri.to_owned(), key, self.get_album_hints(album), true));
+        }
+    }
+
     fn get_artist_key(
         &self,
         artist: &ArtistInfo
@@ -568,16 +1561,12 @@ impl Cache {
         artist: &ArtistInfo
     ) -> Option<ArtistMeta> {
         if let Ok(key) = self.get_artist_key(artist) {
-            let result = self.doc_cache.read().unwrap().collection::<ArtistMeta>("artist").find_one(key);
-            if let Ok(res) = result {
-                if let Some(info) = res {
-                    println!("Artist info cache hit!");
-                    return Some(info);
-                }
-                println!("Artist info cache miss");
-                return None;
+            let result: Option<ArtistMeta> = find_cached(&self.doc_cache, "artist", key);
+            if let Some(info) = result {
+                println!("Artist info cache hit!");
+                return Some(info);
             }
-            println!("{:?}", result.err());
+            println!("Artist info cache miss");
             return None;
         }
         println!("No key!");
@@ -590,17 +1579,180 @@ impl Cache {
     ) {
         // Check whether we have this artist cached
         if let Ok(key) = self.get_artist_key(artist) {
-            let result = self.doc_cache.read().unwrap().collection::<ArtistMeta>("artist").find_one(key.clone());
-            if let Ok(response) = result {
+            let response: Option<ArtistMeta> = find_cached(&self.doc_cache, "artist", key.clone());
+            if response.is_none() || is_meta_stale(load_meta_status(&self.doc_cache, "artist", &key).as_ref()) {
                 let path = self.get_path_for(&Metadata::ArtistAvatar(artist.name.to_owned(), false));
                 let thumbnail_path = self.get_path_for(&Metadata::ArtistAvatar(artist.name.to_owned(), true));
-                if response.is_none() {
-                    let _ = self.bg_sender.send_blocking(CacheTask::ArtistMeta(key, path, thumbnail_path));
-                }
+                let _ = self.bg_sender.send_blocking(CacheTask::ArtistMeta(key, path, thumbnail_path, false));
             }
-            else {
-                println!("{:?}", result.err());
+        }
+    }
+
+    /// Fetch an artist's metadata & avatar again, even if the cached ones haven't expired.
+    /// Listen to artist-meta-downloaded & artist-avatar-downloaded for the results.
+    pub fn refresh_artist_meta(&self, artist: &ArtistInfo) {
+        if let Ok(key) = self.get_artist_key(artist) {
+            let path = self.get_path_for(&Metadata::ArtistAvatar(artist.name.to_owned(), false));
+            let thumbnail_path = self.get_path_for(&Metadata::ArtistAvatar(artist.name.to_owned(), true));
+            let _ = self.bg_sender.send_blocking(CacheTask::ArtistMeta(key, path, thumbnail_path, true));
+        }
+    }
+
+    /// Search all providers for possible matches of an album, for the user to pick from.
+    /// Listen to album-candidates-downloaded, then call take_album_candidates.
+    pub fn search_album_candidates(&self, album: &AlbumInfo) {
+        if let Ok(mut key) = self.get_album_key(album) {
+            // Search by name even if the album has an MBID, since that might be the wrong one.
+            key.insert("name", album.title.clone());
+            if let Some(artist) = album.get_artist_tag() {
+                key.insert("artist", artist.to_owned());
+            }
+            key.remove("mbid");
+            key.extend(self.get_album_hints(album));
+            let _ = self.bg_sender.send_blocking(CacheTask::AlbumCandidates(album.uri.to_owned(), key));
+        }
+    }
+
+    pub fn take_album_candidates(&self, album: &AlbumInfo) -> Option<Vec<models::MatchCandidate>> {
+        self.candidates.borrow_mut().remove(&format!("album:{}", &album.uri))
+    }
+
+    /// Search all providers for possible matches of an artist, for the user to pick from.
+    /// Listen to artist-candidates-downloaded, then call take_artist_candidates.
+    pub fn search_artist_candidates(&self, artist: &ArtistInfo) {
+        let key = bson::doc! {"name": artist.name.clone()};
+        let _ = self.bg_sender.send_blocking(CacheTask::ArtistCandidates(artist.name.to_owned(), key));
+    }
+
+    pub fn take_artist_candidates(&self, artist: &ArtistInfo) -> Option<Vec<models::MatchCandidate>> {
+        self.candidates.borrow_mut().remove(&format!("artist:{}", &artist.name))
+    }
+
+    /// Always use the given candidate for this album from now on, then fetch its metadata again.
+    pub fn pin_album_match(&self, album: &AlbumInfo, candidate: &models::MatchCandidate) {
+        if let Ok(key) = self.get_album_key(album) {
+            let has_image = load_meta_override(&self.doc_cache, "album", &key).is_some_and(|record| record.image.is_some());
+            update_meta_override(&self.doc_cache, "album", &key, |record| {
+                record.pin = candidate.pin.clone();
+            });
+            self.refresh_album_meta(album);
+            if has_image {
+                // The image chosen by the user still takes precedence.
+                return;
+            }
+            // The cached art came from the old match & would otherwise be kept.
+            let folder_uri = album.uri.to_owned();
+            for thumbnail in [false, true] {
+                let _ = std::fs::remove_file(self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), thumbnail)));
+                remove_texture(&(format!("uri:{}", &folder_uri), thumbnail));
+            }
+            // Queued after the metadata refresh, so it downloads the new match's art.
+            let path = self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), false));
+            let thumbnail_path = self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), true));
+            let _ = self.bg_sender.send_blocking(CacheTask::AlbumArt(folder_uri, key, path, thumbnail_path));
+        }
+    }
+
+    /// Always use the given candidate for this artist from now on, then fetch its metadata again.
+    pub fn pin_artist_match(&self, artist: &ArtistInfo, candidate: &models::MatchCandidate) {
+        if let Ok(key) = self.get_artist_key(artist) {
+            update_meta_override(&self.doc_cache, "artist", &key, |record| {
+                record.pin = candidate.pin.clone();
+            });
+            self.refresh_artist_meta(artist);
+        }
+    }
+
+    /// Use a local image file as this album's art from now on. Listen to album-art-downloaded
+    /// for the result.
+    pub fn set_album_art_file(&self, album: &AlbumInfo, file: PathBuf) {
+        let Ok(key) = self.get_album_key(album) else {
+            return;
+        };
+        let file = file.to_string_lossy().to_string();
+        update_meta_override(&self.doc_cache, "album", &key, |record| {
+            record.image = Some(file.clone());
+        });
+        let folder_uri = album.uri.to_owned();
+        let path = self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), false));
+        let thumbnail_path = self.get_path_for(&Metadata::AlbumArt(folder_uri.clone(), true));
+        let fg_sender = self.fg_sender.clone();
+        gio::spawn_blocking(move || {
+            if save_image_from_file(&file, &path, &thumbnail_path) {
+                remove_texture(&(format!("uri:{}", &folder_uri), false));
+                remove_texture(&(format!("uri:{}", &folder_uri), true));
+                let _ = fg_sender.send_blocking(Metadata::AlbumArt(folder_uri, false));
+            }
+        });
+    }
+
+    /// Use a local image file as this artist's avatar from now on. Listen to
+    /// artist-avatar-downloaded for the result.
+    pub fn set_artist_avatar_file(&self, artist: &ArtistInfo, file: PathBuf) {
+        let Ok(key) = self.get_artist_key(artist) else {
+            return;
+        };
+        let file = file.to_string_lossy().to_string();
+        update_meta_override(&self.doc_cache, "artist", &key, |record| {
+            record.image = Some(file.clone());
+        });
+        let name = artist.name.to_owned();
+        let path = self.get_path_for(&Metadata::ArtistAvatar(name.clone(), false));
+        let thumbnail_path = self.get_path_for(&Metadata::ArtistAvatar(name.clone(), true));
+        let fg_sender = self.fg_sender.clone();
+        gio::spawn_blocking(move || {
+            if save_image_from_file(&file, &path, &thumbnail_path) {
+                remove_texture(&(format!("artist:{}", &name), false));
+                remove_texture(&(format!("artist:{}", &name), true));
+                let _ = fg_sender.send_blocking(Metadata::ArtistAvatar(name, false));
             }
+        });
+    }
+
+    /// Whether the user has pinned a match or chosen an image for this album.
+    pub fn has_album_override(&self, album: &AlbumInfo) -> bool {
+        self.get_album_key(album)
+            .ok()
+            .and_then(|key| load_meta_override(&self.doc_cache, "album", &key))
+            .is_some()
+    }
+
+    pub fn has_artist_override(&self, artist: &ArtistInfo) -> bool {
+        self.get_artist_key(artist)
+            .ok()
+            .and_then(|key| load_meta_override(&self.doc_cache, "artist", &key))
+            .is_some()
+    }
+
+    /// Forget the user's choices for this album & go back to automatic matching.
+    pub fn clear_album_override(&self, album: &AlbumInfo) {
+        if let Ok(key) = self.get_album_key(album) {
+            let had_image = load_meta_override(&self.doc_cache, "album", &key).is_some_and(|record| record.image.is_some());
+            update_meta_override(&self.doc_cache, "album", &key, |record| {
+                record.pin = bson::Document::new();
+                record.image = None;
+            });
+            if had_image {
+                // Drop the chosen image so that the usual sources are tried again.
+                for thumbnail in [false, true] {
+                    let _ = std::fs::remove_file(self.get_path_for(&Metadata::AlbumArt(album.uri.to_owned(), thumbnail)));
+                    remove_texture(&(format!("uri:{}", &album.uri), thumbnail));
+                }
+                self.ensure_cached_album_art(album, false);
+            }
+            self.refresh_album_meta(album);
+        }
+    }
+
+    /// Forget the user's choices for this artist & go back to automatic matching.
+    pub fn clear_artist_override(&self, artist: &ArtistInfo) {
+        if let Ok(key) = self.get_artist_key(artist) {
+            update_meta_override(&self.doc_cache, "artist", &key, |record| {
+                record.pin = bson::Document::new();
+                record.image = None;
+            });
+            // Refreshing replaces the avatar too.
+            self.refresh_artist_meta(artist);
         }
     }
 
@@ -624,17 +1776,180 @@ impl Cache {
         let fg_sender = self.fg_sender.clone();
         let content_type = Metadata::ArtistAvatar(name.to_owned(), thumbnail);
         let path = self.get_path_for(&content_type);
+        let image_override = self.get_artist_key(artist)
+            .ok()
+            .and_then(|key| load_meta_override(&self.doc_cache, "artist", &key))
+            .and_then(|record| record.image);
+        let (hires_path, thumbnail_path) = (
+            self.get_path_for(&Metadata::ArtistAvatar(name.to_owned(), false)),
+            self.get_path_for(&Metadata::ArtistAvatar(name.to_owned(), true))
+        );
         // If missed, try loading from disk
         gio::spawn_blocking(move || {
             // Try to load from disk. Do this using the threadpool to avoid blocking UI.
-            if path.exists() {
+            if path.exists() || images::regenerate_from_older(&path) {
                 if let Ok(tex) = Texture::from_filename(&path) {
-                    IMAGE_CACHE.insert(stretto_key, tex.clone(), if thumbnail {1} else {16});
-                    IMAGE_CACHE.wait().unwrap();
+                    touch(&path);
+                    cache_texture(stretto_key, tex);
+                    let _ = fg_sender.send_blocking(content_type);
+                }
+            }
+            // Regenerate from the file chosen by the user, if any
+            else if let Some(file) = image_override {
+                if save_image_from_file(&file, &hires_path, &thumbnail_path) {
                     let _ = fg_sender.send_blocking(content_type);
                 }
             }
         });
         None
     }
+
+    /// Get the full discography of an artist by MBID, even if it is due for a refresh.
+    pub fn load_cached_artist_discography(&self, mbid: &str) -> Option<Vec<models::ReleaseGroupMeta>> {
+        find_cached::<DiscographyRecord>(&self.doc_cache, "discography", bson::doc! {"mbid": mbid})
+            .map(|record| record.release_groups)
+    }
+
+    /// Schedule fetching an artist's discography if we don't have it yet or ours
+    /// has expired. Listen to artist-discography-downloaded for the result.
+    pub fn ensure_cached_artist_discography(&self, mbid: &str) {
+        let ttl_days = meta_provider_settings("musicbrainz").uint("discography-ttl-days") as i64;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let fresh = find_cached::<DiscographyRecord>(&self.doc_cache, "discography", bson::doc! {"mbid": mbid})
+            .is_some_and(|record| now - record.fetched < ttl_days * 86400);
+        if !fresh && self.discography_pending.borrow_mut().insert(mbid.to_owned()) {
+            let _ = self.bg_sender.send_blocking(CacheTask::ArtistDiscography(mbid.to_owned()));
+        }
+    }
+
+    /// Get a thumbnail of an image that is not tied to anything in the library. On a
+    /// miss, load it from disk or optionally download it. Listen to remote-image-downloaded
+    /// for the result.
+    pub fn load_cached_remote_image(&self, url: &str, schedule: bool) -> Option<Texture> {
+        let stretto_key = (format!("remote:{}", url), true);
+        if let Some(tex) = IMAGE_CACHE.get(&stretto_key) {
+            return Some(tex.value().clone());
+        }
+        let content_type = Metadata::RemoteImage(url.to_owned());
+        let path = self.get_path_for(&content_type);
+        if path.exists() {
+            let fg_sender = self.fg_sender.clone();
+            gio::spawn_blocking(move || {
+                if let Ok(tex) = Texture::from_filename(&path) {
+                    touch(&path);
+                    cache_texture(stretto_key, tex);
+                    let _ = fg_sender.send_blocking(content_type);
+                }
+            });
+        }
+        else if schedule && self.remote_image_pending.borrow_mut().insert(url.to_owned()) {
+            let _ = self.bg_sender.send_blocking(CacheTask::RemoteImage(url.to_owned(), path));
+        }
+        None
+    }
+
+    /// Count one more play of the given song in the background.
+    pub fn record_play(&self, uri: &str) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        if let Some(recent) = self.recent_plays.borrow_mut().as_mut() {
+            if let Some(pos) = recent.iter().position(|played| played == uri) {
+                recent.remove(pos);
+            }
+            recent.push_front(uri.to_owned());
+        }
+        let doc_cache = self.doc_cache.clone();
+        let uri = uri.to_owned();
+        gio::spawn_blocking(move || {
+            let db = doc_cache.write().unwrap();
+            let history = db.collection::<PlayRecord>("history");
+            let res = history.update_one(
+                bson::doc! {"uri": &uri},
+                bson::doc! {
+                    "$inc": {"play_count": 1},
+                    "$set": {"last_played": now}
+                }
+            );
+            match res {
+                Ok(update) if update.matched_count > 0 => {}
+                Ok(_) => {
+                    let _ = history.insert_one(PlayRecord {
+                        uri,
+                        play_count: 1,
+                        last_played: now
+                    });
+                }
+                Err(e) => println!("[Cache] Failed to record play of {uri}: {e:?}")
+            }
+        });
+    }
+
+    /// Get play counts of all songs that have been played at least once, keyed by URI.
+    pub fn load_play_counts(&self) -> FxHashMap<String, u32> {
+        let mut res = FxHashMap::default();
+        if let Ok(cursor) = self.doc_cache.read().unwrap().collection::<PlayRecord>("history").find(None) {
+            for record in cursor.flatten() {
+                res.insert(record.uri, record.play_count);
+            }
+        }
+        res
+    }
+
+    /// Read playback history into memory in the background, newest first.
+    fn load_play_history(self: Rc<Self>) {
+        let doc_cache = self.doc_cache.clone();
+        let this = self;
+        glib::MainContext::default().spawn_local(async move {
+            let recent: VecDeque<String> = gio::spawn_blocking(move || {
+                let mut records: Vec<PlayRecord> = Vec::new();
+                if let Ok(cursor) = doc_cache.read().unwrap().collection::<PlayRecord>("history").find(None) {
+                    records.extend(cursor.flatten());
+                }
+                records.sort_by(|a, b| b.last_played.cmp(&a.last_played));
+                records.into_iter().map(|record| record.uri).collect()
+            }).await.unwrap_or_default();
+            let _ = this.recent_plays.replace(Some(recent));
+        });
+    }
+
+    /// Get URIs of the most recently played songs, newest first. Empty until
+    /// playback history has been loaded.
+    pub fn load_recent_plays(&self, limit: usize) -> Vec<String> {
+        self.recent_plays
+            .borrow()
+            .as_ref()
+            .map(|recent| recent.iter().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Save a smart playlist definition, replacing any existing one with the same name.
+    pub fn save_smart_playlist(&self, playlist: &SmartPlaylist) {
+        let db = self.doc_cache.write().unwrap();
+        let coll = db.collection::<SmartPlaylist>("smart_playlist");
+        let _ = coll.delete_one(bson::doc! {"name": &playlist.name});
+        if let Err(e) = coll.insert_one(playlist.clone()) {
+            println!("[Cache] Failed to save smart playlist {}: {e:?}", &playlist.name);
+        }
+    }
+
+    pub fn delete_smart_playlist(&self, name: &str) {
+        let _ = self.doc_cache
+            .write()
+            .unwrap()
+            .collection::<SmartPlaylist>("smart_playlist")
+            .delete_one(bson::doc! {"name": name});
+    }
+
+    /// Get all saved smart playlist definitions, sorted by name.
+    pub fn load_smart_playlists(&self) -> Vec<SmartPlaylist> {
+        let mut res: Vec<SmartPlaylist> = Vec::new();
+        if let Ok(cursor) = self.doc_cache.read().unwrap().collection::<SmartPlaylist>("smart_playlist").find(None) {
+            res.extend(cursor.flatten());
+        }
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        res
+    }
 }
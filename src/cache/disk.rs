@@ -0,0 +1,199 @@
+// Disk usage accounting for the cache folder, plus a size limit enforced by
+// removing the least recently used images first.
+// Many filesystems are mounted with noatime or relatime, so access times cannot be
+// trusted to update by themselves. The cache controller bumps them explicitly (see
+// touch) whenever an image is read back from disk.
+// Metadata documents live in a single PoloDB file, which cannot be trimmed this way.
+// They still count towards the limit, leaving images whatever space they don't take up,
+// but can only be cleared as a whole.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime
+};
+
+use super::images::is_thumbnail;
+
+/// Kinds of cached data, reported & cleared separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheCategory {
+    // Full-size album arts & their kept originals, including images not tied to the library
+    AlbumArt,
+    // Full-size artist avatars & their kept originals
+    Avatars,
+    // Small versions of both of the above
+    Thumbnails,
+    // Wikis, bios, tags & other fetched documents
+    Metadata
+}
+
+/// Bytes taken up on disk by each category.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DiskUsage {
+    pub album_art: u64,
+    pub avatars: u64,
+    pub thumbnails: u64,
+    pub metadata: u64
+}
+
+impl DiskUsage {
+    pub fn total(&self) -> u64 {
+        self.album_art + self.avatars + self.thumbnails + self.metadata
+    }
+}
+
+struct CachedImage {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime
+}
+
+fn list_images(folder: &Path) -> Vec<CachedImage> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let accessed = meta.accessed().unwrap_or(SystemTime::UNIX_EPOCH);
+            Some(CachedImage {
+                path: entry.path(),
+                size: meta.len(),
+                last_used: modified.max(accessed)
+            })
+        })
+        .collect()
+}
+
+/// Size of a file, or of everything inside a folder.
+fn size_of(path: &Path) -> u64 {
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .map(|entries| entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| size_of(&entry.path()))
+                .sum()
+            )
+            .unwrap_or(0),
+        Ok(meta) => meta.len(),
+        Err(_) => 0
+    }
+}
+
+/// Mark a cached image as just used, so that it is evicted last.
+pub fn touch(path: &Path) {
+    let res = fs::File::options()
+        .append(true)
+        .open(path)
+        .and_then(|file| file.set_times(fs::FileTimes::new().set_accessed(SystemTime::now())));
+    if let Err(e) = res {
+        println!("[Cache] Could not update access time of {:?}: {:?}", path, e);
+    }
+}
+
+/// Locations of everything the cache writes to disk. Cheap to clone & safe to use from
+/// blocking threads.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    pub albumart_path: PathBuf,
+    pub avatar_path: PathBuf,
+    pub remote_image_path: PathBuf,
+    pub doc_path: PathBuf
+}
+
+impl DiskCache {
+    fn image_folders(&self) -> [&Path; 3] {
+        [&self.albumart_path, &self.avatar_path, &self.remote_image_path]
+    }
+
+    pub fn usage(&self) -> DiskUsage {
+        let mut usage = DiskUsage::default();
+        for folder in self.image_folders() {
+            for image in list_images(folder) {
+                if is_thumbnail(&image.path) {
+                    usage.thumbnails += image.size;
+                }
+                else if folder == self.avatar_path {
+                    usage.avatars += image.size;
+                }
+                else {
+                    usage.album_art += image.size;
+                }
+            }
+        }
+        // PoloDB may keep a journal next to the main file.
+        if let (Some(parent), Some(name)) = (self.doc_path.parent(), self.doc_path.file_name()) {
+            if let Ok(entries) = fs::read_dir(parent) {
+                usage.metadata = entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_name().to_string_lossy().starts_with(&*name.to_string_lossy()))
+                    .map(|entry| size_of(&entry.path()))
+                    .sum();
+            }
+        }
+        usage
+    }
+
+    /// Remove the least recently used images until everything fits within max_bytes.
+    /// Returns the number of images removed.
+    pub fn enforce_limit(&self, max_bytes: u64) -> usize {
+        let usage = self.usage();
+        if usage.total() <= max_bytes {
+            return 0;
+        }
+        // Evicting images cannot make up for metadata alone taking up the whole budget.
+        if usage.metadata >= max_bytes {
+            println!(
+                "[Cache] Metadata alone takes up {}, more than the disk cache limit. Not evicting images.",
+                gtk::glib::format_size(usage.metadata)
+            );
+            return 0;
+        }
+        let image_budget = max_bytes - usage.metadata;
+        let mut total = usage.total() - usage.metadata;
+        let mut images: Vec<CachedImage> = self.image_folders()
+            .into_iter()
+            .flat_map(list_images)
+            .collect();
+        images.sort_by_key(|image| image.last_used);
+        let mut removed = 0;
+        for image in images.into_iter() {
+            if total <= image_budget {
+                break;
+            }
+            if fs::remove_file(&image.path).is_ok() {
+                total = total.saturating_sub(image.size);
+                removed += 1;
+            }
+        }
+        println!(
+            "[Cache] Evicted {} images to fit disk cache within {}",
+            removed,
+            gtk::glib::format_size(max_bytes)
+        );
+        removed
+    }
+
+    /// Delete all images of the given category. Clearing album arts or avatars removes
+    /// their thumbnails too. Metadata documents are cleared through the database instead.
+    pub fn clear_images(&self, category: CacheCategory) {
+        let folders: Vec<&Path> = match category {
+            CacheCategory::AlbumArt => vec![&self.albumart_path, &self.remote_image_path],
+            CacheCategory::Avatars => vec![&self.avatar_path],
+            CacheCategory::Thumbnails => self.image_folders().to_vec(),
+            CacheCategory::Metadata => Vec::new()
+        };
+        for folder in folders.into_iter() {
+            for image in list_images(folder) {
+                if category != CacheCategory::Thumbnails || is_thumbnail(&image.path) {
+                    let _ = fs::remove_file(&image.path);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+// Writing cached album arts back into the music library as cover.jpg, so that other
+// clients & devices reading the same folders can use them too.
+// Album folders are found by appending MPD's folder URIs to the local provider's
+// music-root setting, so this only works when the music folder is reachable from this
+// machine. The best available version of each art is exported: the untouched original
+// if keep-original-images was on when it was fetched, or the high-resolution cached
+// version otherwise. The latter is downscaled to hires-image-size & recompressed, which
+// reports point out. Originals already in JPEG are copied as-is & others re-encoded.
+use std::{
+    fs,
+    path::{Path, PathBuf}
+};
+
+use super::images::{can_regenerate, find_original, regenerate_from_older, save_full_size};
+
+const COVER_FILE_NAME: &str = "cover.jpg";
+
+/// What to do with albums whose folders already have a cover.jpg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportPolicy {
+    Skip,
+    Overwrite
+}
+
+/// What happened (or would happen in a dry run) to one album.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportOutcome {
+    // A new cover.jpg was written
+    Written,
+    // An existing cover.jpg was replaced
+    Overwritten,
+    // The folder already has a cover.jpg & the policy says to keep it
+    Skipped,
+    // Nothing has been fetched for this album yet
+    NoArt,
+    // The album's folder could not be found under the music root
+    NoFolder,
+    // Writing failed, most likely due to permissions
+    Failed
+}
+
+impl ExportOutcome {
+    // TODO: translatable
+    pub fn describe(&self, dry_run: bool) -> &'static str {
+        match (self, dry_run) {
+            (Self::Written, false) => "Written",
+            (Self::Written, true) => "Will be written",
+            (Self::Overwritten, false) => "Replaced existing cover",
+            (Self::Overwritten, true) => "Will replace existing cover",
+            (Self::Skipped, _) => "Already has a cover",
+            (Self::NoArt, _) => "No cover art fetched yet",
+            (Self::NoFolder, _) => "Folder not found in music folder",
+            (Self::Failed, _) => "Could not write cover"
+        }
+    }
+
+    pub fn is_write(&self) -> bool {
+        matches!(self, Self::Written | Self::Overwritten)
+    }
+}
+
+/// One album to export, with everything needed to do so from a blocking thread.
+#[derive(Debug, Clone)]
+pub struct ExportItem {
+    pub title: String,
+    pub folder_uri: String,
+    // Path of the high-resolution cached version
+    pub hires_path: PathBuf
+}
+
+/// What happened to one album of an export.
+#[derive(Debug, Clone)]
+pub struct ExportEntry {
+    pub title: String,
+    pub folder_uri: String,
+    pub outcome: ExportOutcome,
+    // Whether the written cover is the resized cached version, as no original was kept
+    pub resized: bool
+}
+
+/// Outcome of every album in an export, in the order they were given.
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    pub dry_run: bool,
+    pub entries: Vec<ExportEntry>
+}
+
+impl ExportReport {
+    pub fn count(&self, outcome: ExportOutcome) -> usize {
+        self.entries.iter().filter(|entry| entry.outcome == outcome).count()
+    }
+
+    /// Number of covers written (or to be) from resized cached versions.
+    pub fn resized(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.resized).count()
+    }
+}
+
+/// Find the album's folder, refusing URIs that would point at the music root itself or
+/// outside of it.
+fn album_folder(root: &Path, folder_uri: &str) -> Option<PathBuf> {
+    let uri = folder_uri.trim_matches('/');
+    if uri.is_empty() || uri.split('/').any(|part| part == "..") {
+        return None;
+    }
+    let folder = root.join(uri);
+    if folder.is_dir() {
+        Some(folder)
+    }
+    else {
+        None
+    }
+}
+
+/// Write the best version of a cached art to the given path. Writes to a temporary file
+/// first so that an existing cover is never left half-overwritten.
+fn write_cover(hires_path: &Path, dest: &Path) -> bool {
+    let tmp = dest.with_extension("jpg.part");
+    let written = match find_original(hires_path) {
+        Some(original) if original.extension().is_some_and(|ext| ext == "jpg" || ext == "jpeg") => {
+            fs::copy(&original, &tmp)
+                .map_err(|e| println!("[Cache] Could not copy {:?}: {:?}", &original, e))
+                .is_ok()
+        }
+        Some(original) => match image::open(&original) {
+            Ok(image) => save_full_size(&image, &tmp),
+            Err(e) => {
+                println!("[Cache] Could not read {:?}: {:?}", &original, e);
+                false
+            }
+        }
+        None => fs::copy(hires_path, &tmp)
+            .map_err(|e| println!("[Cache] Could not copy {:?}: {:?}", hires_path, e))
+            .is_ok()
+    };
+    if written {
+        if let Err(e) = fs::rename(&tmp, dest) {
+            println!("[Cache] Could not write {:?}: {:?}", dest, e);
+        }
+        else {
+            return true;
+        }
+    }
+    let _ = fs::remove_file(&tmp);
+    false
+}
+
+/// Export one album's art into its folder, or only work out what would happen if
+/// dry_run is true.
+fn export_one(root: Option<&Path>, item: &ExportItem, policy: ExportPolicy, dry_run: bool) -> ExportOutcome {
+    let Some(folder) = root.and_then(|root| album_folder(root, &item.folder_uri)) else {
+        return ExportOutcome::NoFolder;
+    };
+    let dest = folder.join(COVER_FILE_NAME);
+    let exists = dest.exists();
+    if exists && policy == ExportPolicy::Skip {
+        return ExportOutcome::Skipped;
+    }
+    // Outdated high-resolution versions might still be recoverable. Dry runs only check
+    // whether they are, so as not to touch the cache either.
+    let has_art = find_original(&item.hires_path).is_some()
+        || item.hires_path.exists()
+        || if dry_run {
+            can_regenerate(&item.hires_path)
+        }
+        else {
+            regenerate_from_older(&item.hires_path)
+        };
+    if !has_art {
+        return ExportOutcome::NoArt;
+    }
+    if !dry_run && !write_cover(&item.hires_path, &dest) {
+        return ExportOutcome::Failed;
+    }
+    if exists {
+        ExportOutcome::Overwritten
+    }
+    else {
+        ExportOutcome::Written
+    }
+}
+
+/// Export the arts of the given albums into their folders under the music root.
+pub fn export_covers(root: Option<&Path>, items: &[ExportItem], policy: ExportPolicy, dry_run: bool) -> ExportReport {
+    let entries = items
+        .iter()
+        .map(|item| {
+            let outcome = export_one(root, item, policy, dry_run);
+            ExportEntry {
+                title: item.title.clone(),
+                folder_uri: item.folder_uri.clone(),
+                outcome,
+                resized: outcome.is_write() && find_original(&item.hires_path).is_none()
+            }
+        })
+        .collect();
+    ExportReport {dry_run, entries}
+}
@@ -0,0 +1,306 @@
+// Naming, encoding & regeneration of cached image files.
+// File names record the size each image was resized to (see utils::cached_image_sizes):
+// - {hash}_{size}.jpg for high-resolution versions,
+// - {hash}_thumb_{size}.jpg for thumbnails,
+// - {hash}_original.{format} for the untouched downloaded bytes, if keep-original-images
+//   is on. These are never resized or deleted as outdated.
+// Both resized versions are lossy JPEGs, as most album arts are photographic & would
+// take several times more space as PNGs. Older versions cached them as PNGs, named
+// {hash}.png & {hash}_thumb.png before sizes were recorded.
+// When the sizes or format change, images are re-derived from the originals if kept,
+// or from the high-resolution versions already on disk otherwise. The latter are only
+// ever scaled down, so raising their size then only affects newly-fetched images.
+// Thumbnail sizes also follow the display's scale factor, but moving between screens
+// must not rewrite the whole cache. Thumbnails for a new scale are therefore only made
+// when first needed, & those of other scales kept until the size settings change.
+use std::{
+    fs,
+    io::BufWriter,
+    path::{Path, PathBuf}
+};
+use gtk::prelude::*;
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageFormat, RgbImage};
+use rustc_hash::FxHashMap;
+
+use crate::utils::{cached_image_sizes, resize_convert_image, settings_manager, unscaled_image_sizes};
+
+// Extension of the current format & the one used before it
+const EXTENSION: &str = "jpg";
+const LEGACY_EXTENSION: &str = "png";
+const HIRES_QUALITY: u8 = 90;
+// Thumbnails are small enough that artifacts are hard to spot.
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// What a cached image's file name says about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ImageName {
+    thumbnail: bool,
+    // None for files cached before sizes were recorded
+    size: Option<u32>
+}
+
+fn parse_name(stem: &str) -> Option<(&str, ImageName)> {
+    let (hash, rest) = stem.split_once('_').unwrap_or((stem, ""));
+    let name = match rest {
+        "" => ImageName {thumbnail: false, size: None},
+        "thumb" => ImageName {thumbnail: true, size: None},
+        _ => match rest.strip_prefix("thumb_") {
+            Some(size) => ImageName {thumbnail: true, size: Some(size.parse().ok()?)},
+            None => ImageName {thumbnail: false, size: Some(rest.parse().ok()?)}
+        }
+    };
+    Some((hash, name))
+}
+
+fn format_name(hash: &str, thumbnail: bool, size: Option<u32>, ext: &str) -> String {
+    match (thumbnail, size) {
+        (false, Some(size)) => format!("{hash}_{size}.{ext}"),
+        (true, Some(size)) => format!("{hash}_thumb_{size}.{ext}"),
+        (false, None) => format!("{hash}.{ext}"),
+        (true, None) => format!("{hash}_thumb.{ext}")
+    }
+}
+
+/// File name of a cached image at the current size settings.
+pub fn file_name(hash: &str, thumbnail: bool) -> String {
+    let (hires_size, thumbnail_size) = cached_image_sizes();
+    format_name(hash, thumbnail, Some(if thumbnail {thumbnail_size} else {hires_size}), EXTENSION)
+}
+
+pub fn is_thumbnail(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(parse_name)
+        .is_some_and(|(_, name)| name.thumbnail)
+}
+
+/// Unscaled sizes the images on disk were last brought up to, or None if never.
+fn stored_sizes() -> Option<(u32, u32)> {
+    let state = settings_manager().child("state");
+    match (state.uint("cached-hires-size"), state.uint("cached-thumbnail-size")) {
+        (0, _) | (_, 0) => None,
+        sizes => Some(sizes)
+    }
+}
+
+/// Whether cached images need to be regenerated to match the current size settings
+/// & format. Display scale changes alone never need it.
+pub fn needs_regeneration() -> bool {
+    stored_sizes() != Some(unscaled_image_sizes())
+        || settings_manager().child("state").string("cached-image-format") != EXTENSION
+}
+
+/// Record that all cached images now match the given unscaled sizes & the current format.
+pub fn store_generation(sizes: (u32, u32)) {
+    let state = settings_manager().child("state");
+    let _ = state.set_uint("cached-hires-size", sizes.0);
+    let _ = state.set_uint("cached-thumbnail-size", sizes.1);
+    let _ = state.set_string("cached-image-format", EXTENSION);
+}
+
+/// Encode a resized image as JPEG.
+fn save_jpeg(image: &RgbImage, path: &Path, quality: u8) -> bool {
+    let res = fs::File::create(path)
+        .map_err(image::ImageError::IoError)
+        .and_then(|file| image.write_with_encoder(JpegEncoder::new_with_quality(BufWriter::new(file), quality)));
+    if let Err(e) = res {
+        println!("[Cache] Could not save {:?}: {:?}", path, e);
+        return false;
+    }
+    true
+}
+
+/// Save a thumbnail not paired with any high-resolution version.
+pub fn save_thumbnail(image: &RgbImage, path: &Path) -> bool {
+    save_jpeg(image, path, THUMBNAIL_QUALITY)
+}
+
+/// Save an image at whatever size it comes in, with the same quality as high-resolution
+/// versions.
+pub fn save_full_size(image: &DynamicImage, path: &Path) -> bool {
+    save_jpeg(&image.to_rgb8(), path, HIRES_QUALITY)
+}
+
+fn original_path(folder: &Path, hash: &str, ext: &str) -> PathBuf {
+    folder.join(format!("{hash}_original.{ext}"))
+}
+
+fn split_path(path: &Path) -> Option<(&Path, &str)> {
+    let folder = path.parent()?;
+    let (hash, _) = parse_name(path.file_stem()?.to_str()?)?;
+    Some((folder, hash))
+}
+
+/// Find the untouched original of a cached image, given the path of either of its
+/// resized versions.
+pub fn find_original(path: &Path) -> Option<PathBuf> {
+    let (folder, hash) = split_path(path)?;
+    ImageFormat::all()
+        .filter_map(|format| format.extensions_str().first())
+        .map(|ext| original_path(folder, hash, ext))
+        .find(|path| path.exists())
+}
+
+/// Resize an image into both cached sizes & save them, along with the untouched bytes it
+/// was decoded from if the user wants to keep those. Returns true if both sizes were saved.
+pub fn save_resized(image: DynamicImage, original: Option<&[u8]>, path: &Path, thumbnail_path: &Path) -> bool {
+    let (hires, thumbnail) = resize_convert_image(image);
+    let saved = save_jpeg(&hires, path, HIRES_QUALITY) && save_jpeg(&thumbnail, thumbnail_path, THUMBNAIL_QUALITY);
+    if let (true, Some(bytes)) = (saved, original) {
+        if settings_manager().child("library").boolean("keep-original-images") {
+            let ext = image::guess_format(bytes)
+                .ok()
+                .and_then(|format| format.extensions_str().first().copied());
+            if let (Some(ext), Some((folder, hash))) = (ext, split_path(path)) {
+                if let Err(e) = fs::write(original_path(folder, hash, ext), bytes) {
+                    println!("[Cache] Could not keep original image: {:?}", e);
+                }
+            }
+        }
+    }
+    saved
+}
+
+fn resize(image: &DynamicImage, size: u32, thumbnail: bool) -> DynamicImage {
+    let size = size.min(image.width().max(image.height()));
+    if thumbnail {
+        image.thumbnail(size, size)
+    }
+    else {
+        image.resize(size, size, FilterType::Triangle)
+    }
+}
+
+/// Bring all versions of one image up to the given sizes, then delete outdated ones if
+/// prune is true. Returns true if anything was regenerated.
+fn regenerate_image(folder: &Path, hash: &str, versions: &[(PathBuf, ImageName)], sizes: (u32, u32), prune: bool) -> bool {
+    let hires_path = folder.join(format_name(hash, false, Some(sizes.0), EXTENSION));
+    let thumbnail_path = folder.join(format_name(hash, true, Some(sizes.1), EXTENSION));
+    let outdated: Vec<&(PathBuf, ImageName)> = versions
+        .iter()
+        .filter(|(path, _)| path != &hires_path && path != &thumbnail_path)
+        .collect();
+    let complete = hires_path.exists() && thumbnail_path.exists();
+    if complete && (outdated.is_empty() || !prune) {
+        return false;
+    }
+    let mut regenerated = false;
+    // Originals are the best source for everything else, followed by high-resolution versions.
+    let original: Option<DynamicImage> = find_original(&hires_path).and_then(|path| image::open(path).ok());
+    let hires: Option<DynamicImage> = if hires_path.exists() {
+        original.or_else(|| image::open(&hires_path).ok())
+    }
+    else {
+        let source = original.or_else(|| {
+            outdated
+                .iter()
+                .find(|(_, name)| !name.thumbnail)
+                .and_then(|(path, _)| image::open(path).ok())
+        });
+        if let Some(image) = source.as_ref() {
+            if save_jpeg(&resize(image, sizes.0, false).into_rgb8(), &hires_path, HIRES_QUALITY) {
+                regenerated = true;
+            }
+        }
+        source
+    };
+    if !thumbnail_path.exists() {
+        // Without a high-resolution version, make do with the largest old thumbnail.
+        let source = hires.or_else(|| {
+            outdated
+                .iter()
+                .filter(|(_, name)| name.thumbnail)
+                .max_by_key(|(_, name)| name.size.unwrap_or(0))
+                .and_then(|(path, _)| image::open(path).ok())
+        });
+        if let Some(image) = source {
+            if save_jpeg(&resize(&image, sizes.1, true).into_rgb8(), &thumbnail_path, THUMBNAIL_QUALITY) {
+                regenerated = true;
+            }
+        }
+    }
+    if prune {
+        for (path, _) in outdated.into_iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+    regenerated
+}
+
+/// Older versions of a cached image that it could be regenerated from, such as those
+/// made before the size settings last changed.
+fn older_versions(folder: &Path, hash: &str) -> Vec<(PathBuf, ImageName)> {
+    let mut candidates = vec![
+        (ImageName {thumbnail: false, size: None}, LEGACY_EXTENSION),
+        (ImageName {thumbnail: true, size: None}, LEGACY_EXTENSION)
+    ];
+    if let Some((hires_size, thumbnail_size)) = stored_sizes() {
+        for ext in [EXTENSION, LEGACY_EXTENSION] {
+            candidates.push((ImageName {thumbnail: false, size: Some(hires_size)}, ext));
+            candidates.push((ImageName {thumbnail: true, size: Some(thumbnail_size)}, ext));
+        }
+    }
+    candidates
+        .into_iter()
+        .map(|(name, ext)| (folder.join(format_name(hash, name.thumbnail, name.size, ext)), name))
+        .filter(|(path, _)| path.exists())
+        .collect()
+}
+
+/// Whether a missing cached image could be produced by regenerate_from_older, without
+/// actually doing so.
+pub fn can_regenerate(path: &Path) -> bool {
+    let Some((folder, hash)) = split_path(path) else {
+        return false;
+    };
+    // Thumbnails can be made from any version, high-resolution ones only from others of
+    // their kind.
+    let thumbnail = is_thumbnail(path);
+    find_original(path).is_some() || older_versions(folder, hash)
+        .iter()
+        .any(|(_, name)| thumbnail || !name.thumbnail)
+}
+
+/// Try to produce a missing cached image from older versions of it, such as those made
+/// before the size settings last changed or at another display scale. Older versions are
+/// left for regenerate_folder to clean up. Returns true if the image now exists.
+pub fn regenerate_from_older(path: &Path) -> bool {
+    let Some((folder, hash)) = split_path(path) else {
+        return false;
+    };
+    let versions = older_versions(folder, hash);
+    // Thumbnails for a new display scale can be made from the current high-resolution version.
+    let has_hires = folder.join(file_name(hash, false)).exists();
+    if versions.is_empty() && !has_hires && find_original(path).is_none() {
+        return false;
+    }
+    regenerate_image(folder, hash, &versions, cached_image_sizes(), false);
+    path.exists()
+}
+
+/// Bring every image in a cache folder up to the given sizes, deleting all other
+/// versions, including thumbnails made for other display scales. Returns the number of
+/// images regenerated.
+pub fn regenerate_folder(folder: &Path, sizes: (u32, u32)) -> usize {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return 0;
+    };
+    let mut images: FxHashMap<String, Vec<(PathBuf, ImageName)>> = FxHashMap::default();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let parsed = path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(parse_name)
+            .map(|(hash, name)| (hash.to_owned(), name));
+        if let Some((hash, name)) = parsed {
+            images.entry(hash).or_default().push((path, name));
+        }
+    }
+    let mut regenerated = 0;
+    for (hash, versions) in images.iter() {
+        if regenerate_image(folder, hash, versions, sizes, true) {
+            regenerated += 1;
+        }
+    }
+    regenerated
+}
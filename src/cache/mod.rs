@@ -1,7 +1,14 @@
 mod controller;
+mod disk;
+mod export;
+mod images;
+mod schema;
 mod state;
 
 pub use state::CacheState;
 pub mod placeholders;
 
-pub use controller::Cache;
+pub use controller::{Cache, ImageCacheStats};
+pub use disk::{CacheCategory, DiskCache, DiskUsage};
+pub use export::{ExportOutcome, ExportPolicy, ExportReport};
+pub use images::save_resized;
@@ -0,0 +1,230 @@
+// Versioning of the metadata database's layout.
+// Cached documents change shape as providers gain fields, so each database records the
+// schema version it was last migrated to. Migrations run in order when opening an older
+// one. A failed migration is retried on the next start, the database being used as-is
+// in the meantime.
+// Corrupted databases are backed up under a timestamped name & rebuilt. polodb cannot
+// read anything back from those, so play history, smart playlists & user-picked matches
+// are lost, everything else being fetched again. Databases that fail to open for any
+// other reason (such as being locked by another instance) are left alone.
+// Documents that still fail to deserialise later on (such as those written by a newer
+// version of Euphonica) are treated as missing & deleted, see find_cached.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::RwLock
+};
+use chrono::Local;
+use polodb_core::Database;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::meta_providers::models::{AlbumMeta, ArtistMeta};
+
+pub const SCHEMA_VERSION: u32 = 3;
+// Older backups are deleted once there are more than this many.
+const MAX_BACKUPS: usize = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SchemaVersion {
+    version: u32
+}
+
+type Migration = fn(&Database) -> polodb_core::Result<()>;
+
+// The migration at index i brings a database from version i to i + 1.
+// Version 0 is any database created before versions were recorded.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+    migrate_0_to_1,
+    migrate_1_to_2,
+    migrate_2_to_3
+];
+
+/// Delete documents of a collection that no longer deserialise into T. Returns how many
+/// were deleted.
+fn purge_unreadable<T: DeserializeOwned>(db: &Database, name: &str) -> polodb_core::Result<usize> {
+    let coll = db.collection::<bson::Document>(name);
+    let unreadable: Vec<bson::Bson> = coll
+        .find(None)?
+        .filter_map(|res| res.ok())
+        .filter(|doc| bson::from_document::<T>(doc.clone()).is_err())
+        .filter_map(|doc| doc.get("_id").cloned())
+        .collect();
+    for id in unreadable.iter() {
+        coll.delete_one(bson::doc! {"_id": id.clone()})?;
+    }
+    Ok(unreadable.len())
+}
+
+/// Album & artist documents written before match scores & field sources were added might
+/// not deserialise anymore. Drop those so they are fetched again.
+fn migrate_0_to_1(db: &Database) -> polodb_core::Result<()> {
+    let albums = purge_unreadable::<AlbumMeta>(db, "album")?;
+    let artists = purge_unreadable::<ArtistMeta>(db, "artist")?;
+    println!("[Cache] Dropped {} album & {} artist documents in an outdated format", albums, artists);
+    Ok(())
+}
+
+/// Quality grades used to be aggregated over every album sharing a title. Drop them so
+/// they are aggregated again per album.
+fn migrate_1_to_2(db: &Database) -> polodb_core::Result<()> {
+    db.collection::<bson::Document>("album_quality").delete_many(bson::doc! {})?;
+    Ok(())
+}
+
+/// Quality grades of albums with a multi-valued AlbumArtist used to be aggregated over
+/// no songs at all. Drop them so they are aggregated again.
+fn migrate_2_to_3(db: &Database) -> polodb_core::Result<()> {
+    db.collection::<bson::Document>("album_quality").delete_many(bson::doc! {})?;
+    Ok(())
+}
+
+fn get_version(db: &Database) -> polodb_core::Result<u32> {
+    if let Some(record) = db.collection::<SchemaVersion>("schema").find_one(bson::doc! {})? {
+        return Ok(record.version);
+    }
+    // Brand new databases need no migration, but should remember that.
+    if db.list_collection_names()?.iter().all(|name| name == "schema") {
+        set_version(db, SCHEMA_VERSION)?;
+        Ok(SCHEMA_VERSION)
+    }
+    else {
+        Ok(0)
+    }
+}
+
+fn set_version(db: &Database, version: u32) -> polodb_core::Result<()> {
+    let coll = db.collection::<SchemaVersion>("schema");
+    coll.delete_many(bson::doc! {})?;
+    coll.insert_one(SchemaVersion {version})?;
+    Ok(())
+}
+
+/// Bring a database up to the current schema version.
+fn migrate(db: &Database) -> polodb_core::Result<()> {
+    let version = get_version(db)?;
+    if version > SCHEMA_VERSION {
+        // Downgraded. Keep going & let unreadable documents be dropped as they are found.
+        println!(
+            "[Cache] Metadata database is from a newer version (schema {} > {}). Some cached data might be refetched.",
+            version, SCHEMA_VERSION
+        );
+        return Ok(());
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        println!("[Cache] Migrating metadata database from schema {} to {}...", from, from + 1);
+        migration(db)?;
+        set_version(db, from as u32 + 1)?;
+    }
+    Ok(())
+}
+
+fn backup_prefix(path: &Path) -> String {
+    format!("{}.", path.file_name().unwrap_or_default().to_string_lossy())
+}
+
+/// Delete all but the newest MAX_BACKUPS backups. Their names sort by time.
+fn prune_backups(path: &Path) {
+    let (Some(folder), prefix) = (path.parent(), backup_prefix(path)) else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with(&prefix) && name.ends_with(".bak")
+        })
+        .map(|entry| entry.path())
+        .collect();
+    backups.sort();
+    let excess = backups.len().saturating_sub(MAX_BACKUPS);
+    for backup in backups.into_iter().take(excess) {
+        let _ = fs::remove_file(&backup).or_else(|_| fs::remove_dir_all(&backup));
+    }
+}
+
+/// Move an unreadable database out of the way under a timestamped name. Returns where it
+/// went, or None if it had to be deleted instead.
+fn back_up(path: &Path) -> Option<PathBuf> {
+    let backup = path.with_file_name(format!(
+        "{}{}.bak",
+        backup_prefix(path),
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    match fs::rename(path, &backup) {
+        Ok(()) => {
+            println!("[Cache] Unreadable metadata database backed up to {:?}", backup);
+            prune_backups(path);
+            Some(backup)
+        }
+        Err(e) => {
+            println!("[Cache] Could not back up unreadable metadata database: {:?}. Deleting it instead...", e);
+            let _ = fs::remove_file(path).or_else(|_| fs::remove_dir_all(path));
+            None
+        }
+    }
+}
+
+/// Whether an error opening the database means its file is damaged or not a database
+/// at all, rather than something that might go away by itself.
+fn is_corrupted(e: &polodb_core::Error) -> bool {
+    !matches!(
+        e,
+        polodb_core::Error::IOErr(_) | polodb_core::Error::DatabaseOccupied | polodb_core::Error::LockError
+    )
+}
+
+/// Open the metadata database at the given path & migrate it to the current schema
+/// version. If it is corrupted, it is backed up & replaced with a new one.
+pub fn open_database(path: &Path) -> Database {
+    match Database::open_file(path) {
+        Ok(db) => {
+            if let Err(e) = migrate(&db) {
+                println!("[Cache] Could not migrate metadata database: {:?}. Will retry on next start.", e);
+            }
+            db
+        }
+        Err(e) if is_corrupted(&e) => {
+            println!("[Cache] Metadata database is corrupted: {:?}. Starting afresh...", e);
+            match back_up(path) {
+                Some(backup) => println!(
+                    "[Cache] Play history, smart playlists & picked matches could not be recovered. The old database is kept at {:?}.",
+                    backup
+                ),
+                None => println!("[Cache] Play history, smart playlists & picked matches have been lost.")
+            }
+            let db = Database::open_file(path).expect("ERROR: cannot create a metadata database");
+            let _ = set_version(&db, SCHEMA_VERSION);
+            db
+        }
+        Err(e) => panic!("ERROR: cannot open metadata database at {:?}: {:?}", path, e)
+    }
+}
+
+/// Find a cached document, treating one that fails to deserialise as missing. Such
+/// documents are deleted so that they can be fetched again. Only use this on collections
+/// holding data that can be fetched again.
+pub fn find_cached<T>(doc_cache: &RwLock<Database>, collection: &str, query: bson::Document) -> Option<T>
+where
+    T: DeserializeOwned + Serialize + Send + Sync
+{
+    let res = doc_cache
+        .read()
+        .unwrap()
+        .collection::<T>(collection)
+        .find_one(query.clone());
+    match res {
+        Ok(found) => found,
+        Err(e) => {
+            println!("[Cache] Dropping unreadable document in {} matching {:?}: {:?}", collection, &query, e);
+            let _ = doc_cache
+                .write()
+                .unwrap()
+                .collection::<bson::Document>(collection)
+                .delete_many(query);
+            None
+        }
+    }
+}
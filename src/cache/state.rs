@@ -76,6 +76,12 @@ mod imp {
                             String::static_type()  // folder URI
                         ])
                         .build(),
+                    // Quality grades of all tracks of an album have been aggregated
+                    Signal::builder("album-quality-downloaded")
+                        .param_types([
+                            String::static_type()  // folder URI
+                        ])
+                        .build(),
                     Signal::builder("album-meta-downloaded")
                         .param_types([
                             String::static_type()  // album tag
@@ -90,6 +96,35 @@ mod imp {
                         .param_types([
                             String::static_type()  // artist tag
                         ])
+                        .build(),
+                    Signal::builder("artist-discography-downloaded")
+                        .param_types([
+                            String::static_type()  // artist MBID
+                        ])
+                        .build(),
+                    Signal::builder("remote-image-downloaded")
+                        .param_types([
+                            String::static_type()  // URL
+                        ])
+                        .build(),
+                    // Take the results with Cache::take_album_candidates
+                    Signal::builder("album-candidates-downloaded")
+                        .param_types([
+                            String::static_type()  // folder URI
+                        ])
+                        .build(),
+                    // Take the results with Cache::take_artist_candidates
+                    Signal::builder("artist-candidates-downloaded")
+                        .param_types([
+                            String::static_type()  // artist tag
+                        ])
+                        .build(),
+                    // A provider failed in a way the user should know about
+                    Signal::builder("provider-error")
+                        .param_types([
+                            String::static_type(),  // provider name
+                            String::static_type()   // message
+                        ])
                         .build()
                 ]
             })
@@ -105,6 +105,13 @@ mod imp {
                         .build(),
                     Signal::builder("sticker-db-disabled")
                         .build(),
+                    // Ratings & favourite flags of songs under a folder
+                    Signal::builder("song-stickers-downloaded")
+                        .param_types([
+                            String::static_type(),         // folder URI ("" for whole DB)
+                            BoxedAnyObject::static_type()  // SongStickers
+                        ])
+                        .build(),
                     Signal::builder("album-art-downloaded")
                         .param_types([
                             String::static_type(),         // folder URI
@@ -120,6 +127,9 @@ mod imp {
                     Signal::builder("outputs-changed")
                         .param_types([BoxedAnyObject::static_type()])  // Vec<mpd::output::Output>
                         .build(),
+                    Signal::builder("playlists-changed")
+                        .param_types([BoxedAnyObject::static_type()])  // Vec<mpd::playlist::Playlist>
+                        .build(),
                     // Enough information about this album has been downloaded to display it
                     // as a thumbnail in the album view
                     Signal::builder("album-basic-info-downloaded")
@@ -151,6 +161,21 @@ mod imp {
                             Album::static_type()
                         ])
                         .build(),
+                    // Candidate songs of a smart playlist, with stickers already applied.
+                    // Local rules have yet to be evaluated.
+                    Signal::builder("smart-playlist-songs-downloaded")
+                        .param_types([
+                            String::static_type(),         // smart playlist name
+                            BoxedAnyObject::static_type()  // Vec<Song>
+                        ])
+                        .build(),
+                    // Generic song search results, tagged with the key given in the request.
+                    Signal::builder("songs-found")
+                        .param_types([
+                            String::static_type(),         // request key
+                            BoxedAnyObject::static_type()  // Vec<String> of song URIs
+                        ])
+                        .build(),
                     Signal::builder("status-changed")
                         .param_types([BoxedAnyObject::static_type()])
                         .build(),
@@ -14,7 +14,8 @@ use image::DynamicImage;
 use uuid::Uuid;
 
 use crate::{
-    common::{Album, AlbumInfo, Artist, ArtistInfo, INode, Song, SongInfo}, meta_providers::Metadata, player::PlaybackFlow, utils
+    cache::save_resized,
+    common::{song::{FAVORITE_STICKER, MAX_RATING, RATING_STICKER}, artist_tag_credits, Album, AlbumInfo, AlbumQuality, Artist, ArtistInfo, INode, QualityGrade, SmartPlaylist, Song, SongInfo, SongStickers}, meta_providers::Metadata, player::PlaybackFlow, utils
 };
 
 use super::state::{ClientState, ConnectionState};
@@ -23,11 +24,51 @@ const BATCH_SIZE: u32 = 4096;
 const FETCH_LIMIT: usize = 10000000;  // Fetch at most ten million songs at once (same
 // folder, same tag, etc)
 
+/// Fetch ratings & favourite flags of all songs under the given folder.
+/// MPD can only list the URIs matching a sticker value, so we do one
+/// search per possible rating. That's still far fewer round trips than
+/// querying each song individually.
+fn find_song_stickers(client: &mut Client, base_uri: &str) -> Result<SongStickers, Error> {
+    let mut stickers = SongStickers::default();
+    for rating in 1..=MAX_RATING {
+        match client.find_sticker_eq("song", base_uri, RATING_STICKER, &rating.to_string()) {
+            Ok(uris) => {
+                for uri in uris {
+                    stickers.entry(uri).or_default().0 = rating;
+                }
+            }
+            Err(Error::Server(server_err)) if server_err.detail.contains("disabled") => {
+                return Err(Error::Server(server_err));
+            }
+            _ => {}
+        }
+    }
+    if let Ok(uris) = client.find_sticker_eq("song", base_uri, FAVORITE_STICKER, "1") {
+        for uri in uris {
+            stickers.entry(uri).or_default().1 = true;
+        }
+    }
+    Ok(stickers)
+}
+
+/// Get all values of the given artist tag that credit the given artist. Songs with these
+/// exact values can then be fetched without picking up other artists whose names
+/// merely contain this one.
+fn artist_tag_variants(client: &mut Client, tag_type: &'static str, name: &str) -> Vec<String> {
+    client
+        .list(
+            &Term::Tag(Cow::Borrowed(tag_type)),
+            Query::new().and_with_op(Term::Tag(Cow::Borrowed(tag_type)), QueryOperation::Contains, name)
+        )
+        .map(|values| values.into_iter().filter(|value| artist_tag_credits(value, name)).collect())
+        .unwrap_or_default()
+}
+
 // One for each command in mpd's protocol plus a few special ones such
 // as Connect and Toggle.
 pub enum MpdMessage {
     Connect, // Host and port are always read from gsettings
-    Update, // Update DB
+    Update(Option<String>), // Update DB, optionally only under the given folder URI
     Output(u32, bool), // Set output state. Specify target ID and state to set to.
     SetPlaybackFlow(PlaybackFlow),
     Random(bool),
@@ -45,6 +86,7 @@ pub enum MpdMessage {
     Status,
     SeekCur(f64), // Seek current song to last position set by PrepareSeekCur. For some reason the mpd crate calls this "rewind".
     FindAdd(Query<'static>),
+    FindAddArtist(String, bool), // Queue all songs crediting the artist with given name. If true, use the AlbumArtist tag.
     Queue, // Get songs in current queue
     Albums, // Get albums. Will return one by one
     Artists(bool), // Get artists. Will return one by one. If bool flag is true, will parse AlbumArtist tag.
@@ -58,13 +100,20 @@ pub enum MpdMessage {
     Consume(bool),
     GetSticker(String, String, String), // Type, URI, name
     SetSticker(String, String, String, String), // Type, URI, name, value
+    SongStickers(String), // Get ratings & favourite flags of all songs under given folder URI ("" for all)
     LsInfo(String),  // URI
+    Playlists, // Get stored playlists
+    LoadPlaylist(String), // Append stored playlist with given name to the queue
+    SavePlaylist(String, Vec<String>), // Create or overwrite stored playlist with given name & song URIs
+    SmartPlaylistContent(SmartPlaylist), // Get candidate songs (with stickers) for a smart playlist
+    FindSongs(String, Vec<(String, String)>), // Request key & (tag, value) pairs to match exactly. Empty list gets all songs.
 
     // Reserved for cache controller
-    // folder-level URI, key doc & paths to write the hires & thumbnail versions
+    // folder-level URI, key doc, paths to write the hires & thumbnail versions & whether this is a prefetch
     // Key doc is here so we can query fetching from remote sources with the cache controller in case MPD can't
     // give us an album art.
-    AlbumArt(String, bson::Document, PathBuf, PathBuf),
+    AlbumArt(String, bson::Document, PathBuf, PathBuf, bool),
+    AlbumQuality(String, String), // Album tag & folder-level URI. Result goes to the cache controller.
 
 	// Reserved for child thread
 	Busy(bool), // A true will be sent when the work queue starts having tasks, and a false when it is empty again.
@@ -78,6 +127,8 @@ pub enum MpdMessage {
     ArtistSongInfoDownloaded(String, Vec<SongInfo>),  // Return songs of an artist (or had their participation)
     ArtistAlbumBasicInfoDownloaded(String, AlbumInfo),  // Return albums that had this artist in their AlbumArtist tag.
     FolderContentsDownloaded(String, Vec<LsInfoEntry>),
+    SmartPlaylistSongInfoDownloaded(String, Vec<SongInfo>, SongStickers), // Smart playlist name, candidate songs & their stickers
+    SongsFound(String, Vec<String>), // Request key & URIs of all matching songs
     DBUpdated
 }
 
@@ -85,14 +136,19 @@ pub enum MpdMessage {
 // Completed results will be reported back via MpdMessage.
 #[derive(Debug)]
 pub enum BackgroundTask {
-    Update,
-    DownloadAlbumArt(String, bson::Document, PathBuf, PathBuf),  // folder-level URI
+    Update(Option<String>),
+    DownloadAlbumArt(String, bson::Document, PathBuf, PathBuf, bool),  // folder-level URI, true for prefetches
+    FetchAlbumQuality(String, String),  // Album tag & folder-level URI
     FetchFolderContents(String), // Gradually get all inodes in folder at path
     FetchAlbums,  // Gradually get all albums
     FetchAlbumSongs(String),  // Get songs of album with given tag
     FetchArtists(bool),  // Gradually get all artists. If bool flag is true, will parse AlbumArtist tag
     FetchArtistSongs(String),  // Get all songs of an artist with given name
     FetchArtistAlbums(String),  // Get all albums of an artist with given name
+    FetchSmartPlaylistSongs(SmartPlaylist),  // Get all songs matching the MPD-side rules of a smart playlist
+    FindSongs(String, Vec<(String, String)>),  // Get URIs of all songs matching the given tags, tagged with a request key
+    SavePlaylist(String, Vec<String>),  // Create or overwrite stored playlist with given name & song URIs
+    FindAddArtist(String, bool),  // Queue all songs crediting the artist with given name. If true, use the AlbumArtist tag.
 }
 
 // Thin wrapper around the blocking mpd::Client. It contains two separate client
@@ -132,8 +188,46 @@ pub enum BackgroundTask {
 
 mod background {
     use super::*;
-    pub fn update_mpd_database(client: &mut mpd::Client, sender_to_fg: &Sender<MpdMessage>) {
-        if let Ok(_) = client.update() {
+    /// Create or overwrite a stored playlist. Songs are written to a temporary playlist
+    /// first, which then replaces the existing one, so that a failure partway through
+    /// never leaves a partial playlist in its place.
+    pub fn save_playlist(client: &mut mpd::Client, name: String, uris: Vec<String>) {
+        let tmp_name = format!("{name}.part");
+        // Left over from an earlier failed attempt, if any
+        let _ = client.pl_remove(&tmp_name);
+        for uri in uris.into_iter() {
+            let song = mpd::Song {
+                file: uri,
+                ..Default::default()
+            };
+            if let Err(e) = client.pl_push(&tmp_name, song) {
+                println!("Failed to save playlist {}: {:?}", &name, e);
+                let _ = client.pl_remove(&tmp_name);
+                return;
+            }
+        }
+        // MPD refuses to rename over an existing playlist. Fails harmlessly if there is none.
+        let _ = client.pl_remove(&name);
+        if let Err(e) = client.pl_rename(&tmp_name, &name) {
+            println!("Failed to save playlist {}, its songs are kept as {}: {:?}", &name, &tmp_name, e);
+        }
+    }
+
+    pub fn find_add_artist(client: &mut mpd::Client, name: String, use_albumartist: bool) {
+        let tag_type = if use_albumartist { "albumartist" } else { "artist" };
+        for variant in artist_tag_variants(client, tag_type, &name).into_iter() {
+            if let Err(e) = client.findadd(Query::new().and(Term::Tag(Cow::Borrowed(tag_type)), variant)) {
+                println!("Failed to queue songs of {}: {:?}", &name, e);
+            }
+        }
+    }
+
+    pub fn update_mpd_database(client: &mut mpd::Client, sender_to_fg: &Sender<MpdMessage>, path: Option<String>) {
+        let res = match path.as_deref() {
+            Some(path) => client.update_path(path),
+            None => client.update()
+        };
+        if let Ok(_) = res {
             let _ = sender_to_fg.send_blocking(MpdMessage::DBUpdated);
         }
     }
@@ -144,17 +238,14 @@ mod background {
         uri: String,
         key: bson::Document,
         path: PathBuf,
-        thumbnail_path: PathBuf
+        thumbnail_path: PathBuf,
+        prefetch: bool
     ) {
         if let Ok(bytes) = client.albumart(&uri) {
             println!("Downloaded album art for {:?}", uri);
-            if let Some(dyn_img) = utils::read_image_from_bytes(bytes) {
-                let (hires, thumb) = utils::resize_convert_image(dyn_img);
+            if let Some(dyn_img) = utils::read_image_from_bytes(&bytes) {
                 if !path.exists() || !thumbnail_path.exists() {
-                    if let (Ok(_), Ok(_)) = (
-                        hires.save(path),
-                        thumb.save(thumbnail_path)
-                    ) {
+                    if save_resized(dyn_img, Some(&bytes), &path, &thumbnail_path) {
                         sender_to_cache.send_blocking(Metadata::AlbumArt(uri, false)).expect(
                             "Cannot notify main cache of album art download result."
                         );
@@ -164,28 +255,70 @@ mod background {
         }
         else {
             // Fetch from local sources instead.
-            sender_to_cache.send_blocking(Metadata::AlbumArtNotAvailable(uri, key)).expect(
+            sender_to_cache.send_blocking(Metadata::AlbumArtNotAvailable(uri, key, prefetch)).expect(
                 "Album art not available from MPD, but cannot notify cache of this."
             );
         }
     }
 
-    fn fetch_albums_by_query<F>(
+    /// Let the cache controller know which version of the library it is dealing with.
+    pub fn report_library_version(client: &mut mpd::Client, sender_to_cache: &Sender<Metadata>) {
+        if let Ok(stats) = client.stats() {
+            let _ = sender_to_cache.send_blocking(Metadata::LibraryVersion(stats.db_update.as_secs() as i64));
+        }
+    }
+
+    /// Aggregate the quality grades of all songs of an album. Album info is otherwise
+    /// derived from a single song, which can mislabel albums with bonus tracks in
+    /// different formats.
+    /// Titles alone are not unique (think "Greatest Hits"), so songs are also matched by
+    /// the album's folder. AlbumArtist can't be used for that, as our multi-valued
+    /// AlbumArtist string matches none of the individual tag values.
+    pub fn fetch_album_quality(
         client: &mut mpd::Client,
-        query: &Query,
+        sender_to_cache: &Sender<Metadata>,
+        tag: String,
+        folder_uri: String
+    ) {
+        let grades: RefCell<Vec<QualityGrade>> = RefCell::new(Vec::new());
+        let mut query = Query::new();
+        query
+            .and(Term::Tag(Cow::Borrowed("album")), tag.as_str())
+            .and(Term::Base, folder_uri.as_str());
+        fetch_songs_by_query(
+            client,
+            &query,
+            |songs| {
+                grades.borrow_mut().extend(songs.iter().map(SongInfo::get_quality_grade));
+                Ok(())
+            }
+        );
+        let _ = sender_to_cache.send_blocking(Metadata::AlbumQuality(
+            folder_uri,
+            AlbumQuality::from_grades(grades.into_inner())
+        ));
+    }
+
+    /// Album titles are looked up within the same query, as they are not unique.
+    /// Queries are built on demand since they cannot be cloned.
+    fn fetch_albums_by_query<Q, F>(
+        client: &mut mpd::Client,
+        query: Q,
         respond: F
     ) where
+        Q: Fn() -> Query<'static>,
         F: Fn(AlbumInfo) -> Result<(), SendError<MpdMessage>>
     {
         // TODO: batched windowed retrieval
         // Get list of unique album tags
         // Will block child thread until info for all albums have been retrieved.
         if let Ok(tag_list) = client
-            .list(&Term::Tag(Cow::Borrowed("album")), query) {
-            for tag in &tag_list {
+            .list(&Term::Tag(Cow::Borrowed("album")), &query()) {
+            for tag in tag_list.into_iter() {
+                let mut album_query = query();
+                album_query.and(Term::Tag(Cow::Borrowed("album")), tag);
                 if let Ok(mut songs) = client.find(
-                    Query::new()
-                        .and(Term::Tag(Cow::Borrowed("album")), tag),
+                    &album_query,
                     Window::from((0, 1))
                 ) {
                     if !songs.is_empty() {
@@ -234,7 +367,7 @@ mod background {
     ) {
         fetch_albums_by_query(
             client,
-            &Query::new(),
+            Query::new,
             |info| {
                 sender_to_fg.send_blocking(
                     MpdMessage::AlbumBasicInfoDownloaded(
@@ -250,22 +383,30 @@ mod background {
         sender_to_fg: &Sender<MpdMessage>,
         artist_name: String,
     ) {
-        fetch_albums_by_query(
-            client,
-            Query::new().and_with_op(
-                Term::Tag(Cow::Borrowed("artist")),
-                QueryOperation::Contains,
-                artist_name.clone()
-            ),
-            |info| {
-                sender_to_fg.send_blocking(
-                    MpdMessage::ArtistAlbumBasicInfoDownloaded(
-                        artist_name.clone(),
-                        info
+        // An album can have songs under several variants of the artist tag. Different albums
+        // can share a title though, so tell them apart by folder.
+        let seen: RefCell<FxHashSet<String>> = RefCell::new(FxHashSet::default());
+        for variant in artist_tag_variants(client, "artist", &artist_name).into_iter() {
+            fetch_albums_by_query(
+                client,
+                || {
+                    let mut query = Query::new();
+                    query.and(Term::Tag(Cow::Borrowed("artist")), variant.clone());
+                    query
+                },
+                |info| {
+                    if !seen.borrow_mut().insert(info.uri.clone()) {
+                        return Ok(());
+                    }
+                    sender_to_fg.send_blocking(
+                        MpdMessage::ArtistAlbumBasicInfoDownloaded(
+                            artist_name.clone(),
+                            info
+                        )
                     )
-                )
-            }
-        );
+                }
+            );
+        }
     }
 
     pub fn fetch_album_songs(
@@ -335,23 +476,61 @@ mod background {
         sender_to_fg: &Sender<MpdMessage>,
         name: String
     ) {
+        for variant in artist_tag_variants(client, "artist", &name).into_iter() {
+            fetch_songs_by_query(
+                client,
+                Query::new().and(Term::Tag(Cow::Borrowed("artist")), variant),
+                |songs| {
+                    sender_to_fg.send_blocking(
+                        MpdMessage::ArtistSongInfoDownloaded(
+                            name.clone(),
+                            songs
+                        )
+                    )
+                }
+            );
+        }
+    }
+
+    pub fn fetch_smart_playlist_songs(
+        client: &mut mpd::Client,
+        sender_to_fg: &Sender<MpdMessage>,
+        playlist: SmartPlaylist
+    ) {
+        // Local rules need the whole candidate set at once (for limits),
+        // so don't stream batches to the UI.
+        let candidates: RefCell<Vec<SongInfo>> = RefCell::new(Vec::new());
         fetch_songs_by_query(
             client,
-            Query::new()
-                .and_with_op(
-                    Term::Tag(Cow::Borrowed("artist")),
-                    QueryOperation::Contains,
-                    name.clone()
-                ),
-            |songs| {
-                sender_to_fg.send_blocking(
-                    MpdMessage::ArtistSongInfoDownloaded(
-                        name.clone(),
-                        songs
-                    )
-                )
+            &playlist.to_query(),
+            |mut songs| {
+                candidates.borrow_mut().append(&mut songs);
+                Ok(())
             }
         );
+        // A disabled sticker DB simply means no song has a rating.
+        let stickers = find_song_stickers(client, "").unwrap_or_default();
+        let _ = sender_to_fg.send_blocking(MpdMessage::SmartPlaylistSongInfoDownloaded(
+            playlist.name,
+            candidates.into_inner(),
+            stickers
+        ));
+    }
+
+    pub fn find_songs(
+        client: &mut mpd::Client,
+        sender_to_fg: &Sender<MpdMessage>,
+        key: String,
+        terms: Vec<(String, String)>
+    ) {
+        // Only URIs are needed, so list them instead of fetching full song info.
+        // An empty query lists every song in the database.
+        let mut query = Query::new();
+        for (tag, value) in terms.into_iter() {
+            query.and(Term::Tag(Cow::Owned(tag)), value);
+        }
+        let uris = client.list(&Term::File, &query).unwrap_or_default();
+        let _ = sender_to_fg.send_blocking(MpdMessage::SongsFound(key, uris));
     }
 
     pub fn fetch_folder_contents(
@@ -432,6 +611,7 @@ impl MpdWrapper {
             );
             let bg_handle = gio::spawn_blocking(move || {
                 println!("Starting idle loop...");
+                background::report_library_version(&mut client, &meta_sender);
                 let mut prev_size: usize = bg_receiver.len();
                 'outer: loop {
                     // Check if there is work to do
@@ -445,14 +625,19 @@ impl MpdWrapper {
                         if let Ok(task) = bg_receiver.recv_blocking() {
                             // println!("Got task: {:?}", task);
                             match task {
-                                BackgroundTask::Update => {
+                                BackgroundTask::Update(path) => {
                                     background::update_mpd_database(
-                                        &mut client, &sender_to_fg
+                                        &mut client, &sender_to_fg, path
                                     )
                                 }
-                                BackgroundTask::DownloadAlbumArt(uri, key, path, thumbnail_path) => {
+                                BackgroundTask::DownloadAlbumArt(uri, key, path, thumbnail_path, prefetch) => {
                                     background::download_album_art(
-                                        &mut client, &meta_sender, uri, key, path, thumbnail_path
+                                        &mut client, &meta_sender, uri, key, path, thumbnail_path, prefetch
+                                    )
+                                }
+                                BackgroundTask::FetchAlbumQuality(tag, folder_uri) => {
+                                    background::fetch_album_quality(
+                                        &mut client, &meta_sender, tag, folder_uri
                                     )
                                 }
                                 BackgroundTask::FetchAlbums => {
@@ -484,6 +669,18 @@ impl MpdWrapper {
                                 BackgroundTask::FetchFolderContents(uri) => {
                                     background::fetch_folder_contents(&mut client, &sender_to_fg, uri)
                                 }
+                                BackgroundTask::FetchSmartPlaylistSongs(playlist) => {
+                                    background::fetch_smart_playlist_songs(&mut client, &sender_to_fg, playlist)
+                                }
+                                BackgroundTask::FindSongs(key, terms) => {
+                                    background::find_songs(&mut client, &sender_to_fg, key, terms)
+                                }
+                                BackgroundTask::SavePlaylist(name, uris) => {
+                                    background::save_playlist(&mut client, name, uris)
+                                }
+                                BackgroundTask::FindAddArtist(name, use_albumartist) => {
+                                    background::find_add_artist(&mut client, name, use_albumartist)
+                                }
                             }
                         }
                     }
@@ -496,6 +693,9 @@ impl MpdWrapper {
                         // If not, go into idle mode
                         if let Ok(changes) = client.wait(&[]) {
                             println!("Change: {:?}", changes);
+                            if changes.contains(&Subsystem::Database) {
+                                background::report_library_version(&mut client, &meta_sender);
+                            }
                             if changes.contains(&Subsystem::Message) {
                                 if let Ok(msgs) = client.readmessages() {
                                     for msg in msgs {
@@ -570,7 +770,7 @@ impl MpdWrapper {
         // println!("Received MpdMessage {:?}", request);
         match request {
             MpdMessage::Connect => self.connect().await,
-            MpdMessage::Update => self.queue_task(BackgroundTask::Update),
+            MpdMessage::Update(path) => self.queue_task(BackgroundTask::Update(path)),
             MpdMessage::Output(id, state) => self.set_output(id, state),
             MpdMessage::Volume(vol) => self.volume(vol),
             MpdMessage::Crossfade(fade) => self.set_crossfade(fade),
@@ -596,11 +796,12 @@ impl MpdWrapper {
             MpdMessage::SeekCur(position) => self.seek_current_song(position),
             MpdMessage::Queue => self.get_current_queue(),
             MpdMessage::Albums => self.queue_task(BackgroundTask::FetchAlbums),
-            MpdMessage::AlbumArt(folder_uri, key, path, thumbnail_path) => {
+            MpdMessage::AlbumArt(folder_uri, key, path, thumbnail_path, prefetch) => {
                 self.queue_task(
-                    BackgroundTask::DownloadAlbumArt(folder_uri.to_owned(), key, path, thumbnail_path)
+                    BackgroundTask::DownloadAlbumArt(folder_uri.to_owned(), key, path, thumbnail_path, prefetch)
                 );
             },
+            MpdMessage::AlbumQuality(tag, folder_uri) => self.queue_task(BackgroundTask::FetchAlbumQuality(tag, folder_uri)),
             MpdMessage::AlbumContent(tag) => {
                 // For now we only have songs.
                 // In the future we might want to have additional types of per-album content,
@@ -612,7 +813,13 @@ impl MpdWrapper {
             }
             MpdMessage::ArtistContent(name) => self.get_artist_content(name),
             MpdMessage::FindAdd(terms) => self.find_add(terms),
+            MpdMessage::FindAddArtist(name, use_albumartist) => self.queue_task(BackgroundTask::FindAddArtist(name, use_albumartist)),
             MpdMessage::LsInfo(uri) => self.queue_task(BackgroundTask::FetchFolderContents(uri)),
+            MpdMessage::Playlists => self.get_playlists(),
+            MpdMessage::LoadPlaylist(name) => self.load_playlist(&name),
+            MpdMessage::SavePlaylist(name, uris) => self.queue_task(BackgroundTask::SavePlaylist(name, uris)),
+            MpdMessage::SmartPlaylistContent(playlist) => self.queue_task(BackgroundTask::FetchSmartPlaylistSongs(playlist)),
+            MpdMessage::FindSongs(key, terms) => self.queue_task(BackgroundTask::FindSongs(key, terms)),
             // Result messages from child thread
             MpdMessage::AlbumArtDownloaded(folder_uri, hires, thumb) => self.state.emit_by_name::<()>(
                 "album-art-downloaded",
@@ -624,6 +831,7 @@ impl MpdWrapper {
             ),
             MpdMessage::GetSticker(typ, uri, name) => self.get_sticker(&typ, &uri, &name),
             MpdMessage::SetSticker(typ, uri, name, value) => self.set_sticker(&typ, &uri, &name, &value),
+            MpdMessage::SongStickers(base_uri) => self.get_song_stickers(base_uri),
             MpdMessage::AlbumArtNotAvailable(folder_uri) => self.state.emit_result(
                 "album-art-not-available",
                 folder_uri
@@ -653,6 +861,15 @@ impl MpdWrapper {
                 album_info
             ),
             MpdMessage::FolderContentsDownloaded(uri, contents) => self.on_folder_contents_downloaded(uri, contents),
+            MpdMessage::SmartPlaylistSongInfoDownloaded(name, songs, stickers) => self.on_smart_playlist_songs_downloaded(
+                name,
+                songs,
+                stickers
+            ),
+            MpdMessage::SongsFound(key, uris) => self.state.emit_by_name::<()>("songs-found", &[
+                &key.to_value(),
+                &BoxedAnyObject::new(uris).to_value()
+            ]),
             MpdMessage::DBUpdated => {},
             MpdMessage::Busy(busy) => self.state.set_busy(busy),
         }
@@ -674,6 +891,10 @@ impl MpdWrapper {
                 Subsystem::Output => {
                     self.get_outputs();
                 }
+                Subsystem::Playlist => {
+                    // Stored playlists (not the queue)
+                    self.get_playlists();
+                }
                 // More to come
                 _ => {}
             }
@@ -700,6 +921,7 @@ impl MpdWrapper {
         self.queue_task(BackgroundTask::FetchAlbums);
         self.queue_task(BackgroundTask::FetchArtists(false));
         self.get_outputs();
+        self.get_playlists();
         // Get queue first so we can look for current song in it later
         self.get_current_queue();
         self.get_status();
@@ -778,6 +1000,20 @@ impl MpdWrapper {
         }
     }
 
+    fn get_playlists(&self) {
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            if let Ok(playlists) = client.playlists() {
+                self.state.emit_boxed_result("playlists-changed", playlists);
+            }
+        }
+    }
+
+    fn load_playlist(&self, name: &str) {
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            let _ = client.load(name, ..);
+        }
+    }
+
     fn get_sticker(&self, typ: &str, uri: &str, name: &str) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             let res = client.sticker(typ, uri, name);
@@ -813,9 +1049,39 @@ impl MpdWrapper {
 
     fn set_sticker(&self, typ: &str, uri: &str, name: &str, value: &str) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
-            let _ = client.set_sticker(typ, uri, name, value);
+            match client.set_sticker(typ, uri, name, value) {
+                Ok(()) => {
+                    // Echo back so that every view showing this object can update.
+                    self.state.emit_by_name::<()>("sticker-downloaded", &[
+                        &typ.to_value(),
+                        &uri.to_value(),
+                        &name.to_value(),
+                        &value.to_value()
+                    ]);
+                }
+                Err(Error::Server(server_err)) if server_err.detail.contains("disabled") => {
+                    self.state.emit_by_name::<()>("sticker-db-disabled", &[]);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn get_song_stickers(&self, base_uri: String) {
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            match find_song_stickers(client, &base_uri) {
+                Ok(stickers) => {
+                    self.state.emit_by_name::<()>("song-stickers-downloaded", &[
+                        &base_uri.to_value(),
+                        &BoxedAnyObject::new(stickers).to_value()
+                    ]);
+                }
+                Err(_) => {
+                    self.state.emit_by_name::<()>("sticker-db-disabled", &[]);
+                }
+            }
         }
-    } 
+    }
 
     pub fn get_status(&self) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
@@ -1088,6 +1354,18 @@ impl MpdWrapper {
         }
     }
 
+    fn on_smart_playlist_songs_downloaded(&self, name: String, songs: Vec<SongInfo>, stickers: SongStickers) {
+        let songs: Vec<Song> = songs.into_iter().map(Song::from).collect();
+        for song in songs.iter() {
+            song.apply_stickers("", &stickers);
+        }
+        // Emit even if empty so that views can stop showing their loading state.
+        self.state.emit_by_name::<()>("smart-playlist-songs-downloaded", &[
+            &name.to_value(),
+            &BoxedAnyObject::new(songs).to_value()
+        ]);
+    }
+
     pub fn on_folder_contents_downloaded(&self, uri: String, contents: Vec<LsInfoEntry>) {
         self.state.emit_by_name::<()>("folder-contents-downloaded", &[
             &uri.to_value(),
@@ -4,15 +4,128 @@ use gtk::glib;
 use gtk::gdk::Texture;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::{
     QualityGrade,
     ArtistInfo,
     SongInfo,
-    parse_mb_artist_tag,
     artists_to_string
 };
 
+/// Range of quality grades across all tracks of an album. Tracks of unknown
+/// quality are ignored unless no track has a known grade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct AlbumQuality {
+    pub lowest: QualityGrade,
+    pub highest: QualityGrade,
+    // Number of tracks aggregated, including those of unknown quality. 0 if unknown.
+    #[serde(default)]
+    pub track_count: u32
+}
+
+impl AlbumQuality {
+    pub fn from_grades<I: IntoIterator<Item = QualityGrade>>(grades: I) -> Self {
+        let mut res: Option<Self> = None;
+        let mut track_count: u32 = 0;
+        for grade in grades.into_iter() {
+            track_count += 1;
+            if grade == QualityGrade::Unknown {
+                continue;
+            }
+            res = Some(match res {
+                Some(range) => Self {
+                    lowest: range.lowest.min(grade),
+                    highest: range.highest.max(grade),
+                    track_count: 0
+                },
+                None => Self { lowest: grade, highest: grade, track_count: 0 }
+            });
+        }
+        Self { track_count, ..res.unwrap_or_default() }
+    }
+
+    pub fn is_mixed(&self) -> bool {
+        self.lowest != self.highest
+    }
+
+    /// Icon of the best grade available. Check is_mixed() to tell whether
+    /// all tracks actually meet it.
+    pub fn icon_name(&self) -> Option<&'static str> {
+        self.highest.to_icon_name()
+    }
+
+    // TODO: translatable
+    pub fn description(&self) -> String {
+        if self.is_mixed() {
+            format!("Mixed ({} to {})", self.lowest.description(), self.highest.description())
+        }
+        else {
+            self.highest.description().to_owned()
+        }
+    }
+}
+
+impl From<QualityGrade> for AlbumQuality {
+    fn from(grade: QualityGrade) -> Self {
+        Self { lowest: grade, highest: grade, track_count: 0 }
+    }
+}
+
+/// Kind of release, used to group an artist's discography. Secondary types
+/// (compilation, live) take precedence over primary ones (album, EP, single).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ReleaseType {
+    #[default]
+    Album,
+    EP,
+    Single,
+    Compilation,
+    Live
+}
+
+impl ReleaseType {
+    /// Parse the values of a ReleaseType or MUSICBRAINZ_ALBUMTYPE tag. Taggers either
+    /// write one value per type or a single value such as "album; live".
+    pub fn from_tag_values<S: AsRef<str>>(vals: &[S]) -> Option<Self> {
+        let types: Vec<String> = vals
+            .iter()
+            .flat_map(|val| val.as_ref().split([';', ',', '/']))
+            .map(|t| t.trim().to_lowercase())
+            .collect();
+        let has = |name: &str| types.iter().any(|t| t == name);
+        if has("compilation") {
+            Some(Self::Compilation)
+        }
+        else if has("live") {
+            Some(Self::Live)
+        }
+        else if has("ep") {
+            Some(Self::EP)
+        }
+        else if has("single") {
+            Some(Self::Single)
+        }
+        else if has("album") {
+            Some(Self::Album)
+        }
+        else {
+            None
+        }
+    }
+
+    // TODO: translatable
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Album => "Album",
+            Self::EP => "EP",
+            Self::Single => "Single",
+            Self::Compilation => "Compilation",
+            Self::Live => "Live"
+        }
+    }
+}
+
 // This is a model class for queue view displays.
 // It does not contain any actual song in terms of data.
 
@@ -27,7 +140,10 @@ pub struct AlbumInfo {
     pub cover: Option<Texture>,
     pub release_date: Option<Date>,
     pub quality_grade: QualityGrade,
-    pub mbid: Option<String>
+    pub mbid: Option<String>,
+    // From the ReleaseType/MUSICBRAINZ_ALBUMTYPE tags, if present.
+    pub release_type: Option<ReleaseType>,
+    pub release_group_mbid: Option<String>
 }
 
 impl AlbumInfo {
@@ -46,18 +162,12 @@ impl AlbumInfo {
             cover: None,
             release_date: None,
             quality_grade,
-            mbid: None
+            mbid: None,
+            release_type: None,
+            release_group_mbid: None
         }
     }
 
-    pub fn set_artists_from_string(&mut self, tag: &str) {
-        self.artist_tag = Some(tag.to_owned());
-        self.artists = parse_mb_artist_tag(tag)
-            .iter()
-            .map(|s| ArtistInfo::new(s, false))
-            .collect();
-    }
-
     pub fn get_artist_str(&self) -> Option<String> {
         artists_to_string(&self.artists)
     }
@@ -77,7 +187,9 @@ impl Default for AlbumInfo {
             cover: None,
             release_date: None,
             quality_grade: QualityGrade::Unknown,
-            mbid: None
+            mbid: None,
+            release_type: None,
+            release_group_mbid: None
         }
     }
 }
@@ -196,6 +308,14 @@ impl Album {
     pub fn get_quality_grade(&self) -> QualityGrade {
         self.get_info().quality_grade.clone()
     }
+
+    pub fn get_release_type(&self) -> Option<ReleaseType> {
+        self.get_info().release_type
+    }
+
+    pub fn get_release_group_mbid(&self) -> Option<&str> {
+        self.get_info().release_group_mbid.as_deref()
+    }
 }
 
 impl Default for Album {
@@ -103,6 +103,48 @@ pub fn parse_mb_artist_tag<'a>(input: &'a str) -> Vec<&'a str> {
     }
 }
 
+/// Check whether an artist tag value credits the given artist, as opposed to merely
+/// containing its name (for example "Adonis" does not credit "Ado").
+pub fn artist_tag_credits(tag: &str, name: &str) -> bool {
+    tag == name || parse_mb_artist_tag(tag).contains(&name)
+}
+
+/// Build a list of artists from the values of a song's artist-related tags.
+///
+/// - credits: values of the Artist (or AlbumArtist) tag, in order. MPD returns one
+///   per line when the tag is multi-valued.
+/// - names: values of the Artists (or AlbumArtists) tag, which Picard & co. write
+///   alongside a single joined credit string.
+/// - mbids: values of the matching MUSICBRAINZ_*ARTISTID tag.
+///
+/// Tag values are used as-is whenever there are several of them. Delimiter-based
+/// parsing is only used as a fallback for a lone credit string. MBIDs are assumed
+/// to be given in the same order as the artist names.
+pub fn artists_from_tags(credits: &[String], names: &[String], mbids: Vec<String>) -> Vec<ArtistInfo> {
+    let mut artists: Vec<ArtistInfo> = if !names.is_empty() {
+        names.iter().map(|name| ArtistInfo::new(name, false)).collect()
+    }
+    else if credits.len() > 1 {
+        credits.iter().map(|name| ArtistInfo::new(name, false)).collect()
+    }
+    else if let Some(credit) = credits.first() {
+        // TODO: Find a way to detect classical works
+        parse_mb_artist_tag(credit)
+            .iter()
+            .map(|s| ArtistInfo::new(s, false))
+            .collect()
+    }
+    else {
+        Vec::with_capacity(0)
+    };
+    for (idx, id) in mbids.into_iter().enumerate() {
+        if idx < artists.len() {
+            let _ = artists[idx].mbid.replace(id);
+        }
+    }
+    artists
+}
+
 pub fn artists_to_string(artists: &[ArtistInfo]) -> Option<String> {
     if artists.is_empty() {
         None
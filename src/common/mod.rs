@@ -4,14 +4,20 @@ pub mod inode;
 pub mod artist;
 pub mod paintables;
 pub mod marquee;
+pub mod rating;
+pub mod smart_playlist;
 
-pub use song::{SongInfo, Song, QualityGrade};
+pub use song::{SongInfo, Song, QualityGrade, SongStickers};
 pub use inode::{INodeType, INodeInfo, INode};
-pub use album::{AlbumInfo, Album};
+pub use album::{AlbumInfo, Album, AlbumQuality, ReleaseType};
 pub use marquee::Marquee;
+pub use rating::Rating;
+pub use smart_playlist::{SmartPlaylist, SmartRule};
 pub use artist::{
     ArtistInfo,
     Artist,
     parse_mb_artist_tag,
+    artists_from_tags,
+    artist_tag_credits,
     artists_to_string
 };
@@ -0,0 +1,146 @@
+use std::{
+    cell::{Cell, RefCell},
+    sync::OnceLock
+};
+use gtk::{
+    glib,
+    prelude::*,
+    subclass::prelude::*
+};
+use glib::{clone, subclass::Signal};
+
+use super::song::MAX_RATING;
+
+mod imp {
+    use glib::{
+        ParamSpec,
+        ParamSpecUInt
+    };
+    use once_cell::sync::Lazy;
+    use super::*;
+
+    /// A row of star buttons. Setting the value property only updates the
+    /// displayed stars, while clicking on them emits "rated" with the new
+    /// value. This avoids writing back values that were just read from MPD.
+    #[derive(Default)]
+    pub struct Rating {
+        pub value: Cell<u32>,
+        pub stars: RefCell<Vec<gtk::Button>>
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Rating {
+        const NAME: &'static str = "EuphonicaRating";
+        type Type = super::Rating;
+        type ParentType = gtk::Box;
+    }
+
+    impl ObjectImpl for Rating {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            obj.add_css_class("rating");
+            obj.set_valign(gtk::Align::Center);
+            let mut stars = self.stars.borrow_mut();
+            for idx in 1..=MAX_RATING {
+                let star = gtk::Button::builder()
+                    .icon_name("non-starred-symbolic")
+                    .tooltip_text(format!("Rate {} out of {}", idx, MAX_RATING))
+                    .css_classes(["flat", "circular", "rating-star"])
+                    .build();
+                star.connect_clicked(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        // Clicking on the current rating again clears it.
+                        let new = if obj.value() == idx { 0 } else { idx };
+                        obj.emit_by_name::<()>("rated", &[&new]);
+                    }
+                ));
+                obj.append(&star);
+                stars.push(star);
+            }
+        }
+
+        fn properties() -> &'static [ParamSpec] {
+            static PROPERTIES: Lazy<Vec<ParamSpec>> = Lazy::new(|| {
+                vec![
+                    ParamSpecUInt::builder("value").maximum(MAX_RATING).build()
+                ]
+            });
+            PROPERTIES.as_ref()
+        }
+
+        fn property(&self, _id: usize, pspec: &ParamSpec) -> glib::Value {
+            match pspec.name() {
+                "value" => self.value.get().to_value(),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn set_property(&self, _id: usize, value: &glib::Value, pspec: &ParamSpec) {
+            match pspec.name() {
+                "value" => {
+                    if let Ok(v) = value.get::<u32>() {
+                        self.obj().set_value(v);
+                    }
+                }
+                _ => unimplemented!(),
+            }
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![
+                    // Only emitted on user interaction.
+                    Signal::builder("rated")
+                        .param_types([u32::static_type()])
+                        .build(),
+                ]
+            })
+        }
+    }
+
+    impl WidgetImpl for Rating {}
+
+    impl BoxImpl for Rating {}
+}
+
+glib::wrapper! {
+    pub struct Rating(ObjectSubclass<imp::Rating>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Orientable;
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl Rating {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn value(&self) -> u32 {
+        self.imp().value.get()
+    }
+
+    pub fn set_value(&self, new: u32) {
+        let new = new.min(MAX_RATING);
+        let old = self.imp().value.replace(new);
+        for (idx, star) in self.imp().stars.borrow().iter().enumerate() {
+            if (idx as u32) < new {
+                star.set_icon_name("starred-symbolic");
+            }
+            else {
+                star.set_icon_name("non-starred-symbolic");
+            }
+        }
+        if old != new {
+            self.notify("value");
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::borrow::Cow;
+use chrono::{DateTime, Utc};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use mpd::{search::Operation as QueryOperation, Query, Term};
+
+use super::{QualityGrade, Song};
+
+/// A single condition of a smart playlist. Genre is evaluated by MPD itself
+/// while the rest need data that MPD either cannot filter by (stickers) or
+/// simply does not have (play counts), so they are checked locally.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SmartRule {
+    Genre(String),
+    YearRange(Option<i32>, Option<i32>),  // Inclusive. Songs without a date never match.
+    MinRating(u32),
+    PlayCount(Option<u32>, Option<u32>),  // Inclusive. (None, Some(0)) means unplayed.
+    ModifiedWithinDays(u32),  // Last-modified timestamp as reported by MPD
+    MinQuality(QualityGrade)
+}
+
+impl SmartRule {
+    fn matches(&self, song: &Song, play_count: u32, now: DateTime<Utc>) -> bool {
+        match self {
+            // Already filtered by MPD
+            Self::Genre(_) => true,
+            Self::YearRange(from, to) => {
+                if let Some(date) = song.get_release_date() {
+                    let year = date.year();
+                    from.map_or(true, |from| year >= from) && to.map_or(true, |to| year <= to)
+                }
+                else {
+                    false
+                }
+            }
+            Self::MinRating(min) => song.get_rating() >= *min,
+            Self::PlayCount(min, max) => {
+                min.map_or(true, |min| play_count >= min) && max.map_or(true, |max| play_count <= max)
+            }
+            Self::ModifiedWithinDays(days) => {
+                song.get_last_modified()
+                    .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+                    .is_some_and(|ts| (now - ts.with_timezone(&Utc)).num_days() < *days as i64)
+            }
+            Self::MinQuality(grade) => song.get_quality_grade() >= *grade
+        }
+    }
+}
+
+/// A rule-based playlist whose contents are computed on demand. All rules
+/// must be satisfied for a song to be included.
+/// Definitions are stored in the metadata database, keyed by name.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SmartPlaylist {
+    pub name: String,
+    pub rules: Vec<SmartRule>,
+    pub limit: Option<u32>  // Maximum number of songs, or None for no limit
+}
+
+impl SmartPlaylist {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            rules: Vec::new(),
+            limit: None
+        }
+    }
+
+    /// Build the MPD-side part of this playlist's filter.
+    pub fn to_query(&self) -> Query<'static> {
+        let mut query = Query::new();
+        let mut empty = true;
+        for rule in self.rules.iter() {
+            if let SmartRule::Genre(genre) = rule {
+                query.and(Term::Tag(Cow::Borrowed("genre")), genre.clone());
+                empty = false;
+            }
+        }
+        if empty {
+            // MPD's find command needs at least one filter. This one
+            // matches every song in the database.
+            query.and_with_op(Term::File, QueryOperation::Contains, "");
+        }
+        query
+    }
+
+    /// Evaluate the locally-checked rules over candidates fetched using to_query(),
+    /// applying the song limit if there is one. Stickers must have already been applied.
+    pub fn filter(&self, candidates: &[Song], play_counts: &FxHashMap<String, u32>) -> Vec<Song> {
+        let now = Utc::now();
+        let matching = candidates
+            .iter()
+            .filter(|song| {
+                let play_count = play_counts.get(song.get_uri()).copied().unwrap_or(0);
+                self.rules.iter().all(|rule| rule.matches(song, play_count, now))
+            })
+            .cloned();
+        if let Some(limit) = self.limit {
+            matching.take(limit as usize).collect()
+        }
+        else {
+            matching.collect()
+        }
+    }
+}
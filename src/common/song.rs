@@ -8,18 +8,22 @@ use gtk::glib;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use mpd::status::AudioFormat;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
 
 use crate::{cache::Cache, meta_providers::Metadata, utils::strip_filename_linux};
 
 use super::{
     ArtistInfo,
     AlbumInfo,
-    parse_mb_artist_tag,
+    ReleaseType,
+    artists_from_tags,
     artists_to_string
 };
 
-// Mostly for eyecandy
-#[derive(Clone, Copy, Debug, glib::Enum, PartialEq, Default)]
+// Mostly for eyecandy. Variants are declared from worst to best so that
+// grades can be compared (e.g. by smart playlist rules).
+#[derive(Clone, Copy, Debug, glib::Enum, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 #[enum_type(name = "EuphonicaQualityGrade")]
 pub enum QualityGrade {
     #[default]
@@ -42,8 +46,28 @@ impl QualityGrade {
             Self::DSD => Some("format-dsd-symbolic")
         }
     }
+
+    // TODO: translatable
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Lossy => "Lossy",
+            Self::CD => "CD",
+            Self::HiRes => "Hi-Res",
+            Self::DSD => "DSD"
+        }
+    }
 }
 
+// Sticker names used to store per-song user data in MPD's sticker database.
+// Other clients (myMPD, Cantata, etc.) can read these too.
+pub const RATING_STICKER: &str = "rating";  // "0" to "5"
+pub const FAVORITE_STICKER: &str = "favorite";  // "1" or "0"
+pub const MAX_RATING: u32 = 5;
+
+/// Ratings & favourite flags of all songs under a folder, keyed by song URI.
+/// Songs with neither sticker are omitted.
+pub type SongStickers = FxHashMap<String, (u32, bool)>;
 
 fn parse_date(datestr: &str) -> Option<Date> {
     // MPD uses yyyy-MM-dd but the month and day may be optional.
@@ -102,6 +126,7 @@ pub struct SongInfo {
     // Store Date instead of string to save a tiny bit of memory.
     // Also gives us formatting flexibility in the future.
     release_date: Option<Date>,
+    genre: Option<String>,  // First Genre tag only
     // TODO: Add more fields for managing classical music, such as composer, ensemble and movement number
     quality_grade: QualityGrade,
     // MusicBrainz stuff
@@ -114,6 +139,10 @@ impl SongInfo {
         Self::default()
     }
 
+    pub fn get_quality_grade(&self) -> QualityGrade {
+        self.quality_grade
+    }
+
     pub fn into_album_info(self) -> Option<AlbumInfo> {
         self.album
     }
@@ -136,6 +165,7 @@ impl Default for SongInfo {
             track: Cell::new(-1),  // negative values indicate no track index 
             disc: Cell::new(-1),
             release_date: None,
+            genre: None,
             quality_grade: QualityGrade::Unknown,
             mbid: None,
             last_modified: None
@@ -167,7 +197,11 @@ mod imp {
     pub struct Song {
         pub info: OnceCell<SongInfo>,
         pub pos: Cell<u32>,  // stored at the wrapper level to allow easy local updating
-        pub is_playing: Cell<bool>
+        pub is_playing: Cell<bool>,
+        // Stickers are fetched separately from the song itself, so these
+        // are also kept at the wrapper level.
+        pub rating: Cell<u32>,
+        pub favorite: Cell<bool>
     }
 
     #[glib::object_subclass]
@@ -179,7 +213,9 @@ mod imp {
             Self {
                 info: OnceCell::new(),
                 pos: Cell::new(0),
-                is_playing: Cell::new(false)
+                is_playing: Cell::new(false),
+                rating: Cell::new(0),
+                favorite: Cell::new(false)
             }
         }
     }
@@ -202,7 +238,9 @@ mod imp {
                     ParamSpecInt64::builder("disc").read_only().build(),
                     ParamSpecObject::builder::<glib::BoxedAnyObject>("release-date").read_only().build(),  // boxes Option<time::Date>
                     ParamSpecString::builder("quality-grade").read_only().build(),
-                    ParamSpecString::builder("last-modified").read_only().build()
+                    ParamSpecString::builder("last-modified").read_only().build(),
+                    ParamSpecUInt::builder("rating").maximum(MAX_RATING).read_only().build(),
+                    ParamSpecBoolean::builder("favorite").read_only().build()
                 ]
             });
             PROPERTIES.as_ref()
@@ -229,6 +267,8 @@ mod imp {
                 // "release_date" => obj.get_release_date.to_value(),
                 "quality-grade" => obj.get_quality_grade().to_icon_name().to_value(),
                 "last-modified" => obj.get_last_modified().to_value(),
+                "rating" => obj.get_rating().to_value(),
+                "favorite" => obj.is_favorite().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -346,10 +386,65 @@ impl Song {
         self.get_info().quality_grade
     }
 
+    pub fn get_rating(&self) -> u32 {
+        self.imp().rating.get()
+    }
+
+    /// Set the locally-displayed rating. This does not write anything to MPD.
+    pub fn set_rating(&self, new: u32) {
+        let new = new.min(MAX_RATING);
+        let old = self.imp().rating.replace(new);
+        if old != new {
+            self.notify("rating");
+        }
+    }
+
+    pub fn is_favorite(&self) -> bool {
+        self.imp().favorite.get()
+    }
+
+    /// Set the locally-displayed favourite flag. This does not write anything to MPD.
+    pub fn set_favorite(&self, new: bool) {
+        let old = self.imp().favorite.replace(new);
+        if old != new {
+            self.notify("favorite");
+        }
+    }
+
+    /// Update rating or favourite flag from a single sticker value.
+    /// Unrelated stickers are ignored.
+    pub fn apply_sticker(&self, name: &str, value: &str) {
+        match name {
+            RATING_STICKER => self.set_rating(value.parse::<u32>().unwrap_or(0)),
+            FAVORITE_STICKER => self.set_favorite(value == "1"),
+            _ => {}
+        }
+    }
+
+    /// Update rating & favourite flag from the results of a sticker search under
+    /// the given folder. Songs outside that folder are left untouched, while
+    /// songs inside it that are missing from the results are reset.
+    pub fn apply_stickers(&self, base_uri: &str, stickers: &SongStickers) {
+        let uri = self.get_uri();
+        if !base_uri.is_empty() && !uri
+            .strip_prefix(base_uri)
+            .is_some_and(|rest| rest.starts_with('/'))
+        {
+            return;
+        }
+        let (rating, favorite) = stickers.get(uri).copied().unwrap_or((0, false));
+        self.set_rating(rating);
+        self.set_favorite(favorite);
+    }
+
     pub fn get_release_date(&self) -> Option<Date> {
         self.get_info().release_date
     }
 
+    pub fn get_genre(&self) -> Option<&str> {
+        self.get_info().genre.as_deref()
+    }
+
     pub fn get_mbid(&self) -> Option<&str> {
         self.get_info().mbid.as_deref()
     }
@@ -388,6 +483,10 @@ impl Song {
             meta.set_art_url(Some(path_string));
         }
         // TODO: disc & track num
+        let rating = self.get_rating();
+        if rating > 0 {
+            meta.set_user_rating(Some(rating as f64 / MAX_RATING as f64));
+        }
         meta
     }
 }
@@ -400,17 +499,6 @@ impl Default for Song {
 
 impl From<mpd::song::Song> for SongInfo {
     fn from(song: mpd::song::Song) -> Self {
-        let artists: Vec<ArtistInfo>;
-        if let Some(artist_str) = &song.artist {
-            // TODO: Find a way to detect classical works
-            artists = parse_mb_artist_tag(artist_str)
-                .iter()
-                .map(|s| ArtistInfo::new(s, false))
-                .collect();
-        }
-        else {
-            artists = Vec::with_capacity(0);
-        }
         let name: String;
         if let Some(title) = song.title {
             name = title;
@@ -425,14 +513,15 @@ impl From<mpd::song::Song> for SongInfo {
         let mut res = Self {
             uri: song.file,
             title: name,
-            artists,
-            artist_tag: song.artist,
+            artists: Vec::with_capacity(0),  // Filled in once all tags have been read
+            artist_tag: None,
             duration: song.duration,
             queue_id: None,
             album: None,
             track: Cell::new(-1),
             disc: Cell::new(-1),
             release_date: None,
+            genre: None,
             quality_grade: QualityGrade::Unknown,
             mbid: None,
             last_modified: song.last_mod
@@ -455,10 +544,16 @@ impl From<mpd::song::Song> for SongInfo {
                 res.quality_grade = QualityGrade::DSD;
             }
         }
+        // Artist-related tags can be multi-valued, in which case MPD returns one line per value.
+        let mut artist_credits: Vec<String> = song.artist.into_iter().collect();
+        let mut artist_names: Vec<String> = Vec::new();
         let mut artist_mbids: Vec<String> = Vec::new();
-        let mut album_artist_str: Option<String> = None;
+        let mut album_artist_credits: Vec<String> = Vec::new();
+        let mut album_artist_names: Vec<String> = Vec::new();
         let mut album_artist_mbids: Vec<String> = Vec::new();
         let mut album_mbid: Option<String> = None;
+        let mut release_types: Vec<String> = Vec::new();
+        let mut release_group_mbid: Option<String> = None;
         for (tag, val) in song.tags.into_iter() {
             match tag.to_lowercase().as_str() {
                 "album" => {
@@ -477,14 +572,15 @@ impl From<mpd::song::Song> for SongInfo {
                         panic!("Multiple Album tags found. Only one per song is supported.");
                     }
                 },
-                "albumartist" => {
-                    if album_artist_str.is_none() {
-                        let _ = album_artist_str.replace(val);
-                    }
-                    else {
-                        panic!("Multiple AlbumArtist tags found. Only one per song is supported (use MusicBrainz syntax to specify multiple artists).");
+                "artist" => {
+                    // Additional values of a multi-valued Artist tag
+                    if !artist_credits.contains(&val) {
+                        artist_credits.push(val);
                     }
                 },
+                "artists" => artist_names.push(val),
+                "albumartist" => album_artist_credits.push(val),
+                "albumartists" => album_artist_names.push(val),
                 // "date" => res.imp().release_date.replace(Some(val.clone())),
                 "format" => {
                     if let Some(extension) = maybe_extension {
@@ -504,6 +600,11 @@ impl From<mpd::song::Song> for SongInfo {
                         }
                     }
                 },
+                "genre" => {
+                    if res.genre.is_none() {
+                        let _ = res.genre.replace(val);
+                    }
+                }
                 "originaldate" => {
                     res.release_date = parse_date(val.as_ref());
                 },
@@ -536,6 +637,12 @@ impl From<mpd::song::Song> for SongInfo {
                     // initialising the artist objects
                     artist_mbids.push(val);
                 }
+                "musicbrainz_releasegroupid" => {
+                    let _ = release_group_mbid.replace(val);
+                }
+                "releasetype" | "musicbrainz_albumtype" | "musicbrainz_releasetype" => {
+                    release_types.push(val);
+                }
                 "musicbrainz_albumartistid" => {
                     // Can encounter this multiple times and/or before
                     // initialising the albumartist objects
@@ -545,24 +652,21 @@ impl From<mpd::song::Song> for SongInfo {
             }
         }
 
-        // Assume the artist IDs are given in the same order as the artist tags
-        for (idx, id) in artist_mbids.drain(..).enumerate() {
-            if idx < res.artists.len() {
-                let _ = res.artists[idx].mbid.replace(id);
-            }
+        res.artists = artists_from_tags(&artist_credits, &artist_names, artist_mbids);
+        if !artist_credits.is_empty() {
+            res.artist_tag = Some(artist_credits.join("; "));
         }
 
         if let Some(album) = res.album.as_mut() {
             album.mbid = album_mbid;
             album.release_date = res.release_date.clone();
-            // Assume the albumartist IDs are given in the same order as the albumartist tags
-            if let Some(s) = album_artist_str.as_mut() {
-                album.set_artists_from_string(s);
-                for (idx, id) in album_artist_mbids.drain(..).enumerate() {
-                    if idx < album.artists.len() {
-                        let _ = album.artists[idx].mbid.replace(id);
-                    }
-                }
+            // The Format tag might come after the Album tag
+            album.quality_grade = res.quality_grade;
+            album.release_type = ReleaseType::from_tag_values(&release_types);
+            album.release_group_mbid = release_group_mbid;
+            if !album_artist_credits.is_empty() {
+                album.artist_tag = Some(album_artist_credits.join("; "));
+                album.artists = artists_from_tags(&album_artist_credits, &album_artist_names, album_artist_mbids);
             }
         }
 
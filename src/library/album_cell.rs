@@ -20,7 +20,7 @@ use crate::{
     cache::{
         placeholders::ALBUMART_PLACEHOLDER, Cache, CacheState
     }, common::{
-        Album, AlbumInfo
+        Album, AlbumInfo, AlbumQuality
     }
 };
 
@@ -45,6 +45,7 @@ mod imp {
         pub album: RefCell<Option<Album>>,
         // Vector holding the bindings to properties of the Album GObject
         pub cover_signal_id: RefCell<Option<SignalHandlerId>>,
+        pub quality_signal_id: RefCell<Option<SignalHandlerId>>,
         pub cache: OnceCell<Rc<Cache>>
     }
 
@@ -71,8 +72,7 @@ mod imp {
             static PROPERTIES: Lazy<Vec<ParamSpec>> = Lazy::new(|| {
                 vec![
                     ParamSpecString::builder("title").build(),
-                    ParamSpecString::builder("artist").build(),
-                    ParamSpecString::builder("quality-grade").build()
+                    ParamSpecString::builder("artist").build()
                 ]
             });
             PROPERTIES.as_ref()
@@ -82,7 +82,6 @@ mod imp {
             match pspec.name() {
                 "title" => self.title.label().to_value(),
                 "artist" => self.artist.label().to_value(),
-                "quality-grade" => self.quality_grade.icon_name().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -102,16 +101,6 @@ mod imp {
                         obj.notify("artist");
                     }
                 }
-                "quality-grade" => {
-                    if let Ok(icon_name) = value.get::<&str>() {
-                        self.quality_grade.set_icon_name(Some(icon_name));
-                        self.quality_grade.set_visible(true);
-                    }
-                    else {
-                        self.quality_grade.set_icon_name(None);
-                        self.quality_grade.set_visible(false);
-                    }
-                }
                 _ => unimplemented!()
             }
         }
@@ -152,6 +141,23 @@ impl AlbumCell {
                 )
             ))
         );
+        let _ = res.imp().quality_signal_id.replace(
+            Some(res.imp().cache.get().unwrap().get_cache_state().connect_closure(
+                "album-quality-downloaded",
+                false,
+                closure_local!(
+                    #[weak(rename_to = this)]
+                    res,
+                    move |_: CacheState, folder_uri: String| {
+                        if let Some(album) = this.imp().album.borrow().as_ref() {
+                            if album.get_uri() == &folder_uri {
+                                this.update_quality(album.get_info());
+                            }
+                        }
+                    }
+                )
+            ))
+        );
         res
     }
 
@@ -166,11 +172,6 @@ impl AlbumCell {
             .property_expression("item")
             .chain_property::<Album>("artist")
             .bind(self, "artist", gtk::Widget::NONE);
-
-        item
-            .property_expression("item")
-            .chain_property::<Album>("quality-grade")
-            .bind(self, "quality-grade", gtk::Widget::NONE);
     }
 
     fn update_album_art(&self, info: &AlbumInfo) {
@@ -182,11 +183,32 @@ impl AlbumCell {
         }
     }
 
+    /// Show the quality grade of the whole album. Until that has been aggregated,
+    /// fall back to the grade of the song the album info was derived from.
+    fn update_quality(&self, info: &AlbumInfo) {
+        let quality = self.imp().cache.get().unwrap()
+            .load_album_quality(info, true)
+            .unwrap_or_else(|| AlbumQuality::from(info.quality_grade));
+        let icon = self.imp().quality_grade.get();
+        icon.set_icon_name(quality.icon_name());
+        icon.set_visible(quality.icon_name().is_some());
+        icon.set_tooltip_text(Some(&quality.description()));
+        // Not all tracks meet the displayed grade
+        if quality.is_mixed() {
+            icon.add_css_class("dim-label");
+        }
+        else {
+            icon.remove_css_class("dim-label");
+        }
+    }
+
     pub fn bind(&self, album: &Album) {
         // The string properties are bound using property expressions in setup().
-        // Here we only need to manually bind to the cache controller to fetch album art.
+        // Here we only need to manually bind to the cache controller to fetch album art
+        // and quality grades.
         // Set once first (like sync_create)
         self.update_album_art(album.get_info());
+        self.update_quality(album.get_info());
         let _ = self.imp().album.replace(Some(album.clone()));
     }
 
@@ -198,5 +220,8 @@ impl AlbumCell {
         if let Some(id) = self.imp().cover_signal_id.take() {
             self.imp().cache.get().unwrap().get_cache_state().disconnect(id);
         }
+        if let Some(id) = self.imp().quality_signal_id.take() {
+            self.imp().cache.get().unwrap().get_cache_state().disconnect(id);
+        }
     }
 }
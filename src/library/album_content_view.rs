@@ -21,12 +21,20 @@ use glib::{
 
 use super::{
     Library,
-    AlbumSongRow
+    AlbumSongRow,
+    ArtworkDialog,
+    ExportDialog,
+    MatchDialog,
+    match_dialog::MatchTarget
 };
 use crate::{
     cache::{
         placeholders::ALBUMART_PLACEHOLDER, Cache, CacheState
-    }, client::ClientState, common::{Album, AlbumInfo, Song}, utils::format_secs_as_duration
+    },
+    client::ClientState,
+    common::{Album, AlbumInfo, AlbumQuality, Song, SongStickers},
+    meta_providers::models::ArtworkMeta,
+    utils::format_secs_as_duration
 };
 
 mod imp {
@@ -53,6 +61,8 @@ mod imp {
         #[template_child]
         pub wiki_text: TemplateChild<gtk::Label>,
         #[template_child]
+        pub wiki_source: TemplateChild<gtk::Label>,
+        #[template_child]
         pub wiki_link: TemplateChild<gtk::LinkButton>,
         #[template_child]
         pub wiki_attrib: TemplateChild<gtk::Label>,
@@ -63,18 +73,38 @@ mod imp {
         pub track_count: TemplateChild<gtk::Label>,
         #[template_child]
         pub runtime: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub quality: TemplateChild<gtk::Label>,
 
         #[template_child]
         pub replace_queue: TemplateChild<gtk::Button>,
         #[template_child]
         pub append_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub show_artwork: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub refresh_meta: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub pick_match: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub export_cover: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub rating_filter: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub sort_mode: TemplateChild<gtk::DropDown>,
 
         pub song_list: gio::ListStore,
+        // Sort & filter by rating. Track order is kept otherwise.
+        pub filter: gtk::CustomFilter,
+        pub sorter: gtk::CustomSorter,
 
         pub album: RefCell<Option<Album>>,
+        // From the album's metadata, shown on demand in an ArtworkDialog
+        pub artwork: RefCell<Vec<ArtworkMeta>>,
         pub bindings: RefCell<Vec<Binding>>,
         pub cover_signal_id: RefCell<Option<SignalHandlerId>>,
         pub cache: OnceCell<Rc<Cache>>,
+        pub library: OnceCell<Library>,
     }
 
     impl Default for AlbumContentView {
@@ -89,17 +119,29 @@ mod imp {
                 collapse_infobox: TemplateChild::default(),
                 wiki_box: TemplateChild::default(),
                 wiki_text: TemplateChild::default(),
+                wiki_source: TemplateChild::default(),
                 wiki_link: TemplateChild::default(),
                 wiki_attrib: TemplateChild::default(),
                 runtime: TemplateChild::default(),
+                quality: TemplateChild::default(),
                 content: TemplateChild::default(),
                 song_list: gio::ListStore::new::<Song>(),
                 replace_queue: TemplateChild::default(),
                 append_queue: TemplateChild::default(),
+                show_artwork: TemplateChild::default(),
+                refresh_meta: TemplateChild::default(),
+                pick_match: TemplateChild::default(),
+                export_cover: TemplateChild::default(),
+                rating_filter: TemplateChild::default(),
+                sort_mode: TemplateChild::default(),
+                filter: gtk::CustomFilter::default(),
+                sorter: gtk::CustomSorter::default(),
                 album: RefCell::new(None),
+                artwork: RefCell::new(Vec::new()),
                 bindings: RefCell::new(Vec::new()),
                 cover_signal_id: RefCell::new(None),
-                cache: OnceCell::new()
+                cache: OnceCell::new(),
+                library: OnceCell::new()
             }
         }
     }
@@ -157,9 +199,25 @@ impl AlbumContentView {
         let wiki_text = self.imp().wiki_text.get();
         let wiki_link = self.imp().wiki_link.get();
         let wiki_attrib = self.imp().wiki_attrib.get();
+        let wiki_source = self.imp().wiki_source.get();
+        let show_artwork = self.imp().show_artwork.get();
         if let Some(meta) = cache.load_cached_album_meta(
             album.get_info()
         ) {
+            show_artwork.set_visible(!meta.artwork.is_empty());
+            // TODO: translatable
+            show_artwork.set_tooltip_text(Some(&match meta.source_of("artwork") {
+                Some(source) => format!("Browse covers, booklet scans and other artwork ({})", source.describe()),
+                None => "Browse covers, booklet scans and other artwork".to_owned()
+            }));
+            let _ = self.imp().artwork.replace(meta.artwork);
+            if let Some(source) = meta.source_of("wiki") {
+                wiki_source.set_visible(true);
+                wiki_source.set_label(&source.describe());
+            }
+            else {
+                wiki_source.set_visible(false);
+            }
             if let Some(wiki) = meta.wiki {
                 wiki_box.set_visible(true);
                 wiki_text.set_label(&wiki.content);
@@ -177,6 +235,8 @@ impl AlbumContentView {
             }
         }
         else {
+            show_artwork.set_visible(false);
+            self.imp().artwork.borrow_mut().clear();
             wiki_box.set_visible(false);
         }
     }
@@ -197,6 +257,21 @@ impl AlbumContentView {
                 }
             )
         );
+        cache.get_cache_state().connect_closure(
+            "album-quality-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: CacheState, folder_uri: String| {
+                    if let Some(album) = this.imp().album.borrow().as_ref() {
+                        if folder_uri == album.get_uri() {
+                            this.update_quality(album.get_info());
+                        }
+                    }
+                }
+            )
+        );
         cache.get_cache_state().connect_closure(
             "album-meta-downloaded",
             false,
@@ -227,6 +302,41 @@ impl AlbumContentView {
                 }
             )
         );
+        client_state.connect_closure(
+            "sticker-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, typ: String, uri: String, name: String, value: String| {
+                    if typ == "song" {
+                        for song in this.imp().song_list.iter::<Song>().flatten() {
+                            if song.get_uri() == uri {
+                                song.apply_sticker(&name, &value);
+                            }
+                        }
+                        this.on_ratings_changed();
+                    }
+                }
+            )
+        );
+        client_state.connect_closure(
+            "song-stickers-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, base_uri: String, boxed: glib::BoxedAnyObject| {
+                    let stickers = boxed.borrow::<SongStickers>();
+                    for song in this.imp().song_list.iter::<Song>().flatten() {
+                        song.apply_stickers(&base_uri, &stickers);
+                    }
+                    this.on_ratings_changed();
+                }
+            )
+        );
+        self.setup_sort_filter();
+        let _ = self.imp().library.set(library.clone());
 
         let _ = self.imp().cache.set(cache);
         let infobox_revealer = self.imp().infobox_revealer.get();
@@ -286,6 +396,45 @@ impl AlbumContentView {
                 }
             )
         );
+        self.imp().show_artwork.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let Some(cache) = this.imp().cache.get() {
+                    let dialog = ArtworkDialog::new(&this.imp().artwork.borrow(), cache.clone());
+                    dialog.present(Some(&this));
+                }
+            }
+        ));
+        self.imp().refresh_meta.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let (Some(cache), Some(album)) = (this.imp().cache.get(), this.imp().album.borrow().as_ref()) {
+                    cache.refresh_album_meta(album.get_info());
+                }
+            }
+        ));
+        self.imp().pick_match.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let (Some(cache), Some(album)) = (this.imp().cache.get(), this.imp().album.borrow().as_ref()) {
+                    let dialog = MatchDialog::new(MatchTarget::Album(album.get_info().clone()), cache.clone());
+                    dialog.present(Some(&this));
+                }
+            }
+        ));
+        self.imp().export_cover.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let (Some(cache), Some(album)) = (this.imp().cache.get(), this.imp().album.borrow().as_ref()) {
+                    let dialog = ExportDialog::new(vec![album.get_info().clone()], cache.clone());
+                    dialog.present(Some(&this));
+                }
+            }
+        ));
 
         // Set up factory
         let factory = SignalListItemFactory::new();
@@ -337,6 +486,78 @@ impl AlbumContentView {
         self.imp().content.set_factory(Some(&factory));
     }
 
+    fn setup_sort_filter(&self) {
+        let rating_filter = self.imp().rating_filter.get();
+        self.imp().filter.set_filter_func(
+            clone!(
+                #[weak]
+                rating_filter,
+                #[upgrade_or]
+                true,
+                move |obj| {
+                    let song = obj
+                        .downcast_ref::<Song>()
+                        .expect("Filter obj has to be a common::Song.");
+                    // Matched by index. Refer to the dropdown's model in the UI file.
+                    match rating_filter.selected() {
+                        0 => true,
+                        6 => song.is_favorite(),
+                        min_rating => song.get_rating() >= min_rating
+                    }
+                }
+            )
+        );
+        rating_filter.connect_selected_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.imp().filter.changed(gtk::FilterChange::Different);
+            }
+        ));
+
+        let sort_mode = self.imp().sort_mode.get();
+        self.imp().sorter.set_sort_func(
+            clone!(
+                #[weak]
+                sort_mode,
+                #[upgrade_or]
+                gtk::Ordering::Equal,
+                move |obj1, obj2| {
+                    let song1 = obj1
+                        .downcast_ref::<Song>()
+                        .expect("Sort obj has to be a common::Song.");
+                    let song2 = obj2
+                        .downcast_ref::<Song>()
+                        .expect("Sort obj has to be a common::Song.");
+                    match sort_mode.selected() {
+                        // Highest-rated first. Sorting is stable, so songs with the
+                        // same rating stay in track order.
+                        1 => song2.get_rating().cmp(&song1.get_rating()).into(),
+                        // Keep the order we received them in (track order).
+                        _ => gtk::Ordering::Equal
+                    }
+                }
+            )
+        );
+        sort_mode.connect_selected_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.imp().sorter.changed(gtk::SorterChange::Different);
+            }
+        ));
+    }
+
+    /// Re-run sorting & filtering after ratings have been updated.
+    fn on_ratings_changed(&self) {
+        if self.imp().rating_filter.selected() > 0 {
+            self.imp().filter.changed(gtk::FilterChange::Different);
+        }
+        if self.imp().sort_mode.selected() > 0 {
+            self.imp().sorter.changed(gtk::SorterChange::Different);
+        }
+    }
+
     /// Returns true if an album art was successfully retrieved.
     /// On false, we will want to call cache.ensure_local_album_art()
     fn update_cover(&self, info: &AlbumInfo) -> bool {
@@ -353,6 +574,17 @@ impl AlbumContentView {
         false
     }
 
+    /// Show the quality range of all tracks, or that of the first track until
+    /// the whole album has been checked.
+    fn update_quality(&self, info: &AlbumInfo) {
+        if let Some(cache) = self.imp().cache.get() {
+            let quality = cache
+                .load_album_quality(info, true)
+                .unwrap_or_else(|| AlbumQuality::from(info.quality_grade));
+            self.imp().quality.set_label(&quality.description());
+        }
+    }
+
     pub fn bind(&self, album: Album) {
         println!("Binding to album: {:?}", &album);
         let title_label = self.imp().title.get();
@@ -412,9 +644,15 @@ impl AlbumContentView {
         let info = album.get_info();
         println!("[AlbumContentView] Updating cover");
         self.update_cover(info);
+        self.update_quality(info);
 
         // Save reference to album object
-        let sel_model = gtk::NoSelection::new(Some(self.imp().song_list.clone()));
+        let filter_model = gtk::FilterListModel::new(
+            Some(self.imp().song_list.clone()),
+            Some(self.imp().filter.clone())
+        );
+        let sort_model = gtk::SortListModel::new(Some(filter_model), Some(self.imp().sorter.clone()));
+        let sel_model = gtk::NoSelection::new(Some(sort_model));
         self.imp().content.set_model(Some(&sel_model));
         self.imp().album.borrow_mut().replace(album);
     }
@@ -431,6 +669,8 @@ impl AlbumContentView {
         }
         // Unset metadata widgets
         self.imp().wiki_box.set_visible(false);
+        self.imp().show_artwork.set_visible(false);
+        self.imp().artwork.borrow_mut().clear();
         self.imp().song_list.remove_all();
     }
 
@@ -452,5 +692,9 @@ impl AlbumContentView {
                     .sum::<u64>() as f64
             )
         );
+        // Ratings & favourites are stored separately as stickers
+        if let (Some(library), Some(album)) = (self.imp().library.get(), self.imp().album.borrow().as_ref()) {
+            library.get_song_stickers(album.get_uri());
+        }
     }
 }
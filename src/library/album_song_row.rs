@@ -9,12 +9,13 @@ use gtk::{
 use glib::{
     clone,
     closure,
+    closure_local,
     Object,
     SignalHandlerId
 };
 
 use crate::{
-    common::Song,
+    common::{Rating, Song},
     utils::format_secs_as_duration
 };
 
@@ -47,9 +48,15 @@ mod imp {
         pub artist_name: TemplateChild<Label>,
         #[template_child]
         pub duration: TemplateChild<Label>,
-        // For unbinding the queue buttons when not bound to a song (i.e. being recycled)
+        #[template_child]
+        pub rating: TemplateChild<Rating>,
+        #[template_child]
+        pub favorite: TemplateChild<gtk::Button>,
+        // For unbinding the queue & sticker buttons when not bound to a song (i.e. being recycled)
         pub replace_queue_id: RefCell<Option<SignalHandlerId>>,
         pub append_queue_id: RefCell<Option<SignalHandlerId>>,
+        pub rating_id: RefCell<Option<SignalHandlerId>>,
+        pub favorite_id: RefCell<Option<SignalHandlerId>>,
         pub library: OnceCell<Library>
     }
 
@@ -198,6 +205,19 @@ impl AlbumSongRow {
             .property_expression("item")
             .chain_property::<Song>("quality-grade")
             .bind(self, "quality-grade", gtk::Widget::NONE);
+
+        item
+            .property_expression("item")
+            .chain_property::<Song>("rating")
+            .bind(&self.imp().rating.get(), "value", gtk::Widget::NONE);
+
+        item
+            .property_expression("item")
+            .chain_property::<Song>("favorite")
+            .chain_closure::<String>(closure!(|_: Option<Object>, favorite: bool| {
+                if favorite { "heart-filled-symbolic" } else { "heart-outline-symbolic" }
+            }))
+            .bind(&self.imp().favorite.get(), "icon-name", gtk::Widget::NONE);
     }
 
     pub fn bind(&self, song: &Song) {
@@ -243,6 +263,48 @@ impl AlbumSongRow {
             // Unbind old ID
             self.imp().append_queue.disconnect(old_id);
         }
+        if let Some(old_id) = self.imp().rating_id.replace(
+            Some(
+                self.imp().rating.connect_closure(
+                    "rated",
+                    false,
+                    closure_local!(
+                        #[weak(rename_to = this)]
+                        self,
+                        #[strong]
+                        uri,
+                        move |_: Rating, rating: u32| {
+                            if let Some(library) = this.imp().library.get() {
+                                library.set_song_rating(&uri, rating);
+                            }
+                        }
+                    )
+                )
+            )
+        ) {
+            // Unbind old ID
+            self.imp().rating.disconnect(old_id);
+        }
+        if let Some(old_id) = self.imp().favorite_id.replace(
+            Some(
+                self.imp().favorite.connect_clicked(
+                    clone!(
+                        #[weak(rename_to = this)]
+                        self,
+                        #[weak]
+                        song,
+                        move |_| {
+                            if let Some(library) = this.imp().library.get() {
+                                library.set_song_favorite(song.get_uri(), !song.is_favorite());
+                            }
+                        }
+                    )
+                )
+            )
+        ) {
+            // Unbind old ID
+            self.imp().favorite.disconnect(old_id);
+        }
     }
 
     pub fn unbind(&self) {
@@ -252,5 +314,11 @@ impl AlbumSongRow {
         if let Some(id) = self.imp().append_queue_id.borrow_mut().take() {
             self.imp().append_queue.disconnect(id);
         }
+        if let Some(id) = self.imp().rating_id.borrow_mut().take() {
+            self.imp().rating.disconnect(id);
+        }
+        if let Some(id) = self.imp().favorite_id.borrow_mut().take() {
+            self.imp().favorite.disconnect(id);
+        }
     }
 }
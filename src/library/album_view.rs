@@ -14,10 +14,11 @@ use glib::clone;
 use super::{
     Library,
     AlbumCell,
-    AlbumContentView
+    AlbumContentView,
+    ExportDialog
 };
 use crate::{
-    common::Album,
+    common::{Album, AlbumInfo},
     cache::Cache,
     client::ClientState,
     utils::{settings_manager, g_cmp_str_options, g_cmp_options, g_search_substr}
@@ -150,7 +151,10 @@ impl AlbumView {
     }
 
     pub fn setup(&self, library: Library, cache: Rc<Cache>, client_state: ClientState) {
-        self.setup_sort();
+        let actions = gio::SimpleActionGroup::new();
+        self.setup_sort(&actions);
+        self.setup_export(&actions, cache.clone());
+        self.insert_action_group("albumview", Some(&actions));
         self.setup_search();
         self.imp().library.set(library.clone()).expect("Cannot init AlbumView with Library");
         self.setup_gridview(client_state.clone(), cache.clone());
@@ -162,20 +166,18 @@ impl AlbumView {
         });
     }
 
-    fn setup_sort(&self) {
+    fn setup_sort(&self, actions: &gio::SimpleActionGroup) {
         // TODO: use albumsort & albumartistsort tags where available
         // Setup sort widget & actions
         let settings = settings_manager();
         let state = settings.child("state").child("albumview");
         let library_settings = settings.child("library");
-        let actions = gio::SimpleActionGroup::new();
         actions.add_action(
             &state.create_action("sort-by")
         );
         actions.add_action(
             &state.create_action("sort-direction")
         );
-        self.insert_action_group("albumview", Some(&actions));
         let sort_dir = self.imp().sort_dir.get();
         state
             .bind(
@@ -295,6 +297,28 @@ impl AlbumView {
         );
     }
 
+    /// Export the covers of all albums currently shown, i.e. matching the search if any.
+    fn setup_export(&self, actions: &gio::SimpleActionGroup, cache: Rc<Cache>) {
+        let export_action = gio::ActionEntry::<gio::SimpleActionGroup>::builder("export-covers")
+            .activate(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _, _| {
+                    let Some(model) = this.imp().grid_view.model() else {
+                        return;
+                    };
+                    let albums: Vec<AlbumInfo> = (0..model.n_items())
+                        .filter_map(|idx| model.item(idx).and_downcast::<Album>())
+                        .map(|album| album.get_info().clone())
+                        .collect();
+                    let dialog = ExportDialog::new(albums, cache.clone());
+                    dialog.present(Some(&this));
+                }
+            ))
+            .build();
+        actions.add_action_entries([export_action]);
+    }
+
     fn setup_search(&self) {
         let settings = settings_manager();
         let library_settings = settings.child("library");
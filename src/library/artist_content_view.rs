@@ -1,7 +1,9 @@
 use std::{
-    cell::{OnceCell, RefCell},
+    cell::{Cell, OnceCell, RefCell},
     rc::Rc,
 };
+use chrono::Datelike;
+use rustc_hash::{FxHashMap, FxHashSet};
 use adw::subclass::prelude::*;
 use gtk::{
     gdk,
@@ -22,15 +24,61 @@ use glib::{
 use super::{
     Library,
     ArtistSongRow,
+    MatchDialog,
+    match_dialog::MatchTarget,
     AlbumCell
 };
 use crate::{
     cache::{
+        placeholders::ALBUMART_PLACEHOLDER,
         Cache,
         CacheState
-    }, client::ClientState, common::{Album, AlbumInfo, Artist, ArtistInfo, Song}
+    },
+    client::ClientState,
+    common::{artist_tag_credits, Album, AlbumInfo, Artist, ArtistInfo, ReleaseType, Song, SongStickers},
+    meta_providers::{models::{AlbumMeta, ReleaseGroupMeta}, musicbrainz::release_group_front_url},
+    utils::meta_provider_settings
 };
 
+// Discography sections in display order. The last one holds albums the artist
+// is credited on without being one of their album artists.
+// TODO: translatable
+const SECTION_TITLES: [&str; 6] = ["Albums", "EPs", "Singles", "Compilations", "Live", "Appears on"];
+const APPEARS_ON: usize = 5;
+
+fn section_of(release_type: ReleaseType) -> usize {
+    match release_type {
+        ReleaseType::Album => 0,
+        ReleaseType::EP => 1,
+        ReleaseType::Single => 2,
+        ReleaseType::Compilation => 3,
+        ReleaseType::Live => 4
+    }
+}
+
+/// Lowercase alphanumerics only, ignoring bracketed parts such as "(Deluxe Edition)".
+fn normalise_title(title: &str) -> String {
+    let mut res = String::with_capacity(title.len());
+    let mut depth: u32 = 0;
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth == 0 && c.is_alphanumeric() => res.extend(c.to_lowercase()),
+            _ => {}
+        }
+    }
+    res
+}
+
+/// Albums without an AlbumArtist tag are assumed to be the track artist's own.
+fn is_album_artist(album: &Album, name: &str) -> bool {
+    let artists = album.get_artists();
+    artists.is_empty()
+        || artists.iter().any(|artist| artist.name == name)
+        || album.get_artist_tag().is_some_and(|tag| artist_tag_credits(tag, name))
+}
+
 mod imp {
     use std::sync::OnceLock;
 
@@ -60,6 +108,8 @@ mod imp {
         #[template_child]
         pub bio_text: TemplateChild<gtk::Label>,
         #[template_child]
+        pub bio_source: TemplateChild<gtk::Label>,
+        #[template_child]
         pub bio_link: TemplateChild<gtk::LinkButton>,
         #[template_child]
         pub bio_attrib: TemplateChild<gtk::Label>,
@@ -74,11 +124,31 @@ mod imp {
         pub replace_queue: TemplateChild<gtk::Button>,
         #[template_child]
         pub append_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub refresh_meta: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub pick_match: TemplateChild<gtk::Button>,
 
         // Discography sub-view
         #[template_child]
-        pub album_subview: TemplateChild<gtk::GridView>,
-        pub album_list: gio::ListStore,
+        pub discography: TemplateChild<gtk::Box>,
+        // One list per entry in SECTION_TITLES
+        pub album_lists: [gio::ListStore; 6],
+        #[template_child]
+        pub missing_section: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub missing_albums: TemplateChild<gtk::FlowBox>,
+        // Cover widgets of missing albums, keyed by cover URL
+        pub missing_covers: RefCell<FxHashMap<String, gtk::Picture>>,
+        // Coalesces missing album updates while albums are still arriving
+        pub missing_update_queued: Cell<bool>,
+        // Release group MBID of each local album, from its tags or cached metadata,
+        // keyed by folder URI. Discographies only list release groups.
+        pub owned_mbids: RefCell<FxHashMap<String, String>>,
+        // Albums whose cached metadata is yet to be loaded in the background
+        pub meta_load_queue: RefCell<Vec<AlbumInfo>>,
+        // From ArtistMeta, or from tags if the metadata providers have not found one
+        pub artist_mbid: RefCell<Option<String>>,
 
         pub artist: RefCell<Option<Artist>>,
         pub bindings: RefCell<Vec<Binding>>,
@@ -97,6 +167,7 @@ mod imp {
                 collapse_infobox: TemplateChild::default(),
                 bio_box: TemplateChild::default(),
                 bio_text: TemplateChild::default(),
+                bio_source: TemplateChild::default(),
                 bio_link: TemplateChild::default(),
                 bio_attrib: TemplateChild::default(),
                 // runtime: TemplateChild::default(),
@@ -105,9 +176,18 @@ mod imp {
                 song_list: gio::ListStore::new::<Song>(),
                 replace_queue: TemplateChild::default(),
                 append_queue: TemplateChild::default(),
+                refresh_meta: TemplateChild::default(),
+                pick_match: TemplateChild::default(),
                 // Discography sub-view
-                album_subview: TemplateChild::default(),
-                album_list: gio::ListStore::new::<Album>(),
+                discography: TemplateChild::default(),
+                album_lists: std::array::from_fn(|_| gio::ListStore::new::<Album>()),
+                missing_section: TemplateChild::default(),
+                missing_albums: TemplateChild::default(),
+                missing_covers: RefCell::new(FxHashMap::default()),
+                missing_update_queued: Cell::new(false),
+                owned_mbids: RefCell::new(FxHashMap::default()),
+                meta_load_queue: RefCell::new(Vec::new()),
+                artist_mbid: RefCell::new(None),
                 artist: RefCell::new(None),
                 bindings: RefCell::new(Vec::new()),
                 avatar_signal_id: RefCell::new(None),
@@ -180,7 +260,29 @@ impl ArtistContentView {
         let bio_text = self.imp().bio_text.get();
         let bio_link = self.imp().bio_link.get();
         let bio_attrib = self.imp().bio_attrib.get();
-        if let Some(meta) = cache.load_cached_artist_meta(artist.get_info()) {
+        let bio_source = self.imp().bio_source.get();
+        let meta = cache.load_cached_artist_meta(artist.get_info());
+        let mbid = meta
+            .as_ref()
+            .and_then(|meta| meta.mbid.clone())
+            .or_else(|| artist.get_info().mbid.clone());
+        if let Some(mbid) = mbid.as_ref() {
+            if meta_provider_settings("musicbrainz").boolean("show-missing-albums") {
+                cache.ensure_cached_artist_discography(mbid);
+            }
+        }
+        if *self.imp().artist_mbid.borrow() != mbid {
+            let _ = self.imp().artist_mbid.replace(mbid);
+            self.queue_update_missing_albums();
+        }
+        if let Some(meta) = meta {
+            if let Some(source) = meta.source_of("bio") {
+                bio_source.set_visible(true);
+                bio_source.set_label(&source.describe());
+            }
+            else {
+                bio_source.set_visible(false);
+            }
             if let Some(bio) = meta.bio {
                 bio_box.set_visible(true);
                 bio_text.set_label(&bio.content);
@@ -204,6 +306,29 @@ impl ArtistContentView {
 
     #[inline(always)]
     fn setup_info_box(&self, cache: Rc<Cache>) {
+        self.imp().refresh_meta.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            cache,
+            move |_| {
+                if let Some(artist) = this.imp().artist.borrow().as_ref() {
+                    cache.refresh_artist_meta(artist.get_info());
+                }
+            }
+        ));
+        self.imp().pick_match.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            cache,
+            move |_| {
+                if let Some(artist) = this.imp().artist.borrow().as_ref() {
+                    let dialog = MatchDialog::new(MatchTarget::Artist(artist.get_info().clone()), cache.clone());
+                    dialog.present(Some(&this));
+                }
+            }
+        ));
         cache.get_cache_state().connect_closure(
             "artist-avatar-downloaded",
             false,
@@ -274,15 +399,50 @@ impl ArtistContentView {
                 self,
                 #[weak]
                 cache,
+                #[weak]
+                library,
                 move |_: ClientState, name: String, songs: glib::BoxedAnyObject| {
                     if let Some(artist) = this.imp().artist.borrow().as_ref() {
                         if name == artist.get_name() {
                             this.add_songs(songs.borrow::<Vec<Song>>().as_ref(), cache);
+                            // An artist's songs can be anywhere in the library.
+                            library.get_song_stickers("");
+                        }
+                    }
+                }
+            )
+        );
+        client_state.connect_closure(
+            "sticker-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, typ: String, uri: String, name: String, value: String| {
+                    if typ == "song" {
+                        for song in this.imp().song_list.iter::<Song>().flatten() {
+                            if song.get_uri() == uri {
+                                song.apply_sticker(&name, &value);
+                            }
                         }
                     }
                 }
             )
         );
+        client_state.connect_closure(
+            "song-stickers-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, base_uri: String, boxed: glib::BoxedAnyObject| {
+                    let stickers = boxed.borrow::<SongStickers>();
+                    for song in this.imp().song_list.iter::<Song>().flatten() {
+                        song.apply_stickers(&base_uri, &stickers);
+                    }
+                }
+            )
+        );
 
         // TODO
         // let replace_queue_btn = self.imp().replace_queue.get();
@@ -378,7 +538,6 @@ impl ArtistContentView {
     }
 
     fn setup_album_subview(&self, library: Library, cache: Rc<Cache>, client_state: ClientState) {
-        // TODO: handle click (switch to album tab & push album content page)
         // Unlike songs, we receive albums one by one.
         client_state.connect_closure(
             "artist-album-basic-info-downloaded",
@@ -397,8 +556,69 @@ impl ArtistContentView {
                 }
             )
         );
+        // Albums whose tags don't specify a release type are shown under Albums until
+        // we learn their release group type from the metadata providers.
+        cache.get_cache_state().connect_closure(
+            "album-meta-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                cache,
+                move |_: CacheState, folder_uri: String| {
+                    // The album's release type & MBID might have just become known.
+                    if let Some(album) = this.find_album(&folder_uri) {
+                        this.queue_load_album_meta(album.get_info().clone(), cache);
+                    }
+                }
+            )
+        );
+        cache.get_cache_state().connect_closure(
+            "artist-discography-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: CacheState, mbid: String| {
+                    if this.imp().artist_mbid.borrow().as_deref() == Some(mbid.as_str()) {
+                        this.queue_update_missing_albums();
+                    }
+                }
+            )
+        );
+        cache.get_cache_state().connect_closure(
+            "remote-image-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                cache,
+                move |_: CacheState, url: String| {
+                    if let Some(cover) = this.imp().missing_covers.borrow().get(&url) {
+                        if let Some(tex) = cache.load_cached_remote_image(&url, false) {
+                            cover.set_paintable(Some(&tex));
+                        }
+                    }
+                }
+            )
+        );
+        meta_provider_settings("musicbrainz").connect_changed(
+            Some("show-missing-albums"),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _| {
+                    if let Some(artist) = this.imp().artist.borrow().as_ref() {
+                        this.update_meta(artist);
+                    }
+                    this.queue_update_missing_albums();
+                }
+            )
+        );
 
-        // Set up factory
+        // Set up factory. It is shared by the grids of all sections.
         let factory = SignalListItemFactory::new();
         factory.connect_setup(clone!(
             #[weak]
@@ -443,22 +663,47 @@ impl ArtistContentView {
             child.unbind();
         });
 
-        // Set the factory of the list view
-        self.imp().album_subview.set_factory(Some(&factory));
-        let sel_model = gtk::SingleSelection::new(Some(self.imp().album_list.clone()));
-        self.imp().album_subview.set_model(Some(&sel_model));
-        self.imp().album_subview.connect_activate(clone!(
-            #[weak(rename_to = this)]
-            self,
-            move |view, position| {
-            let model = view.model().expect("The model has to exist.");
-            let album = model
-                .item(position)
-                .and_downcast::<Album>()
-                .expect("The item has to be a `common::Album`.");
+        // Build one titled grid per section. Empty sections are hidden.
+        let discography = self.imp().discography.get();
+        for (title, album_list) in SECTION_TITLES.iter().zip(self.imp().album_lists.iter()) {
+            let section = gtk::Box::new(gtk::Orientation::Vertical, 6);
+            section.set_visible(false);
+            let heading = gtk::Label::new(Some(title));
+            heading.set_halign(gtk::Align::Start);
+            heading.add_css_class("heading");
+            section.append(&heading);
 
-            this.emit_by_name::<()>("album-clicked", &[&album.to_value()]);
-        }));
+            let grid = gtk::GridView::new(
+                Some(gtk::SingleSelection::new(Some(album_list.clone()))),
+                Some(factory.clone())
+            );
+            grid.set_orientation(gtk::Orientation::Vertical);
+            grid.set_min_columns(1);
+            grid.set_single_click_activate(true);
+            grid.add_css_class("no-bg");
+            grid.connect_activate(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |view, position| {
+                let model = view.model().expect("The model has to exist.");
+                let album = model
+                    .item(position)
+                    .and_downcast::<Album>()
+                    .expect("The item has to be a `common::Album`.");
+
+                this.emit_by_name::<()>("album-clicked", &[&album.to_value()]);
+            }));
+            section.append(&grid);
+
+            album_list.connect_items_changed(clone!(
+                #[weak]
+                section,
+                move |list, _, _, _| {
+                    section.set_visible(list.n_items() > 0);
+                }
+            ));
+            discography.append(&section);
+        }
 
         // Hook up buttons
         let replace_queue_btn = self.imp().replace_queue.get();
@@ -553,9 +798,212 @@ impl ArtistContentView {
     }
 
     fn add_album(&self, album: Album, cache: Rc<Cache>) {
-        self.imp().album_list.append(&album);
+        let is_own = match self.imp().artist.borrow().as_ref() {
+            Some(artist) => is_album_artist(&album, artist.get_name()),
+            None => true
+        };
+        let section: usize;
+        if !is_own {
+            section = APPEARS_ON;
+        }
+        else if let Some(release_type) = album.get_release_type() {
+            section = section_of(release_type);
+        }
+        else {
+            // Shown under Albums until the metadata providers tell us its release group type.
+            section = 0;
+            cache.ensure_cached_album_meta(album.get_info());
+        }
+        self.imp().album_lists[section].append(&album);
         cache.ensure_cached_album_art(album.get_info(), false);
-        self.imp().album_count.set_label(&self.imp().album_list.n_items().to_string());
+        if let Some(mbid) = album.get_release_group_mbid() {
+            self.imp().owned_mbids.borrow_mut().insert(album.get_uri().to_owned(), mbid.to_owned());
+        }
+        if (is_own && album.get_release_type().is_none()) || album.get_release_group_mbid().is_none() {
+            self.queue_load_album_meta(album.get_info().clone(), cache);
+        }
+        self.update_album_count();
+        self.queue_update_missing_albums();
+    }
+
+    fn find_album(&self, folder_uri: &str) -> Option<Album> {
+        self.imp().album_lists
+            .iter()
+            .flat_map(|list| list.iter::<Album>().flatten())
+            .find(|album| album.get_uri() == folder_uri)
+    }
+
+    /// Load cached metadata of listed albums in one batch, off the main thread.
+    fn queue_load_album_meta(&self, album: AlbumInfo, cache: Rc<Cache>) {
+        let mut queue = self.imp().meta_load_queue.borrow_mut();
+        queue.push(album);
+        if queue.len() > 1 {
+            return;
+        }
+        glib::idle_add_local_once(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move || {
+                let albums = this.imp().meta_load_queue.take();
+                let handle = cache.load_cached_album_metas(&albums);
+                glib::MainContext::default().spawn_local(clone!(
+                    #[weak]
+                    this,
+                    async move {
+                        for (folder_uri, meta) in handle.await.unwrap_or_default().into_iter() {
+                            this.apply_album_meta(&folder_uri, meta);
+                        }
+                    }
+                ));
+            }
+        ));
+    }
+
+    /// Fill in what an album's tags lack from its cached metadata.
+    fn apply_album_meta(&self, folder_uri: &str, meta: AlbumMeta) {
+        // Might have been loaded for a previously shown artist.
+        let Some(album) = self.find_album(folder_uri) else {
+            return;
+        };
+        if album.get_release_type().is_none() {
+            if let Some(release_type) = meta.release_type {
+                self.regroup_album(folder_uri, release_type);
+            }
+        }
+        if album.get_release_group_mbid().is_none() {
+            if let Some(mbid) = meta.release_group_mbid {
+                let prev = self.imp().owned_mbids.borrow_mut().insert(folder_uri.to_owned(), mbid.clone());
+                if prev != Some(mbid) {
+                    self.queue_update_missing_albums();
+                }
+            }
+        }
+    }
+
+    /// Move an untagged album out of the Albums section once its metadata says otherwise.
+    fn regroup_album(&self, folder_uri: &str, release_type: ReleaseType) {
+        let section = section_of(release_type);
+        if section == 0 {
+            return;
+        }
+        let albums = &self.imp().album_lists[0];
+        let found = albums
+            .iter::<Album>()
+            .flatten()
+            .position(|album| album.get_uri() == folder_uri && album.get_release_type().is_none());
+        if let Some(pos) = found {
+            let album = albums.item(pos as u32).and_downcast::<Album>().unwrap();
+            albums.remove(pos as u32);
+            self.imp().album_lists[section].append(&album);
+        }
+    }
+
+    /// Rebuild the missing albums section once the current burst of updates is over.
+    fn queue_update_missing_albums(&self) {
+        if !self.imp().missing_update_queued.replace(true) {
+            glib::idle_add_local_once(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move || {
+                    this.imp().missing_update_queued.set(false);
+                    this.update_missing_albums();
+                }
+            ));
+        }
+    }
+
+    /// List release groups of this artist that match none of the local albums, first
+    /// by MBID and then by normalised title.
+    fn update_missing_albums(&self) {
+        let imp = self.imp();
+        imp.missing_albums.remove_all();
+        imp.missing_covers.borrow_mut().clear();
+        imp.missing_section.set_visible(false);
+        if !meta_provider_settings("musicbrainz").boolean("show-missing-albums") {
+            return;
+        }
+        let Some(cache) = imp.cache.get() else {
+            return;
+        };
+        let Some(release_groups) = imp.artist_mbid
+            .borrow()
+            .as_deref()
+            .and_then(|mbid| cache.load_cached_artist_discography(mbid)) else {
+            return;
+        };
+
+        let owned_mbids = imp.owned_mbids.borrow();
+        let local_mbids: FxHashSet<&str> = owned_mbids.values().map(String::as_str).collect();
+        let local_titles: FxHashSet<String> = imp.album_lists
+            .iter()
+            .flat_map(|list| list.iter::<Album>().flatten())
+            .map(|album| normalise_title(album.get_title()))
+            .collect();
+
+        let mut missing: Vec<&ReleaseGroupMeta> = release_groups
+            .iter()
+            .filter(|group| {
+                let title = normalise_title(&group.title);
+                !local_mbids.contains(group.mbid.as_str()) && (title.is_empty() || !local_titles.contains(&title))
+            })
+            .collect();
+        missing.sort_by_key(|group| group.first_release_date);
+        for group in missing.iter() {
+            imp.missing_albums.append(&self.missing_album_card(group, cache));
+        }
+        imp.missing_section.set_visible(!missing.is_empty());
+    }
+
+    fn missing_album_card(&self, group: &ReleaseGroupMeta, cache: &Cache) -> gtk::Box {
+        let card = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        card.set_halign(gtk::Align::Center);
+        card.set_valign(gtk::Align::Start);
+        card.set_tooltip_text(Some(&group.title));
+
+        let cover_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        cover_box.set_overflow(gtk::Overflow::Hidden);
+        cover_box.add_css_class("border-radius-6");
+        let cover = gtk::Picture::new();
+        cover.set_size_request(128, 128);
+        cover.set_content_fit(gtk::ContentFit::Cover);
+        let url = release_group_front_url(&group.mbid);
+        let download = meta_provider_settings("musicbrainz").boolean("download-album-art");
+        if let Some(tex) = cache.load_cached_remote_image(&url, download) {
+            cover.set_paintable(Some(&tex));
+        }
+        else {
+            cover.set_paintable(Some(&*ALBUMART_PLACEHOLDER));
+        }
+        cover_box.append(&cover);
+        card.append(&cover_box);
+        self.imp().missing_covers.borrow_mut().insert(url, cover);
+
+        let title = gtk::Label::new(Some(&group.title));
+        title.set_halign(gtk::Align::Start);
+        title.set_wrap(true);
+        title.set_lines(2);
+        title.set_ellipsize(gtk::pango::EllipsizeMode::End);
+        title.set_max_width_chars(16);
+        title.add_css_class("caption-heading");
+        card.append(&title);
+
+        let details: Vec<String> = group.first_release_date
+            .map(|date| date.year().to_string())
+            .into_iter()
+            .chain(group.release_type.map(|typ| typ.description().to_owned()))
+            .collect();
+        let subtitle = gtk::Label::new(Some(&details.join(" · ")));
+        subtitle.set_halign(gtk::Align::Start);
+        subtitle.add_css_class("caption");
+        subtitle.add_css_class("dim-label");
+        card.append(&subtitle);
+        card
+    }
+
+    fn update_album_count(&self) {
+        // Albums the artist merely appears on are not counted.
+        let count: u32 = self.imp().album_lists[..APPEARS_ON].iter().map(|list| list.n_items()).sum();
+        self.imp().album_count.set_label(&count.to_string());
     }
 
     pub fn add_songs(&self, songs: &[Song], cache: Rc<Cache>) {
@@ -574,6 +1022,14 @@ impl ArtistContentView {
 
     fn clear_content(&self) {
         self.imp().song_list.remove_all();
-        self.imp().album_list.remove_all();
+        for album_list in self.imp().album_lists.iter() {
+            album_list.remove_all();
+        }
+        let _ = self.imp().artist_mbid.take();
+        self.imp().owned_mbids.borrow_mut().clear();
+        self.imp().meta_load_queue.borrow_mut().clear();
+        self.imp().missing_albums.remove_all();
+        self.imp().missing_covers.borrow_mut().clear();
+        self.imp().missing_section.set_visible(false);
     }
 }
@@ -21,7 +21,7 @@ use crate::{
         Cache,
         CacheState
     },
-    common::{AlbumInfo, Song},
+    common::{AlbumInfo, Rating, Song},
     utils::format_secs_as_duration
 };
 
@@ -52,9 +52,15 @@ mod imp {
         pub album_name: TemplateChild<gtk::Label>,
         #[template_child]
         pub duration: TemplateChild<gtk::Label>,
-        // For unbinding the queue buttons when not bound to a song (i.e. being recycled)
+        #[template_child]
+        pub rating: TemplateChild<Rating>,
+        #[template_child]
+        pub favorite: TemplateChild<gtk::Button>,
+        // For unbinding the queue & sticker buttons when not bound to a song (i.e. being recycled)
         pub replace_queue_id: RefCell<Option<SignalHandlerId>>,
         pub append_queue_id: RefCell<Option<SignalHandlerId>>,
+        pub rating_id: RefCell<Option<SignalHandlerId>>,
+        pub favorite_id: RefCell<Option<SignalHandlerId>>,
         pub thumbnail_signal_id: RefCell<Option<SignalHandlerId>>,
         pub library: OnceCell<Library>
     }
@@ -182,6 +188,19 @@ impl ArtistSongRow {
             .property_expression("item")
             .chain_property::<Song>("quality-grade")
             .bind(self, "quality-grade", gtk::Widget::NONE);
+
+        item
+            .property_expression("item")
+            .chain_property::<Song>("rating")
+            .bind(&self.imp().rating.get(), "value", gtk::Widget::NONE);
+
+        item
+            .property_expression("item")
+            .chain_property::<Song>("favorite")
+            .chain_closure::<String>(closure_local!(|_: Option<Object>, favorite: bool| {
+                if favorite { "heart-filled-symbolic" } else { "heart-outline-symbolic" }
+            }))
+            .bind(&self.imp().favorite.get(), "icon-name", gtk::Widget::NONE);
     }
 
     fn update_thumbnail(&self, info: Option<&AlbumInfo>, cache: Rc<Cache>, schedule: bool) {
@@ -260,6 +279,48 @@ impl ArtistSongRow {
             // Unbind old ID
             self.imp().append_queue.disconnect(old_id);
         }
+        if let Some(old_id) = self.imp().rating_id.replace(
+            Some(
+                self.imp().rating.connect_closure(
+                    "rated",
+                    false,
+                    closure_local!(
+                        #[weak(rename_to = this)]
+                        self,
+                        #[strong]
+                        uri,
+                        move |_: Rating, rating: u32| {
+                            if let Some(library) = this.imp().library.get() {
+                                library.set_song_rating(&uri, rating);
+                            }
+                        }
+                    )
+                )
+            )
+        ) {
+            // Unbind old ID
+            self.imp().rating.disconnect(old_id);
+        }
+        if let Some(old_id) = self.imp().favorite_id.replace(
+            Some(
+                self.imp().favorite.connect_clicked(
+                    clone!(
+                        #[weak(rename_to = this)]
+                        self,
+                        #[weak]
+                        song,
+                        move |_| {
+                            if let Some(library) = this.imp().library.get() {
+                                library.set_song_favorite(song.get_uri(), !song.is_favorite());
+                            }
+                        }
+                    )
+                )
+            )
+        ) {
+            // Unbind old ID
+            self.imp().favorite.disconnect(old_id);
+        }
     }
 
     pub fn unbind(&self) {
@@ -269,5 +330,11 @@ impl ArtistSongRow {
         if let Some(id) = self.imp().append_queue_id.borrow_mut().take() {
             self.imp().append_queue.disconnect(id);
         }
+        if let Some(id) = self.imp().rating_id.borrow_mut().take() {
+            self.imp().rating.disconnect(id);
+        }
+        if let Some(id) = self.imp().favorite_id.borrow_mut().take() {
+            self.imp().favorite.disconnect(id);
+        }
     }
 }
@@ -14,10 +14,11 @@ use glib::clone;
 use super::{
     Library,
     ArtistCell,
-    ArtistContentView
+    ArtistContentView,
+    AlbumContentView
 };
 use crate::{
-    common::Artist,
+    common::{Album, Artist},
     cache::Cache,
     client::ClientState,
     utils::{settings_manager, g_cmp_str_options, g_search_substr}
@@ -53,6 +54,10 @@ mod imp {
         pub content_page: TemplateChild<adw::NavigationPage>,
         #[template_child]
         pub content_view: TemplateChild<ArtistContentView>,
+        #[template_child]
+        pub album_page: TemplateChild<adw::NavigationPage>,
+        #[template_child]
+        pub album_content_view: TemplateChild<AlbumContentView>,
 
         pub artist_list: gio::ListStore,
         // Search & filter models
@@ -81,6 +86,8 @@ mod imp {
                 grid_view: TemplateChild::default(),
                 content_page: TemplateChild::default(),
                 content_view: TemplateChild::default(),
+                album_page: TemplateChild::default(),
+                album_content_view: TemplateChild::default(),
                 artist_list: gio::ListStore::new::<Artist>(),
                 // Search & filter models
                 search_filter: gtk::CustomFilter::default(),
@@ -150,14 +157,51 @@ impl ArtistView {
         self.setup_gridview(library.clone(), client_state.clone(), cache.clone());
 
         let content_view = self.imp().content_view.get();
-        content_view.setup(library.clone(), cache, client_state);
-        self.imp().content_page.connect_hidden(move |_| {
-            content_view.unbind();
-        });
+        content_view.setup(library.clone(), cache.clone(), client_state.clone());
+        let album_content_view = self.imp().album_content_view.get();
+        album_content_view.setup(library.clone(), client_state, cache);
+        // The artist page is also hidden when one of its albums is pushed on top,
+        // so only unbind it once it has actually been popped.
+        let content_page = self.imp().content_page.get();
+        self.imp().nav_view.connect_popped(clone!(
+            #[weak]
+            content_view,
+            #[weak]
+            content_page,
+            move |_, page| {
+                if page == &content_page {
+                    content_view.unbind();
+                }
+            }
+        ));
+        self.imp().album_page.connect_hidden(clone!(
+            #[weak]
+            album_content_view,
+            move |_| {
+                album_content_view.unbind();
+            }
+        ));
+        content_view.connect_closure(
+            "album-clicked",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                library,
+                move |_: ArtistContentView, album: Album| {
+                    this.on_album_clicked(&album, library);
+                }
+            )
+        );
     }
 
-    pub fn get_content_view(&self) -> ArtistContentView {
-        self.imp().content_view.get()
+    fn on_album_clicked(&self, album: &Album, library: Library) {
+        // Same steps as AlbumView::on_album_clicked, but stay within this view so
+        // that going back returns to the artist.
+        self.imp().album_content_view.bind(album.clone());
+        library.init_album(album);
+        self.imp().nav_view.push_by_tag("album");
     }
 
     fn setup_sort(&self) {
@@ -0,0 +1,162 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    rc::Rc
+};
+use adw::subclass::prelude::*;
+use gtk::{
+    gio,
+    glib,
+    prelude::*,
+    CompositeTemplate
+};
+use glib::{
+    clone,
+    closure_local,
+    signal::SignalHandlerId
+};
+use rustc_hash::FxHashMap;
+
+use crate::{
+    cache::{placeholders::ALBUMART_PLACEHOLDER, Cache, CacheState},
+    meta_providers::models::ArtworkMeta
+};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/org/euphonica/Euphonica/gtk/library/artwork-dialog.ui")]
+    pub struct ArtworkDialog {
+        #[template_child]
+        pub gallery: TemplateChild<gtk::FlowBox>,
+        // Full-size image URLs in gallery order
+        pub urls: RefCell<Vec<String>>,
+        // Thumbnail widgets, keyed by thumbnail URL
+        pub thumbnails: RefCell<FxHashMap<String, gtk::Picture>>,
+        pub thumbnail_signal_id: RefCell<Option<SignalHandlerId>>,
+        pub cache: OnceCell<Rc<Cache>>
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ArtworkDialog {
+        const NAME: &'static str = "EuphonicaArtworkDialog";
+        type Type = super::ArtworkDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ArtworkDialog {}
+    impl WidgetImpl for ArtworkDialog {}
+    impl AdwDialogImpl for ArtworkDialog {
+        fn closed(&self) {
+            if let (Some(id), Some(cache)) = (self.thumbnail_signal_id.take(), self.cache.get()) {
+                cache.get_cache_state().disconnect(id);
+            }
+            self.parent_closed();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct ArtworkDialog(ObjectSubclass<imp::ArtworkDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl ArtworkDialog {
+    pub fn new(artwork: &[ArtworkMeta], cache: Rc<Cache>) -> Self {
+        let res: Self = glib::Object::new();
+        let _ = res.imp().thumbnail_signal_id.replace(Some(
+            cache.get_cache_state().connect_closure(
+                "remote-image-downloaded",
+                false,
+                closure_local!(
+                    #[weak(rename_to = this)]
+                    res,
+                    move |_: CacheState, url: String| {
+                        this.update_thumbnail(&url);
+                    }
+                )
+            )
+        ));
+        let _ = res.imp().cache.set(cache);
+
+        for item in artwork.iter() {
+            res.add_item(item);
+        }
+
+        res.imp().gallery.connect_child_activated(clone!(
+            #[weak(rename_to = this)]
+            res,
+            move |_, child| {
+                let idx = child.index();
+                if idx < 0 {
+                    return;
+                }
+                if let Some(url) = this.imp().urls.borrow().get(idx as usize) {
+                    let root = this.root().and_downcast::<gtk::Window>();
+                    gtk::UriLauncher::new(url).launch(
+                        root.as_ref(),
+                        Option::<&gio::Cancellable>::None,
+                        |res| {
+                            if let Err(e) = res {
+                                println!("[ArtworkDialog] Could not open image: {e:?}");
+                            }
+                        }
+                    );
+                }
+            }
+        ));
+        res
+    }
+
+    fn add_item(&self, item: &ArtworkMeta) {
+        let card = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        card.set_valign(gtk::Align::Start);
+        card.set_tooltip_text(Some(&item.url));
+
+        let cover_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        cover_box.set_overflow(gtk::Overflow::Hidden);
+        cover_box.add_css_class("border-radius-6");
+        let picture = gtk::Picture::new();
+        picture.set_size_request(128, 128);
+        picture.set_content_fit(gtk::ContentFit::Cover);
+        picture.set_paintable(Some(&*ALBUMART_PLACEHOLDER));
+        cover_box.append(&picture);
+        card.append(&cover_box);
+
+        // TODO: translatable
+        let caption = gtk::Label::new(Some(&if item.types.is_empty() {
+            "Other".to_owned()
+        }
+        else {
+            item.types.join(", ")
+        }));
+        caption.set_ellipsize(gtk::pango::EllipsizeMode::End);
+        caption.add_css_class("caption");
+        card.append(&caption);
+        self.imp().gallery.append(&card);
+        self.imp().urls.borrow_mut().push(item.url.clone());
+
+        // Fall back to the full-size image if there is no thumbnail. It'll be
+        // downscaled before being cached anyway.
+        let thumbnail_url = item.thumbnail.clone().unwrap_or_else(|| item.url.clone());
+        self.imp().thumbnails.borrow_mut().insert(thumbnail_url.clone(), picture);
+        self.update_thumbnail(&thumbnail_url);
+    }
+
+    fn update_thumbnail(&self, url: &str) {
+        if let (Some(picture), Some(cache)) = (self.imp().thumbnails.borrow().get(url), self.imp().cache.get()) {
+            if let Some(tex) = cache.load_cached_remote_image(url, true) {
+                picture.set_paintable(Some(&tex));
+            }
+        }
+    }
+}
@@ -10,8 +10,11 @@ use crate::{
     client::MpdMessage,
     cache::Cache,
     common::{
+        song::{FAVORITE_STICKER, RATING_STICKER},
         Album,
-        Artist
+        Artist,
+        SmartPlaylist,
+        Song
     }
 };
 use gtk::{
@@ -23,7 +26,7 @@ use glib::subclass::Signal;
 
 use adw::subclass::prelude::*;
 
-use mpd::{search::Operation, Query, Term};
+use mpd::{Query, Term};
 
 mod imp {
     use super::*;
@@ -122,15 +125,7 @@ impl Library {
             if replace {
                 let _ = sender.send_blocking(MpdMessage::Clear);
             }
-            let mut query = Query::new();
-            query.and_with_op(
-                Term::Tag(Cow::Borrowed(
-                    if use_albumartist { "albumartist" } else { "artist" }
-                )),
-                Operation::Contains,
-                artist.get_name().to_owned()
-            );
-            let _ = sender.send_blocking(MpdMessage::FindAdd(query));
+            let _ = sender.send_blocking(MpdMessage::FindAddArtist(artist.get_name().to_owned(), use_albumartist));
             if replace && play {
                 let _ = sender.send_blocking(MpdMessage::PlayPos(0));
             }
@@ -163,6 +158,91 @@ impl Library {
         }
     }
 
+    /// Store a song's rating (0 to 5 stars) as a sticker. Views will be updated
+    /// once MPD has accepted it.
+    pub fn set_song_rating(&self, uri: &str, rating: u32) {
+        if let Some(sender) = self.imp().sender.get() {
+            let _ = sender.send_blocking(MpdMessage::SetSticker(
+                "song".to_owned(), uri.to_owned(), RATING_STICKER.to_owned(), rating.to_string()
+            ));
+        }
+    }
+
+    /// Store a song's favourite flag as a sticker. Views will be updated
+    /// once MPD has accepted it.
+    pub fn set_song_favorite(&self, uri: &str, favorite: bool) {
+        if let Some(sender) = self.imp().sender.get() {
+            let _ = sender.send_blocking(MpdMessage::SetSticker(
+                "song".to_owned(), uri.to_owned(), FAVORITE_STICKER.to_owned(),
+                (if favorite { "1" } else { "0" }).to_owned()
+            ));
+        }
+    }
+
+    /// Fetch ratings & favourite flags of all songs under a folder ("" for the whole
+    /// library). Results are announced via ClientState's song-stickers-downloaded signal.
+    pub fn get_song_stickers(&self, base_uri: &str) {
+        if let Some(sender) = self.imp().sender.get() {
+            let _ = sender.send_blocking(MpdMessage::SongStickers(base_uri.to_owned()));
+        }
+    }
+
+    /// Queue a list of songs by URI, in the given order.
+    pub fn queue_uris(&self, uris: &[String], replace: bool, play: bool) {
+        if let Some(sender) = self.imp().sender.get() {
+            if replace {
+                let _ = sender.send_blocking(MpdMessage::Clear);
+            }
+            for uri in uris.iter() {
+                let _ = sender.send_blocking(MpdMessage::Add(uri.to_owned(), false));
+            }
+            if replace && play && !uris.is_empty() {
+                let _ = sender.send_blocking(MpdMessage::PlayPos(0));
+            }
+        }
+    }
+
+    /// Create or overwrite an MPD stored playlist with the given songs.
+    pub fn save_as_playlist(&self, name: &str, uris: Vec<String>) {
+        if let Some(sender) = self.imp().sender.get() {
+            let _ = sender.send_blocking(MpdMessage::SavePlaylist(name.to_owned(), uris));
+        }
+    }
+
+    pub fn get_smart_playlists(&self) -> Vec<SmartPlaylist> {
+        if let Some(cache) = self.imp().cache.get() {
+            return cache.load_smart_playlists();
+        }
+        Vec::new()
+    }
+
+    pub fn save_smart_playlist(&self, playlist: &SmartPlaylist) {
+        if let Some(cache) = self.imp().cache.get() {
+            cache.save_smart_playlist(playlist);
+        }
+    }
+
+    pub fn delete_smart_playlist(&self, name: &str) {
+        if let Some(cache) = self.imp().cache.get() {
+            cache.delete_smart_playlist(name);
+        }
+    }
+
+    /// Start evaluating a smart playlist. Candidate songs matching its MPD-side
+    /// rules will arrive via ClientState's smart-playlist-songs-downloaded signal
+    /// and should then be passed through filter_smart_playlist_songs().
+    pub fn init_smart_playlist(&self, playlist: &SmartPlaylist) {
+        if let Some(sender) = self.imp().sender.get() {
+            let _ = sender.send_blocking(MpdMessage::SmartPlaylistContent(playlist.clone()));
+        }
+    }
+
+    /// Apply the locally-evaluated rules & size limit of a smart playlist.
+    pub fn filter_smart_playlist_songs(&self, playlist: &SmartPlaylist, candidates: &[Song]) -> Vec<Song> {
+        let play_counts = self.imp().cache.get().map(|cache| cache.load_play_counts()).unwrap_or_default();
+        playlist.filter(candidates, &play_counts)
+    }
+
     // TODO: Lsinfo interface
     pub fn get_folder_contents(&self, uri: &str) {
         if let Some(sender) = self.imp().sender.get() {
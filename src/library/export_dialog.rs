@@ -0,0 +1,227 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    rc::Rc
+};
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::{
+    glib,
+    CompositeTemplate
+};
+use glib::clone;
+
+use crate::{
+    cache::{Cache, ExportOutcome, ExportPolicy, ExportReport},
+    common::AlbumInfo,
+    meta_providers::local::music_root,
+    utils::settings_manager
+};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/org/euphonica/Euphonica/gtk/library/export-dialog.ui")]
+    pub struct ExportDialog {
+        #[template_child]
+        pub stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub options_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub overwrite: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub quality_note: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub report_page: TemplateChild<adw::PreferencesPage>,
+        #[template_child]
+        pub preview: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub export: TemplateChild<gtk::Button>,
+        // Rebuilt for each report, as PreferencesGroup has no way to list its rows
+        pub report_group: RefCell<Option<adw::PreferencesGroup>>,
+        pub albums: OnceCell<Vec<AlbumInfo>>,
+        pub cache: OnceCell<Rc<Cache>>
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ExportDialog {
+        const NAME: &'static str = "EuphonicaExportDialog";
+        type Type = super::ExportDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ExportDialog {}
+    impl WidgetImpl for ExportDialog {}
+    impl AdwDialogImpl for ExportDialog {}
+}
+
+glib::wrapper! {
+    pub struct ExportDialog(ObjectSubclass<imp::ExportDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl ExportDialog {
+    /// Offer to export the covers of the given albums into their folders.
+    pub fn new(albums: Vec<AlbumInfo>, cache: Rc<Cache>) -> Self {
+        let res: Self = glib::Object::new();
+        let imp = res.imp();
+        // TODO: translatable
+        if let [album] = albums.as_slice() {
+            res.set_title(&format!("Export Cover of {}", &album.title));
+        }
+        else {
+            imp.options_group.set_title(&format!("{} albums", albums.len()));
+        }
+        let library_settings = settings_manager().child("library");
+        library_settings
+            .bind("export-overwrite-covers", &imp.overwrite.get(), "active")
+            .build();
+        // Without originals, covers can only be exported as they were cached.
+        if !library_settings.boolean("keep-original-images") {
+            // TODO: translatable
+            imp.quality_note.set_title(&format!(
+                "Covers will be resized to {} px",
+                library_settings.uint("hires-image-size")
+            ));
+            imp.quality_note.set_subtitle(
+                "Original images are not being kept. Turn on Keep original images under Preferences › Library to export newly-fetched covers untouched."
+            );
+            imp.quality_note.set_visible(true);
+        }
+        if music_root().is_some() {
+            imp.stack.set_visible_child_name("options");
+        }
+        else {
+            imp.stack.set_visible_child_name("no-root");
+            imp.preview.set_sensitive(false);
+            imp.export.set_sensitive(false);
+        }
+        let _ = imp.albums.set(albums);
+        let _ = imp.cache.set(cache);
+
+        imp.preview.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            res,
+            move |_| {
+                this.run(true);
+            }
+        ));
+        imp.export.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            res,
+            move |_| {
+                this.run(false);
+            }
+        ));
+        res
+    }
+
+    fn run(&self, dry_run: bool) {
+        let (Some(cache), Some(albums)) = (self.imp().cache.get(), self.imp().albums.get()) else {
+            return;
+        };
+        let policy = if self.imp().overwrite.is_active() {
+            ExportPolicy::Overwrite
+        }
+        else {
+            ExportPolicy::Skip
+        };
+        self.imp().preview.set_sensitive(false);
+        self.imp().export.set_sensitive(false);
+        self.imp().stack.set_visible_child_name("loading");
+        let handle = cache.export_album_arts(albums, policy, dry_run);
+        glib::MainContext::default().spawn_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                if let Ok(report) = handle.await {
+                    this.show_report(&report);
+                }
+                else {
+                    this.imp().stack.set_visible_child_name("options");
+                }
+                // Exporting again right away would do nothing new, so only allow going
+                // ahead after a preview.
+                this.imp().preview.set_sensitive(dry_run);
+                this.imp().export.set_sensitive(dry_run);
+            }
+        ));
+    }
+
+    fn show_report(&self, report: &ExportReport) {
+        let page = self.imp().report_page.get();
+        if let Some(old_group) = self.imp().report_group.take() {
+            page.remove(&old_group);
+        }
+        let group = adw::PreferencesGroup::new();
+        group.set_title(&summarise(report));
+        for entry in report.entries.iter() {
+            let row = adw::ActionRow::new();
+            row.set_title(&glib::markup_escape_text(&entry.title));
+            row.set_subtitle(&glib::markup_escape_text(&entry.folder_uri));
+            let status = if entry.resized {
+                // TODO: translatable
+                format!("{} (resized)", entry.outcome.describe(report.dry_run))
+            }
+            else {
+                entry.outcome.describe(report.dry_run).to_owned()
+            };
+            let label = gtk::Label::new(Some(&status));
+            label.set_wrap(true);
+            label.set_xalign(1.0);
+            if entry.outcome == ExportOutcome::Failed {
+                label.add_css_class("error");
+            }
+            else if !entry.outcome.is_write() {
+                label.add_css_class("dim-label");
+            }
+            row.add_suffix(&label);
+            group.add(&row);
+        }
+        page.add(&group);
+        self.imp().report_group.replace(Some(group));
+        self.imp().stack.set_visible_child_name("report");
+    }
+}
+
+/// One line counting albums by outcome, skipping outcomes no album had.
+fn summarise(report: &ExportReport) -> String {
+    // TODO: translatable
+    let (written, overwritten) = if report.dry_run {
+        ("to be written", "to be replaced")
+    }
+    else {
+        ("written", "replaced")
+    };
+    let parts: Vec<String> = [
+        (ExportOutcome::Written, written),
+        (ExportOutcome::Overwritten, overwritten),
+        (ExportOutcome::Skipped, "skipped"),
+        (ExportOutcome::NoArt, "without art"),
+        (ExportOutcome::NoFolder, "not found"),
+        (ExportOutcome::Failed, "failed")
+    ]
+        .into_iter()
+        .map(|(outcome, desc)| (report.count(outcome), desc))
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, desc)| format!("{} {}", count, desc))
+        .collect();
+    if parts.is_empty() {
+        return "Nothing to export".to_owned();
+    }
+    let resized = report.resized();
+    if resized > 0 {
+        format!("{} ({} resized)", parts.join(", "), resized)
+    }
+    else {
+        parts.join(", ")
+    }
+}
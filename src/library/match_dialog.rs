@@ -0,0 +1,303 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    rc::Rc
+};
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::{
+    gio,
+    glib,
+    CompositeTemplate
+};
+use glib::{
+    clone,
+    closure_local,
+    signal::SignalHandlerId
+};
+use rustc_hash::FxHashMap;
+
+use crate::{
+    cache::{placeholders::ALBUMART_PLACEHOLDER, Cache, CacheState},
+    common::{AlbumInfo, ArtistInfo},
+    meta_providers::{get_provider_name, models::MatchCandidate}
+};
+
+/// What the user is picking a match for.
+#[derive(Debug, Clone)]
+pub enum MatchTarget {
+    Album(AlbumInfo),
+    Artist(ArtistInfo)
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/org/euphonica/Euphonica/gtk/library/match-dialog.ui")]
+    pub struct MatchDialog {
+        #[template_child]
+        pub stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub groups: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub choose_image: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub reset: TemplateChild<gtk::Button>,
+        pub target: OnceCell<MatchTarget>,
+        // Thumbnail widgets, keyed by thumbnail URL
+        pub thumbnails: RefCell<FxHashMap<String, gtk::Picture>>,
+        pub signal_ids: RefCell<Vec<SignalHandlerId>>,
+        pub cache: OnceCell<Rc<Cache>>
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for MatchDialog {
+        const NAME: &'static str = "EuphonicaMatchDialog";
+        type Type = super::MatchDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for MatchDialog {}
+    impl WidgetImpl for MatchDialog {}
+    impl AdwDialogImpl for MatchDialog {
+        fn closed(&self) {
+            if let Some(cache) = self.cache.get() {
+                let state = cache.get_cache_state();
+                for id in self.signal_ids.take() {
+                    state.disconnect(id);
+                }
+            }
+            self.parent_closed();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct MatchDialog(ObjectSubclass<imp::MatchDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl MatchDialog {
+    pub fn new(target: MatchTarget, cache: Rc<Cache>) -> Self {
+        let res: Self = glib::Object::new();
+        let state = cache.get_cache_state();
+        let mut signal_ids = res.imp().signal_ids.borrow_mut();
+        signal_ids.push(state.connect_closure(
+            "remote-image-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                res,
+                move |_: CacheState, url: String| {
+                    this.update_thumbnail(&url);
+                }
+            )
+        ));
+        match &target {
+            MatchTarget::Album(album) => {
+                // TODO: translatable
+                res.set_title(&format!("Pick Match for {}", &album.title));
+                signal_ids.push(state.connect_closure(
+                    "album-candidates-downloaded",
+                    false,
+                    closure_local!(
+                        #[weak(rename_to = this)]
+                        res,
+                        move |_: CacheState, folder_uri: String| {
+                            if let Some(MatchTarget::Album(album)) = this.imp().target.get() {
+                                if album.uri == folder_uri {
+                                    if let Some(candidates) = this.imp().cache.get().and_then(|cache| cache.take_album_candidates(album)) {
+                                        this.show_candidates(candidates);
+                                    }
+                                }
+                            }
+                        }
+                    )
+                ));
+                res.imp().reset.set_sensitive(cache.has_album_override(album));
+                cache.search_album_candidates(album);
+            }
+            MatchTarget::Artist(artist) => {
+                res.set_title(&format!("Pick Match for {}", &artist.name));
+                signal_ids.push(state.connect_closure(
+                    "artist-candidates-downloaded",
+                    false,
+                    closure_local!(
+                        #[weak(rename_to = this)]
+                        res,
+                        move |_: CacheState, name: String| {
+                            if let Some(MatchTarget::Artist(artist)) = this.imp().target.get() {
+                                if artist.name == name {
+                                    if let Some(candidates) = this.imp().cache.get().and_then(|cache| cache.take_artist_candidates(artist)) {
+                                        this.show_candidates(candidates);
+                                    }
+                                }
+                            }
+                        }
+                    )
+                ));
+                res.imp().reset.set_sensitive(cache.has_artist_override(artist));
+                cache.search_artist_candidates(artist);
+            }
+        }
+        drop(signal_ids);
+        let _ = res.imp().target.set(target);
+        let _ = res.imp().cache.set(cache);
+        res.imp().stack.set_visible_child_name("loading");
+
+        res.imp().choose_image.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            res,
+            move |_| {
+                this.choose_image();
+            }
+        ));
+        res.imp().reset.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            res,
+            move |_| {
+                if let (Some(cache), Some(target)) = (this.imp().cache.get(), this.imp().target.get()) {
+                    match target {
+                        MatchTarget::Album(album) => cache.clear_album_override(album),
+                        MatchTarget::Artist(artist) => cache.clear_artist_override(artist)
+                    }
+                }
+                this.close();
+            }
+        ));
+        res
+    }
+
+    fn show_candidates(&self, candidates: Vec<MatchCandidate>) {
+        let groups = self.imp().groups.get();
+        while let Some(child) = groups.first_child() {
+            groups.remove(&child);
+        }
+        self.imp().thumbnails.borrow_mut().clear();
+        if candidates.is_empty() {
+            self.imp().stack.set_visible_child_name("empty");
+            return;
+        }
+        // Candidates arrive grouped by provider, in priority order.
+        let mut current: Option<(String, adw::PreferencesGroup)> = None;
+        for candidate in candidates.into_iter() {
+            let new_group = match current.as_ref() {
+                Some((provider, _)) => provider != &candidate.provider,
+                None => true
+            };
+            if new_group {
+                let group = adw::PreferencesGroup::new();
+                group.set_title(&get_provider_name(&candidate.provider));
+                groups.append(&group);
+                current = Some((candidate.provider.clone(), group));
+            }
+            if let Some((_, group)) = current.as_ref() {
+                group.add(&self.build_row(candidate));
+            }
+        }
+        self.imp().stack.set_visible_child_name("results");
+    }
+
+    fn build_row(&self, candidate: MatchCandidate) -> adw::ActionRow {
+        let row = adw::ActionRow::new();
+        row.set_title(&glib::markup_escape_text(&candidate.title));
+        let mut subtitle: Vec<String> = Vec::new();
+        if let Some(artist) = candidate.artist.as_ref() {
+            subtitle.push(artist.clone());
+        }
+        subtitle.extend(candidate.details.iter().cloned());
+        row.set_subtitle(&glib::markup_escape_text(&subtitle.join(" · ")));
+        row.set_activatable(true);
+
+        if let Some(url) = candidate.thumbnail.as_ref() {
+            let picture = gtk::Picture::new();
+            picture.set_size_request(48, 48);
+            picture.set_content_fit(gtk::ContentFit::Cover);
+            picture.set_paintable(Some(&*ALBUMART_PLACEHOLDER));
+            picture.set_valign(gtk::Align::Center);
+            picture.set_overflow(gtk::Overflow::Hidden);
+            picture.add_css_class("border-radius-6");
+            row.add_prefix(&picture);
+            self.imp().thumbnails.borrow_mut().insert(url.clone(), picture);
+            self.update_thumbnail(url);
+        }
+        if let Some(score) = candidate.score {
+            let label = gtk::Label::new(Some(&format!("{:.0}%", score * 100.0)));
+            // TODO: translatable
+            label.set_tooltip_text(Some("Match confidence"));
+            label.add_css_class("dim-label");
+            label.add_css_class("numeric");
+            row.add_suffix(&label);
+        }
+        row.connect_activated(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let (Some(cache), Some(target)) = (this.imp().cache.get(), this.imp().target.get()) {
+                    match target {
+                        MatchTarget::Album(album) => cache.pin_album_match(album, &candidate),
+                        MatchTarget::Artist(artist) => cache.pin_artist_match(artist, &candidate)
+                    }
+                }
+                this.close();
+            }
+        ));
+        row
+    }
+
+    fn choose_image(&self) {
+        let filter = gtk::FileFilter::new();
+        // TODO: translatable
+        filter.set_name(Some("Images"));
+        filter.add_pixbuf_formats();
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+        let file_dialog = gtk::FileDialog::builder()
+            .title("Choose Image File")
+            .modal(true)
+            .filters(&filters)
+            .default_filter(&filter)
+            .build();
+        let root = self.root().and_downcast::<gtk::Window>();
+        file_dialog.open(
+            root.as_ref(),
+            Option::<&gio::Cancellable>::None,
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |res| {
+                    match res {
+                        Ok(file) => {
+                            if let (Some(path), Some(cache), Some(target)) = (file.path(), this.imp().cache.get(), this.imp().target.get()) {
+                                match target {
+                                    MatchTarget::Album(album) => cache.set_album_art_file(album, path),
+                                    MatchTarget::Artist(artist) => cache.set_artist_avatar_file(artist, path)
+                                }
+                                this.close();
+                            }
+                        }
+                        Err(e) => {
+                            println!("[MatchDialog] No image chosen: {e:?}");
+                        }
+                    }
+                }
+            )
+        );
+    }
+
+    fn update_thumbnail(&self, url: &str) {
+        if let (Some(picture), Some(cache)) = (self.imp().thumbnails.borrow().get(url), self.imp().cache.get()) {
+            if let Some(tex) = cache.load_cached_remote_image(url, true) {
+                picture.set_paintable(Some(&tex));
+            }
+        }
+    }
+}
@@ -2,6 +2,9 @@ mod album_view;
 mod album_cell;
 mod album_content_view;
 mod album_song_row;
+mod artwork_dialog;
+mod match_dialog;
+mod export_dialog;
 
 mod artist_view;
 mod artist_cell;
@@ -11,12 +14,17 @@ mod artist_song_row;
 mod folder_view;
 mod folder_row;
 
+mod smart_playlist_view;
+
 mod controller;
 
 pub use album_view::AlbumView;
 use album_cell::AlbumCell;
 pub use album_content_view::AlbumContentView;
 use album_song_row::AlbumSongRow;
+use artwork_dialog::ArtworkDialog;
+use match_dialog::MatchDialog;
+use export_dialog::ExportDialog;
 
 pub use artist_view::ArtistView;
 use artist_cell::ArtistCell;
@@ -25,4 +33,6 @@ pub use artist_content_view::ArtistContentView;
 
 pub use folder_view::FolderView;
 
+pub use smart_playlist_view::SmartPlaylistView;
+
 pub use controller::Library;
@@ -0,0 +1,572 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    rc::Rc
+};
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{
+    gio,
+    glib::{self, closure_local},
+    CompositeTemplate,
+    ListItem,
+    SignalListItemFactory
+};
+use glib::clone;
+
+use super::{ArtistSongRow, Library};
+use crate::{
+    cache::Cache,
+    client::ClientState,
+    common::{QualityGrade, SmartPlaylist, SmartRule, Song, SongStickers}
+};
+
+// What to do with a smart playlist's contents once they have been evaluated.
+// Contents are always re-evaluated right before being used since play counts
+// and ratings may have changed since the last preview.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PendingAction {
+    Preview,
+    Queue,
+    Materialize
+}
+
+// Play count presets, in the same order as the play_count combo row.
+const PLAY_COUNT_PRESETS: [(Option<u32>, Option<u32>); 5] = [
+    (None, None),
+    (None, Some(0)),
+    (Some(1), None),
+    (Some(5), None),
+    (Some(10), None)
+];
+
+// Quality presets, in the same order as the min_quality combo row.
+const QUALITY_PRESETS: [QualityGrade; 4] = [
+    QualityGrade::Unknown,
+    QualityGrade::CD,
+    QualityGrade::HiRes,
+    QualityGrade::DSD
+];
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, CompositeTemplate)]
+    #[template(resource = "/org/euphonica/Euphonica/gtk/library/smart-playlist-view.ui")]
+    pub struct SmartPlaylistView {
+        #[template_child]
+        pub nav_view: TemplateChild<adw::NavigationView>,
+        // List page
+        #[template_child]
+        pub new_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub list_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub playlist_list: TemplateChild<gtk::ListBox>,
+        // Editor page
+        #[template_child]
+        pub save_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub delete_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub name_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub genre_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub year_from: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub year_to: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub min_rating: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub play_count: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub modified_days: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub min_quality: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub limit: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub preview_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub play_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub materialize_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub preview_count: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub preview_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub preview_view: TemplateChild<gtk::ListView>,
+
+        pub preview: gio::ListStore,
+        // Name of the definition currently open in the editor as last saved,
+        // so that renaming replaces it instead of creating a copy.
+        pub editing: RefCell<Option<String>>,
+        // Smart playlist being evaluated & what to do once its songs arrive.
+        pub pending: RefCell<Option<(SmartPlaylist, PendingAction)>>,
+        pub library: OnceCell<Library>
+    }
+
+    impl Default for SmartPlaylistView {
+        fn default() -> Self {
+            Self {
+                nav_view: TemplateChild::default(),
+                new_btn: TemplateChild::default(),
+                list_stack: TemplateChild::default(),
+                playlist_list: TemplateChild::default(),
+                save_btn: TemplateChild::default(),
+                delete_btn: TemplateChild::default(),
+                name_entry: TemplateChild::default(),
+                genre_entry: TemplateChild::default(),
+                year_from: TemplateChild::default(),
+                year_to: TemplateChild::default(),
+                min_rating: TemplateChild::default(),
+                play_count: TemplateChild::default(),
+                modified_days: TemplateChild::default(),
+                min_quality: TemplateChild::default(),
+                limit: TemplateChild::default(),
+                preview_btn: TemplateChild::default(),
+                play_btn: TemplateChild::default(),
+                materialize_btn: TemplateChild::default(),
+                preview_count: TemplateChild::default(),
+                preview_stack: TemplateChild::default(),
+                preview_view: TemplateChild::default(),
+                preview: gio::ListStore::new::<Song>(),
+                editing: RefCell::new(None),
+                pending: RefCell::new(None),
+                library: OnceCell::new()
+            }
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SmartPlaylistView {
+        const NAME: &'static str = "EuphonicaSmartPlaylistView";
+        type Type = super::SmartPlaylistView;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.set_layout_manager_type::<gtk::BinLayout>();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for SmartPlaylistView {
+        fn dispose(&self) {
+            while let Some(child) = self.obj().first_child() {
+                child.unparent();
+            }
+        }
+    }
+
+    impl WidgetImpl for SmartPlaylistView {}
+}
+
+glib::wrapper! {
+    pub struct SmartPlaylistView(ObjectSubclass<imp::SmartPlaylistView>)
+        @extends gtk::Widget,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
+
+impl Default for SmartPlaylistView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SmartPlaylistView {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn setup(&self, library: Library, cache: Rc<Cache>, client_state: ClientState) {
+        self.imp().library.set(library.clone()).expect("Cannot init SmartPlaylistView with Library");
+
+        client_state.connect_closure(
+            "smart-playlist-songs-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, name: String, songs: glib::BoxedAnyObject| {
+                    this.on_songs_downloaded(&name, songs.borrow::<Vec<Song>>().as_ref());
+                }
+            )
+        );
+        client_state.connect_closure(
+            "sticker-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, typ: String, uri: String, name: String, value: String| {
+                    if typ == "song" {
+                        for song in this.imp().preview.iter::<Song>().flatten() {
+                            if song.get_uri() == uri {
+                                song.apply_sticker(&name, &value);
+                            }
+                        }
+                    }
+                }
+            )
+        );
+        client_state.connect_closure(
+            "song-stickers-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, base_uri: String, boxed: glib::BoxedAnyObject| {
+                    let stickers = boxed.borrow::<SongStickers>();
+                    for song in this.imp().preview.iter::<Song>().flatten() {
+                        song.apply_stickers(&base_uri, &stickers);
+                    }
+                }
+            )
+        );
+
+        self.imp().new_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.open_editor(None);
+            }
+        ));
+        self.imp().save_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.save_definition();
+            }
+        ));
+        self.imp().delete_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let Some(name) = this.imp().editing.take() {
+                    if let Some(library) = this.imp().library.get() {
+                        library.delete_smart_playlist(&name);
+                    }
+                }
+                this.refresh_list();
+                this.imp().nav_view.pop();
+            }
+        ));
+        self.imp().preview_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.evaluate(this.build_definition(), PendingAction::Preview);
+            }
+        ));
+        self.imp().play_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.evaluate(this.build_definition(), PendingAction::Queue);
+            }
+        ));
+        self.imp().materialize_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.evaluate(this.build_definition(), PendingAction::Materialize);
+            }
+        ));
+        // A playlist without a name can be previewed but not saved anywhere.
+        self.imp().name_entry.connect_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                let has_name = !entry.text().is_empty();
+                this.imp().save_btn.set_sensitive(has_name);
+                this.imp().materialize_btn.set_sensitive(has_name);
+            }
+        ));
+
+        self.setup_preview(library, cache);
+        self.refresh_list();
+    }
+
+    fn setup_preview(&self, library: Library, cache: Rc<Cache>) {
+        let factory = SignalListItemFactory::new();
+        factory.connect_setup(clone!(
+            #[weak]
+            library,
+            move |_, list_item| {
+                let item = list_item
+                    .downcast_ref::<ListItem>()
+                    .expect("Needs to be ListItem");
+                let song_row = ArtistSongRow::new(
+                    library,
+                    &item
+                );
+                item.set_child(Some(&song_row));
+            }
+        ));
+        factory.connect_bind(clone!(
+            #[weak]
+            cache,
+            move |_, list_item| {
+                let item: Song = list_item
+                    .downcast_ref::<ListItem>()
+                    .expect("Needs to be ListItem")
+                    .item()
+                    .and_downcast::<Song>()
+                    .expect("The item has to be a common::Song.");
+                let child: ArtistSongRow = list_item
+                    .downcast_ref::<ListItem>()
+                    .expect("Needs to be ListItem")
+                    .child()
+                    .and_downcast::<ArtistSongRow>()
+                    .expect("The child has to be an `ArtistSongRow`.");
+                child.bind(&item, cache);
+            }
+        ));
+        factory.connect_unbind(move |_, list_item| {
+            let child: ArtistSongRow = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<ArtistSongRow>()
+                .expect("The child has to be an `ArtistSongRow`.");
+            child.unbind();
+        });
+        self.imp().preview_view.set_factory(Some(&factory));
+        let sel_model = gtk::NoSelection::new(Some(self.imp().preview.clone()));
+        self.imp().preview_view.set_model(Some(&sel_model));
+    }
+
+    /// Rebuild the list of saved smart playlists.
+    fn refresh_list(&self) {
+        let list = self.imp().playlist_list.get();
+        list.remove_all();
+        let playlists = self.imp().library.get().map(|library| library.get_smart_playlists()).unwrap_or_default();
+        if playlists.is_empty() {
+            self.imp().list_stack.set_visible_child_name("empty");
+            return;
+        }
+        for playlist in playlists.into_iter() {
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&playlist.name))
+                .subtitle(describe_rules(&playlist))
+                .activatable(true)
+                .build();
+            let play_btn = gtk::Button::builder()
+                .icon_name("play-symbolic")
+                .tooltip_text("Replace queue with this smart playlist")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            play_btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[strong]
+                playlist,
+                move |_| {
+                    this.evaluate(playlist.clone(), PendingAction::Queue);
+                }
+            ));
+            row.add_suffix(&play_btn);
+            row.add_suffix(&gtk::Image::from_icon_name("right-symbolic"));
+            row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[strong]
+                playlist,
+                move |_| {
+                    this.open_editor(Some(&playlist));
+                }
+            ));
+            list.append(&row);
+        }
+        self.imp().list_stack.set_visible_child_name("content");
+    }
+
+    /// Fill the editor with the given definition (or defaults) and show it.
+    fn open_editor(&self, playlist: Option<&SmartPlaylist>) {
+        let imp = self.imp();
+        let _ = imp.editing.replace(playlist.map(|p| p.name.clone()));
+        imp.delete_btn.set_visible(playlist.is_some());
+        imp.name_entry.set_text(playlist.map_or("", |p| p.name.as_str()));
+        imp.genre_entry.set_text("");
+        imp.year_from.set_value(0.0);
+        imp.year_to.set_value(0.0);
+        imp.min_rating.set_selected(0);
+        imp.play_count.set_selected(0);
+        imp.modified_days.set_value(0.0);
+        imp.min_quality.set_selected(0);
+        imp.limit.set_value(playlist.and_then(|p| p.limit).unwrap_or(0) as f64);
+        if let Some(playlist) = playlist {
+            for rule in playlist.rules.iter() {
+                match rule {
+                    SmartRule::Genre(genre) => imp.genre_entry.set_text(genre),
+                    SmartRule::YearRange(from, to) => {
+                        imp.year_from.set_value(from.unwrap_or(0) as f64);
+                        imp.year_to.set_value(to.unwrap_or(0) as f64);
+                    }
+                    SmartRule::MinRating(rating) => imp.min_rating.set_selected(*rating),
+                    SmartRule::PlayCount(min, max) => {
+                        let idx = PLAY_COUNT_PRESETS
+                            .iter()
+                            .position(|preset| *preset == (*min, *max))
+                            .unwrap_or(0);
+                        imp.play_count.set_selected(idx as u32);
+                    }
+                    SmartRule::ModifiedWithinDays(days) => imp.modified_days.set_value(*days as f64),
+                    SmartRule::MinQuality(grade) => {
+                        let idx = QUALITY_PRESETS
+                            .iter()
+                            .position(|preset| preset == grade)
+                            .unwrap_or(0);
+                        imp.min_quality.set_selected(idx as u32);
+                    }
+                }
+            }
+        }
+        let has_name = !imp.name_entry.text().is_empty();
+        imp.save_btn.set_sensitive(has_name);
+        imp.materialize_btn.set_sensitive(has_name);
+        imp.preview.remove_all();
+        imp.preview_count.set_visible(false);
+        imp.preview_stack.set_visible_child_name("idle");
+        imp.nav_view.push_by_tag("editor");
+    }
+
+    /// Read the editor's current contents into a definition.
+    fn build_definition(&self) -> SmartPlaylist {
+        let imp = self.imp();
+        let mut playlist = SmartPlaylist::new(imp.name_entry.text().trim());
+        let genre = imp.genre_entry.text();
+        if !genre.trim().is_empty() {
+            playlist.rules.push(SmartRule::Genre(genre.trim().to_owned()));
+        }
+        let year_from = imp.year_from.value() as i32;
+        let year_to = imp.year_to.value() as i32;
+        if year_from > 0 || year_to > 0 {
+            playlist.rules.push(SmartRule::YearRange(
+                (year_from > 0).then_some(year_from),
+                (year_to > 0).then_some(year_to)
+            ));
+        }
+        let min_rating = imp.min_rating.selected();
+        if min_rating > 0 {
+            playlist.rules.push(SmartRule::MinRating(min_rating));
+        }
+        if let Some((min, max)) = PLAY_COUNT_PRESETS.get(imp.play_count.selected() as usize) {
+            if min.is_some() || max.is_some() {
+                playlist.rules.push(SmartRule::PlayCount(*min, *max));
+            }
+        }
+        let days = imp.modified_days.value() as u32;
+        if days > 0 {
+            playlist.rules.push(SmartRule::ModifiedWithinDays(days));
+        }
+        if let Some(grade) = QUALITY_PRESETS.get(imp.min_quality.selected() as usize) {
+            if *grade != QualityGrade::Unknown {
+                playlist.rules.push(SmartRule::MinQuality(*grade));
+            }
+        }
+        let limit = imp.limit.value() as u32;
+        if limit > 0 {
+            playlist.limit = Some(limit);
+        }
+        playlist
+    }
+
+    fn save_definition(&self) {
+        let playlist = self.build_definition();
+        if playlist.name.is_empty() {
+            return;
+        }
+        if let Some(library) = self.imp().library.get() {
+            if let Some(old_name) = self.imp().editing.replace(Some(playlist.name.clone())) {
+                if old_name != playlist.name {
+                    library.delete_smart_playlist(&old_name);
+                }
+            }
+            library.save_smart_playlist(&playlist);
+        }
+        self.imp().delete_btn.set_visible(true);
+        self.refresh_list();
+    }
+
+    /// Ask MPD for candidate songs. The given action will be carried out
+    /// once they arrive.
+    fn evaluate(&self, playlist: SmartPlaylist, action: PendingAction) {
+        if let Some(library) = self.imp().library.get() {
+            library.init_smart_playlist(&playlist);
+            let _ = self.imp().pending.replace(Some((playlist, action)));
+            self.imp().preview_stack.set_visible_child_name("loading");
+        }
+    }
+
+    fn on_songs_downloaded(&self, name: &str, candidates: &[Song]) {
+        let pending = self.imp().pending.borrow().as_ref().map(|(p, a)| (p.clone(), *a));
+        let Some((playlist, action)) = pending else {
+            return;
+        };
+        // Ignore results of earlier, superseded evaluations
+        if playlist.name != name {
+            return;
+        }
+        let _ = self.imp().pending.take();
+        let Some(library) = self.imp().library.get() else {
+            return;
+        };
+        let songs = library.filter_smart_playlist_songs(&playlist, candidates);
+        let uris: Vec<String> = songs.iter().map(|song| song.get_uri().to_owned()).collect();
+        match action {
+            PendingAction::Preview => {}
+            PendingAction::Queue => library.queue_uris(&uris, true, true),
+            PendingAction::Materialize => {
+                if !playlist.name.is_empty() {
+                    library.save_as_playlist(&playlist.name, uris);
+                }
+            }
+        }
+        let imp = self.imp();
+        imp.preview_count.set_label(&format!("{} songs", songs.len()));
+        imp.preview_count.set_visible(true);
+        imp.preview.remove_all();
+        imp.preview.extend_from_slice(&songs);
+        imp.preview_stack.set_visible_child_name("content");
+    }
+}
+
+/// Short human-readable summary of a smart playlist's rules.
+fn describe_rules(playlist: &SmartPlaylist) -> String {
+    // TODO: i18n
+    let mut parts: Vec<String> = playlist.rules.iter().map(|rule| {
+        match rule {
+            SmartRule::Genre(genre) => glib::markup_escape_text(genre).to_string(),
+            SmartRule::YearRange(Some(from), Some(to)) => format!("{}–{}", from, to),
+            SmartRule::YearRange(Some(from), None) => format!("from {}", from),
+            SmartRule::YearRange(None, Some(to)) => format!("until {}", to),
+            SmartRule::YearRange(None, None) => "any year".to_owned(),
+            SmartRule::MinRating(rating) => format!("{}★ or more", rating),
+            SmartRule::PlayCount(None, Some(0)) => "never played".to_owned(),
+            SmartRule::PlayCount(min, max) => match (min, max) {
+                (Some(min), Some(max)) => format!("played {}–{} times", min, max),
+                (Some(min), None) => format!("played {}+ times", min),
+                (None, Some(max)) => format!("played at most {} times", max),
+                (None, None) => "any play count".to_owned()
+            },
+            SmartRule::ModifiedWithinDays(days) => format!("added in the last {} days", days),
+            SmartRule::MinQuality(grade) => format!("{:?} or better", grade)
+        }
+    }).collect();
+    if let Some(limit) = playlist.limit {
+        parts.push(format!("at most {} songs", limit));
+    }
+    if parts.is_empty() {
+        "All songs".to_owned()
+    }
+    else {
+        parts.join(", ")
+    }
+}
@@ -1,78 +1,112 @@
 extern crate bson;
-use gtk::prelude::*;
-use std::{thread, time::Duration};
-
-use crate::utils::settings_manager;
+use crate::common::AlbumQuality;
 
 use super::models;
 
-pub fn sleep_after_request() {
-    let settings = settings_manager().child("metaprovider");
-    thread::sleep(Duration::from_millis(
-        (settings.double("delay-between-requests-s") * 1000.0) as u64
-    ));
-}
-
 pub enum Metadata {
     // folder-level URI, true for thumbnail
     AlbumArt(String, bool),
     // Reserved for MpdWrapper to notify that we don't have one locally.
-    // Used by cache controller to trigger downloading from daisy-chained metadata.
-    AlbumArtNotAvailable(String, bson::Document),
+    // Used by cache controller to trigger downloading from daisy-chained metadata,
+    // at low priority if the art was only being prefetched.
+    AlbumArtNotAvailable(String, bson::Document, bool),
     // folder-level URI
     AlbumMeta(String),
     // Tag, true for thumbnail
     ArtistAvatar(String, bool),
     // Tag
-    ArtistMeta(String)
+    ArtistMeta(String),
+    // Reserved for MpdWrapper. Folder-level URI & quality range of all tracks in the album.
+    AlbumQuality(String, AlbumQuality),
+    // Reserved for MpdWrapper. Time of MPD's last database update, used to
+    // invalidate data derived from the library itself.
+    LibraryVersion(i64),
+    // Artist MBID
+    ArtistDiscography(String),
+    // URL of an image not tied to any album or artist in the library,
+    // such as covers of missing albums or artwork thumbnails.
+    RemoteImage(String),
+    // Folder-level URI & possible matches from all providers
+    AlbumCandidates(String, Vec<models::MatchCandidate>),
+    // Tag & possible matches from all providers
+    ArtistCandidates(String, Vec<models::MatchCandidate>),
+    // Provider key & a message describing what went wrong, for showing to the user
+    ProviderError(String, String)
 }
 
 /// Common provider-agnostic utilities.
 pub mod utils {
+    use gtk::glib;
     use image::DynamicImage;
     use crate::utils;
     use super::*;
+    use super::super::http;
 
     /// Get a file from the given URL as bytes. Useful for downloading images.
     fn get_file(
         url: &str
     ) -> Option<Vec<u8>> {
-        let response = reqwest::blocking::get(url);
+        // Local providers point to files on disk instead.
+        if url.starts_with("file://") {
+            return match glib::filename_from_uri(url) {
+                Ok((path, _)) => std::fs::read(&path).map_err(|e| {
+                    println!("get_file: Failed to read {:?}: {:?}", &path, e);
+                }).ok(),
+                Err(e) => {
+                    println!("get_file: Invalid file URI {}: {:?}", url, e);
+                    None
+                }
+            };
+        }
         // This empty check comes in handy for certain metadata providers who, instead of
         // skipping the URL fields, opt to return an empty string instead.
-        if !url.is_empty() {
-            if let Ok(res) = response {
-                if let Ok(bytes) = res.bytes() {
-                    return Some(bytes.to_vec());
-                }
-                else {
-                    println!("get_file: Failed to read response as bytes!");
-                    return None;
-                }
-            }
-            println!("get_file: {:?}", response.err());
-            None
+        if url.is_empty() {
+            return None;
         }
-        else {
-            None
+        let parsed = match reqwest::Url::parse(url) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                println!("get_file: Invalid URL {}: {:?}", url, e);
+                return None;
+            }
+        };
+        match http::get(parsed, false) {
+            Ok(res) if res.status().is_success() => Some(res.bytes()),
+            Ok(res) => {
+                println!("get_file: {} returned {}", url, res.status());
+                None
+            }
+            Err(e) => {
+                println!("get_file: {:?}", e);
+                None
+            }
         }
     }
 
     pub fn get_best_image(
         metas: &[models::ImageMeta]
     ) -> Result<DynamicImage, String> {
-        // Get all image URLs, sorted by size in reverse.
+        get_best_image_with_bytes(metas).map(|(image, _)| image)
+    }
+
+    /// Same as get_best_image, but also returns the bytes as downloaded, for keeping
+    /// the original file around.
+    pub fn get_best_image_with_bytes(
+        metas: &[models::ImageMeta]
+    ) -> Result<(DynamicImage, Vec<u8>), String> {
+        // Get all image URLs, sorted by size in reverse. Among images of the same size,
+        // earlier ones (from higher-priority providers) are tried first.
         // Avoid cloning by sorting a mutable vector of references.
         let mut images: Vec<&models::ImageMeta> = metas.iter().collect();
         if images.is_empty() {
             return Err(String::from("This album's metadata does not provide any image."));
         }
-        images.sort_by_key(|img| img.size);
-        for image in images.iter().rev() {
+        images.sort_by_key(|img| std::cmp::Reverse(img.size));
+        for image in images.iter() {
             if let Some(bytes) = get_file(image.url.as_ref()) {
                 println!("Downloaded image from: {:?}", &image.url);
-                if let Some(image) = utils::read_image_from_bytes(bytes) {
-                    return Ok(image);
+                if let Some(image) = utils::read_image_from_bytes(&bytes) {
+                    return Ok((image, bytes));
                 }
             }
         }
@@ -95,6 +129,13 @@ pub trait MetadataProvider: Send + Sync {
     /// Set priority of this provider.
     fn set_priority(&self, prio: u32);
 
+    /// Whether this provider looks things up using fields filled in by providers before
+    /// it in the chain, such as links found by MusicBrainz. Such providers are called only
+    /// once the earlier ones have returned.
+    fn builds_on_earlier(&self) -> bool {
+        false
+    }
+
     /// Get textual metadata that wouldn't be available as song tags, such as wiki, producer name,
     /// etc. A new AlbumMeta object containing data from both the existing AlbumMeta and newly fetched data. New
     /// data will always overwrite existing fields.
@@ -108,4 +149,21 @@ pub trait MetadataProvider: Send + Sync {
     fn get_artist_meta(
         &self, key: bson::Document, existing: Option<models::ArtistMeta>
     ) -> Option<models::ArtistMeta>;
+
+    /// Get all release groups of an artist given their MusicBrainz ID, including those not
+    /// in the user's library. Providers that cannot list discographies return None.
+    fn get_artist_discography(&self, _mbid: &str) -> Option<Vec<models::ReleaseGroupMeta>> {
+        None
+    }
+
+    /// List possible matches for an album, best first, for the user to pick from.
+    /// Providers that don't search remote databases return nothing.
+    fn search_album_candidates(&self, _key: bson::Document) -> Vec<models::MatchCandidate> {
+        Vec::new()
+    }
+
+    /// List possible matches for an artist, best first, for the user to pick from.
+    fn search_artist_candidates(&self, _key: bson::Document) -> Vec<models::MatchCandidate> {
+        Vec::new()
+    }
 }
@@ -1,7 +1,41 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{SystemTime, UNIX_EPOCH}
+};
+use bson::Bson;
+use gtk::prelude::*;
+use rustc_hash::FxHashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::utils::{meta_provider_settings, settings_manager};
+
 use super::{
-    lastfm::LastfmWrapper, models, musicbrainz::MusicBrainzWrapper, MetadataProvider
+    external::ExternalWrapper, lastfm::LastfmWrapper, local::LocalWrapper, models, musicbrainz::MusicBrainzWrapper, wikipedia::WikipediaWrapper,
+    MetadataProvider
 };
 
+/// Keys of all available providers, in their default order.
+const PROVIDER_KEYS: [&str; 5] = ["local", "musicbrainz", "wikipedia", "lastfm", "external"];
+
+/// Fields the user can choose a preferred provider for, as (kind, field name) pairs.
+/// Field names are as serialised.
+pub const PREFERABLE_FIELDS: [(&str, &str); 6] = [
+    ("album", "wiki"),
+    ("album", "tags"),
+    ("album", "image"),
+    ("artist", "bio"),
+    ("artist", "tags"),
+    ("artist", "image")
+];
+
+/// Whether a provider found anything for an album or artist, as of the given UNIX timestamp.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProviderResult {
+    pub provider: String,
+    pub found: bool,
+    pub fetched: i64
+}
+
 /// A meta-MetadataProvider that works by daisy-chaining actual MetadataProviders.
 /// Think composite pattern.
 /// The key document might be updated as it passes through providers, for example
@@ -32,48 +66,386 @@ impl MetadataProvider for MetadataChain {
     fn set_priority(&self, _prio: u32) {}
 
     fn get_album_meta(
-        self: &Self, key: bson::Document, mut existing: Option<models::AlbumMeta>
+        self: &Self, key: bson::Document, existing: Option<models::AlbumMeta>
     ) -> Option<models::AlbumMeta> {
-        let mut current_key: bson::Document = key.clone();
-        for provider in self.providers.iter() {
-            existing = provider.get_album_meta(current_key.clone(), existing);
-            // Update key document with new fields
-            if let Some(meta) = &existing {
-                if let Some(id) = &meta.mbid {
-                    if !current_key.contains_key("mbid") {
-                        current_key.insert("mbid", id.to_owned());
+        self.get_album_meta_skipping(key, existing, &|_| false).0
+    }
+
+    fn get_artist_meta(
+        self: &Self, key: bson::Document, existing: Option<models::ArtistMeta>
+    ) -> Option<models::ArtistMeta> {
+        self.get_artist_meta_skipping(key, existing, &|_| false).0
+    }
+
+    /// Unlike other metadata, discographies are not merged. The first provider to
+    /// return one wins.
+    fn get_artist_discography(&self, mbid: &str) -> Option<Vec<models::ReleaseGroupMeta>> {
+        self.providers
+            .iter()
+            .find_map(|provider| provider.get_artist_discography(mbid))
+    }
+
+    /// Candidates from all providers, grouped by provider in priority order.
+    fn search_album_candidates(&self, key: bson::Document) -> Vec<models::MatchCandidate> {
+        self.providers
+            .iter()
+            .flat_map(|provider| provider.search_album_candidates(key.clone()))
+            .collect()
+    }
+
+    fn search_artist_candidates(&self, key: bson::Document) -> Vec<models::MatchCandidate> {
+        self.providers
+            .iter()
+            .flat_map(|provider| provider.search_artist_candidates(key.clone()))
+            .collect()
+    }
+}
+
+/// Everything gathered so far while running a chain.
+struct ChainRun {
+    preferred: FxHashMap<String, String>,
+    key: bson::Document,
+    merged: Option<bson::Document>,
+    sources: BTreeMap<String, models::FieldSource>,
+    // Values of fields with a preferred provider, in chain order
+    contributions: FxHashMap<String, Vec<(String, Bson)>>,
+    results: Vec<ProviderResult>
+}
+
+impl ChainRun {
+    /// What to give the next providers: everything merged so far, minus fields with a
+    /// preferred provider.
+    fn input(&self) -> Option<bson::Document> {
+        self.merged.clone().map(|mut doc| {
+            for field in self.preferred.keys() {
+                clear_field(&mut doc, field);
+            }
+            doc
+        })
+    }
+
+    /// Merge in what a provider returned for the given input. Outputs must be applied in
+    /// chain order, so that earlier providers fill in single-valued fields first.
+    fn apply(&mut self, provider: &str, input: Option<&bson::Document>, output: Option<bson::Document>) {
+        let fetched = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        // Providers return the existing data as-is when they find nothing.
+        let mut found = false;
+        if let Some(doc) = output {
+            // The first output is taken as-is.
+            let first = self.merged.is_none();
+            let merged = self.merged.get_or_insert_with(|| doc.clone());
+            for (field, value) in doc.iter() {
+                if field == "sources" {
+                    continue;
+                }
+                let Some(added) = added_value(input.and_then(|input| input.get(field)), value) else {
+                    continue;
+                };
+                found = true;
+                if self.preferred.contains_key(field) {
+                    self.contributions.entry(field.to_owned()).or_default().push((provider.to_owned(), added));
+                    continue;
+                }
+                let applied = first || match merged.get_mut(field) {
+                    Some(Bson::Array(items)) => {
+                        if let Bson::Array(mut added) = added {
+                            items.append(&mut added);
+                        }
+                        true
                     }
+                    None | Some(Bson::Null) => {
+                        merged.insert(field.to_owned(), added);
+                        true
+                    }
+                    // Already filled by a provider earlier in the chain
+                    _ => false
+                };
+                if applied {
+                    add_source(&mut self.sources, field, provider, fetched);
+                }
+            }
+            // Update key document with new fields
+            if let Ok(id) = doc.get_str("mbid") {
+                if !self.key.contains_key("mbid") {
+                    self.key.insert("mbid", id.to_owned());
                 }
             }
         }
-        existing
+        self.results.push(ProviderResult {
+            provider: provider.to_owned(),
+            found,
+            fetched
+        });
     }
+}
 
-    fn get_artist_meta(
-        self: &Self, key: bson::Document, mut existing: Option<models::ArtistMeta>
-    ) -> Option<models::ArtistMeta> {
-        let mut current_key: bson::Document = key.clone();
-        for provider in self.providers.iter() {
-            existing = provider.get_artist_meta(current_key.clone(), existing);
-            // Update key document with new fields
-            if let Some(meta) = &existing {
-                if let Some(id) = &meta.mbid {
-                    if !current_key.contains_key("mbid") {
-                        current_key.insert("mbid", id.to_owned());
+impl MetadataChain {
+    /// Call each provider, except those for which skip returns true. Also reports
+    /// whether each called provider added anything, and records where each field came from.
+    /// Providers run in order until one of them finds an MBID, as the rest can locate
+    /// metadata more accurately with it. The remaining ones then run in parallel, each
+    /// within its own rate limit, & their results are merged in chain order. Those that
+    /// build on earlier providers' results are called during that merge instead.
+    /// Fields with a preferred provider are hidden from every provider so that each
+    /// fills them in from scratch. The preferred provider's value is then used if it
+    /// found one, else they are merged in chain order like any other field.
+    fn run<T: Serialize + DeserializeOwned + Send>(
+        &self,
+        kind: &str,
+        key: bson::Document,
+        existing: Option<T>,
+        skip: &dyn Fn(&str) -> bool,
+        call: impl Fn(&dyn MetadataProvider, bson::Document, Option<T>) -> Option<T> + Sync
+    ) -> (Option<T>, Vec<ProviderResult>) {
+        let merged: Option<bson::Document> = existing.as_ref().and_then(|meta| bson::to_document(meta).ok());
+        let sources: BTreeMap<String, models::FieldSource> = merged
+            .as_ref()
+            .and_then(|doc| doc.get("sources").cloned())
+            .and_then(|sources| bson::from_bson(sources).ok())
+            .unwrap_or_default();
+        let mut run = ChainRun {
+            preferred: get_field_preferences(kind),
+            key,
+            merged,
+            sources,
+            contributions: FxHashMap::default(),
+            results: Vec::with_capacity(self.providers.len())
+        };
+        let providers: Vec<&dyn MetadataProvider> = self.providers
+            .iter()
+            .map(|provider| provider.as_ref())
+            .filter(|provider| !skip(provider.key()))
+            .collect();
+        let mut next = 0;
+        while next < providers.len() && !run.key.contains_key("mbid") {
+            let input = run.input();
+            let output = call(
+                providers[next],
+                run.key.clone(),
+                input.clone().and_then(|doc| bson::from_document(doc).ok())
+            );
+            run.apply(providers[next].key(), input.as_ref(), output.and_then(|meta| bson::to_document(&meta).ok()));
+            next += 1;
+        }
+        if next < providers.len() {
+            let input = run.input();
+            let call = &call;
+            // None for providers that have yet to be called
+            let outputs: Vec<Option<Option<T>>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = providers[next..]
+                    .iter()
+                    .map(|provider| {
+                        if provider.builds_on_earlier() {
+                            return None;
+                        }
+                        let key = run.key.clone();
+                        let input: Option<T> = input.clone().and_then(|doc| bson::from_document(doc).ok());
+                        Some(scope.spawn(move || call(*provider, key, input)))
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.map(|handle| handle.join().unwrap_or(None)))
+                    .collect()
+            });
+            for (provider, output) in providers[next..].iter().zip(outputs.into_iter()) {
+                match output {
+                    Some(output) => {
+                        run.apply(provider.key(), input.as_ref(), output.and_then(|meta| bson::to_document(&meta).ok()));
                     }
+                    None => {
+                        let input = run.input();
+                        let output = call(
+                            *provider,
+                            run.key.clone(),
+                            input.clone().and_then(|doc| bson::from_document(doc).ok())
+                        );
+                        run.apply(provider.key(), input.as_ref(), output.and_then(|meta| bson::to_document(&meta).ok()));
+                    }
+                }
+            }
+        }
+        let ChainRun {preferred, merged, mut sources, mut contributions, results, ..} = run;
+        let Some(mut doc) = merged else {
+            return (None, results);
+        };
+        for (field, preferred_provider) in preferred.iter() {
+            clear_field(&mut doc, field);
+            let mut values = contributions.remove(field).unwrap_or_default();
+            if values.iter().any(|(provider, _)| provider == preferred_provider) {
+                values.retain(|(provider, _)| provider == preferred_provider);
+            }
+            for (provider, value) in values.into_iter() {
+                let fetched = results
+                    .iter()
+                    .find(|result| result.provider == provider)
+                    .map_or(0, |result| result.fetched);
+                if !doc.contains_key(field) {
+                    doc.insert(field.to_owned(), value);
+                }
+                else if let (Some(Bson::Array(items)), Bson::Array(mut added)) = (doc.get_mut(field), value) {
+                    items.append(&mut added);
+                }
+                else {
+                    // Already filled by a provider earlier in the chain
+                    continue;
                 }
+                add_source(&mut sources, field, &provider, fetched);
+            }
+        }
+        if let Ok(sources) = bson::to_bson(&sources) {
+            doc.insert("sources", sources);
+        }
+        (bson::from_document(doc).ok(), results)
+    }
+
+    pub fn get_album_meta_skipping(
+        &self, key: bson::Document, existing: Option<models::AlbumMeta>, skip: &dyn Fn(&str) -> bool
+    ) -> (Option<models::AlbumMeta>, Vec<ProviderResult>) {
+        self.run(
+            "album",
+            key,
+            existing,
+            skip,
+            |provider, key, existing| provider.get_album_meta(key, existing)
+        )
+    }
+
+    pub fn get_artist_meta_skipping(
+        &self, key: bson::Document, existing: Option<models::ArtistMeta>, skip: &dyn Fn(&str) -> bool
+    ) -> (Option<models::ArtistMeta>, Vec<ProviderResult>) {
+        self.run(
+            "artist",
+            key,
+            existing,
+            skip,
+            |provider, key, existing| provider.get_artist_meta(key, existing)
+        )
+    }
+}
+
+/// Empty a field of a serialised AlbumMeta or ArtistMeta. List-type fields are kept
+/// as empty arrays since they cannot be missing.
+fn clear_field(doc: &mut bson::Document, field: &str) {
+    if let Some(Bson::Array(items)) = doc.get_mut(field) {
+        items.clear();
+    }
+    else {
+        doc.remove(field);
+    }
+}
+
+/// What a provider added to a field, given its values before & after the call.
+/// Providers append to list-type fields & only fill in missing fields otherwise.
+fn added_value(before: Option<&Bson>, after: &Bson) -> Option<Bson> {
+    match (before, after) {
+        (_, Bson::Null) => None,
+        (Some(Bson::Array(old)), Bson::Array(new)) => {
+            if new.len() > old.len() {
+                Some(Bson::Array(new[old.len()..].to_vec()))
+            }
+            else {
+                None
             }
         }
-        existing
+        (_, Bson::Array(new)) if new.is_empty() => None,
+        (Some(old), new) if old == new => None,
+        (_, new) => Some(new.clone())
+    }
+}
+
+fn add_source(sources: &mut BTreeMap<String, models::FieldSource>, field: &str, provider: &str, fetched: i64) {
+    let source = sources.entry(field.to_owned()).or_insert_with(|| models::FieldSource {
+        providers: Vec::new(),
+        fetched
+    });
+    if !source.providers.iter().any(|existing| existing == provider) {
+        source.providers.push(provider.to_owned());
+    }
+    source.fetched = source.fetched.max(fetched);
+}
+
+/// Get the user's preferred provider for each field of albums or artists ("album" or
+/// "artist"), keyed by field name. Fields without one follow the usual chain order.
+pub fn get_field_preferences(kind: &str) -> FxHashMap<String, String> {
+    let prefix = format!("{kind}.");
+    settings_manager()
+        .child("metaprovider")
+        .value("field-providers")
+        .get::<HashMap<String, String>>()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(field, provider)| {
+            field.strip_prefix(&prefix).map(|field| (field.to_owned(), provider))
+        })
+        .filter(|(_, provider)| PROVIDER_KEYS.contains(&provider.as_str()))
+        .collect()
+}
+
+/// Set or clear (with None) the preferred provider of a field.
+pub fn set_field_preference(kind: &str, field: &str, provider: Option<&str>) {
+    let settings = settings_manager().child("metaprovider");
+    let mut prefs = settings
+        .value("field-providers")
+        .get::<HashMap<String, String>>()
+        .unwrap_or_default();
+    let pref_key = format!("{kind}.{field}");
+    match provider {
+        Some(provider) => {
+            prefs.insert(pref_key, provider.to_owned());
+        }
+        None => {
+            prefs.remove(&pref_key);
+        }
+    }
+    let _ = settings.set_value("field-providers", &prefs.to_variant());
+}
+
+/// Human-readable name of a provider, as shown in the preferences dialog.
+pub fn get_provider_name(key: &str) -> String {
+    if PROVIDER_KEYS.contains(&key) {
+        meta_provider_settings(key).string("name").to_string()
+    }
+    else {
+        key.to_owned()
     }
 }
 
 /// Convenience method to construct a metadata provider instance by key with the given priority.
+/// Returns None for unknown keys, such as those of providers removed since the order
+/// was saved.
 /// When implementing a new provider, you must manually add it to this function too.
-pub fn get_provider_with_priority(key: &str, prio: u32) -> Box<dyn MetadataProvider> {
+pub fn get_provider_with_priority(key: &str, prio: u32) -> Option<Box<dyn MetadataProvider>> {
     match key {
-        "musicbrainz" => Box::new(MusicBrainzWrapper::new(prio)),
-        "lastfm" => Box::new(LastfmWrapper::new(prio)),
-        _ => unimplemented!()
+        "musicbrainz" => Some(Box::new(MusicBrainzWrapper::new(prio))),
+        "lastfm" => Some(Box::new(LastfmWrapper::new(prio))),
+        "wikipedia" => Some(Box::new(WikipediaWrapper::new(prio))),
+        "local" => Some(Box::new(LocalWrapper::new(prio))),
+        "external" => Some(Box::new(ExternalWrapper::new(prio))),
+        _ => {
+            println!("[Metadata] Skipping unknown provider {}", key);
+            None
+        }
+    }
+}
+
+/// Get the user's provider order. Providers added since the order was last saved
+/// are appended to the end, so that they show up in the preferences dialog.
+pub fn get_provider_order() -> Vec<String> {
+    let mut order: Vec<String> = settings_manager()
+        .child("metaprovider")
+        .value("order")
+        .array_iter_str()
+        .unwrap()
+        .filter(|key| PROVIDER_KEYS.contains(key))
+        .map(str::to_owned)
+        .collect();
+    for key in PROVIDER_KEYS {
+        if !order.iter().any(|existing| existing == key) {
+            order.push(key.to_owned());
+        }
     }
+    order
 }
@@ -0,0 +1,271 @@
+// Runs user-configured commands as a metadata provider, so that other sources
+// (such as in-house scrapers) can be plugged in without patching the app.
+//
+// Commands run in the order they are listed, each being given what the previous ones
+// found, so later commands only fill in the blanks. Each command is a command line &
+// may include arguments. Its executable is called with a single argument, either "album" or "artist",
+// and is given the key document as a JSON object on stdin. It may contain "name",
+// "artist", "mbid" and, for albums, "uri" (the album folder relative to MPD's music
+// directory), "year" & "track_count" (both integers). It should print a JSON object in the shape of AlbumMeta or ArtistMeta
+// to stdout & exit with 0. All fields are optional. Exiting with 0 without printing
+// anything means nothing was found. Anything written to stderr is logged.
+// Failures are also reported to the user (see set_error_sender), with the last line
+// written to stderr if any.
+use async_channel::Sender;
+use gtk::{glib, prelude::*};
+extern crate bson;
+
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+    sync::{OnceLock, RwLock},
+    thread,
+    time::{Duration, Instant}
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::utils::meta_provider_settings;
+
+use super::{super::{
+    models, prelude::*, Metadata, MetadataProvider
+}, PROVIDER_KEY};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static ERROR_SENDER: OnceLock<Sender<Metadata>> = OnceLock::new();
+
+/// Let failing commands be reported to the user through the cache controller.
+pub fn set_error_sender(sender: Sender<Metadata>) {
+    let _ = ERROR_SENDER.set(sender);
+}
+
+fn report_error(command: &str, message: String) {
+    println!("[External] {}: {}", command, &message);
+    if let Some(sender) = ERROR_SENDER.get() {
+        let _ = sender.send_blocking(Metadata::ProviderError(PROVIDER_KEY.to_owned(), format!("{}: {}", command, message)));
+    }
+}
+
+/// Configured command lines, in the order they should run. The single executable used
+/// by older versions is run last.
+pub fn get_commands() -> Vec<String> {
+    let settings = meta_provider_settings(PROVIDER_KEY);
+    let mut commands: Vec<String> = settings
+        .strv("commands")
+        .iter()
+        .map(|command| command.trim().to_owned())
+        .filter(|command| !command.is_empty())
+        .collect();
+    let legacy = settings.string("executable");
+    if !legacy.is_empty() && !commands.iter().any(|command| command == legacy.as_str()) {
+        commands.push(legacy.to_string());
+    }
+    commands
+}
+
+pub struct ExternalWrapper {
+    priority: RwLock<u32>
+}
+
+impl ExternalWrapper {
+    /// Run a command & get its stdout. Returns None if it could not be run, timed out
+    /// or exited with an error.
+    fn run(&self, command: &str, kind: &str, key: &bson::Document) -> Option<String> {
+        let timeout = Duration::from_secs(meta_provider_settings(PROVIDER_KEY).uint("timeout-s") as u64);
+        let argv = match glib::shell_parse_argv(command) {
+            Ok(argv) if !argv.is_empty() => argv,
+            Ok(_) => return None,
+            Err(e) => {
+                report_error(command, format!("invalid command line ({})", e));
+                return None;
+            }
+        };
+        let input = match serde_json::to_vec(key) {
+            Ok(input) => input,
+            Err(e) => {
+                println!("[External] Could not serialise key document: {:?}", e);
+                return None;
+            }
+        };
+
+        println!("[External] Running {} {} with key {:?}", command, kind, key);
+        let mut child = match Command::new(&argv[0])
+            .args(&argv[1..])
+            .arg(kind)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    report_error(command, format!("could not run ({})", e));
+                    return None;
+                }
+            };
+
+        // Write & close stdin so the executable knows the input has ended. Outputs are
+        // read on separate threads to avoid deadlocking on a full pipe.
+        if let Some(mut stdin) = child.stdin.take() {
+            if let Err(e) = stdin.write_all(&input) {
+                println!("[External] Could not write to stdin: {:?}", e);
+            }
+        }
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let stdout_reader = thread::spawn(move || {
+            let mut buf = String::new();
+            let _ = stdout.read_to_string(&mut buf);
+            buf
+        });
+        let stderr_reader = thread::spawn(move || {
+            let mut buf = String::new();
+            let _ = stderr.read_to_string(&mut buf);
+            buf
+        });
+
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) => {
+                    if started.elapsed() >= timeout {
+                        report_error(command, format!("timed out after {}s", timeout.as_secs()));
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        break None;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(e) => {
+                    report_error(command, format!("could not wait for it ({})", e));
+                    let _ = child.kill();
+                    break None;
+                }
+            }
+        };
+
+        let output = stdout_reader.join().unwrap_or_default();
+        let errors = stderr_reader.join().unwrap_or_default();
+        for line in errors.lines() {
+            println!("[External] {}: {}", command, line);
+        }
+        match status {
+            Some(status) if status.success() => Some(output),
+            Some(status) => {
+                match errors.lines().rev().find(|line| !line.trim().is_empty()) {
+                    Some(line) => report_error(command, format!("exited with {} ({})", status, line.trim())),
+                    None => report_error(command, format!("exited with {}", status))
+                }
+                None
+            }
+            None => None
+        }
+    }
+
+    /// Parse a command's output on top of a minimal document built from the key, so
+    /// that the command only has to print the fields it knows about.
+    fn parse<T: Serialize + DeserializeOwned>(&self, command: &str, output: &str, base: T) -> Option<T> {
+        if output.trim().is_empty() {
+            return None;
+        }
+        let parsed: serde_json::Value = match serde_json::from_str(output) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                report_error(command, format!("printed invalid JSON ({})", e));
+                return None;
+            }
+        };
+        let serde_json::Value::Object(fields) = parsed else {
+            report_error(command, "printed something other than a JSON object".to_owned());
+            return None;
+        };
+        let mut res = serde_json::to_value(base).ok()?;
+        if let serde_json::Value::Object(res_fields) = &mut res {
+            res_fields.extend(fields);
+        }
+        match serde_json::from_value(res) {
+            Ok(meta) => Some(meta),
+            Err(e) => {
+                report_error(command, format!("printed invalid fields ({})", e));
+                None
+            }
+        }
+    }
+}
+
+impl MetadataProvider for ExternalWrapper {
+    fn new(prio: u32) -> Self {
+        Self {
+            priority: RwLock::new(prio)
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        PROVIDER_KEY
+    }
+
+    fn priority(&self) -> u32 {
+        *self.priority.read().expect("Poisoned RwLock")
+    }
+
+    fn set_priority(&self, prio: u32) {
+        let mut this_prio = self.priority.write().expect("Poisoned RwLock");
+        *this_prio = prio;
+    }
+
+    fn get_album_meta(
+        &self,
+        key: bson::Document,
+        mut existing: Option<models::AlbumMeta>
+    ) -> Option<models::AlbumMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        for command in get_commands().iter() {
+            let Some(output) = self.run(command, "album", &key) else {
+                continue;
+            };
+            if let Some(mut new) = self.parse(command, &output, models::AlbumMeta::from_key(&key)) {
+                // Keep the name & artist we queried with, else we won't be able to
+                // look the result back up from the cache.
+                if let Ok(name) = key.get_str("name") {
+                    new.name = name.to_owned();
+                }
+                if let Ok(artist) = key.get_str("artist") {
+                    new.artist = Some(artist.to_owned());
+                }
+                existing = match existing {
+                    Some(old) => Some(old.merge(new)),
+                    None => Some(new)
+                };
+            }
+        }
+        existing
+    }
+
+    fn get_artist_meta(
+        &self,
+        key: bson::Document,
+        mut existing: Option<models::ArtistMeta>
+    ) -> Option<models::ArtistMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        for command in get_commands().iter() {
+            let Some(output) = self.run(command, "artist", &key) else {
+                continue;
+            };
+            if let Some(mut new) = self.parse(command, &output, models::ArtistMeta::from_key(&key)) {
+                if let Ok(name) = key.get_str("name") {
+                    new.name = name.to_owned();
+                }
+                existing = match existing {
+                    Some(old) => Some(old.merge(new)),
+                    None => Some(new)
+                };
+            }
+        }
+        existing
+    }
+}
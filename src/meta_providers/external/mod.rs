@@ -0,0 +1,5 @@
+static PROVIDER_KEY: &str = "external";
+
+mod controller;
+
+pub use controller::{get_commands, set_error_sender, ExternalWrapper};
@@ -0,0 +1,257 @@
+// Shared HTTP request scheduler for all metadata providers. Requests to the same
+// service are rate-limited together using per-host token buckets, retried with
+// exponential backoff when the service asks us to slow down, and optionally made
+// conditional using ETag/Last-Modified validators stored in the document cache.
+extern crate bson;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, RwLock},
+    thread,
+    time::{Duration, Instant}
+};
+
+use gtk::prelude::*;
+use once_cell::sync::Lazy;
+use reqwest::{
+    blocking::Client,
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER, USER_AGENT},
+    StatusCode,
+    Url
+};
+use rustc_hash::FxHashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{config::APPLICATION_USER_AGENT, utils::settings_manager};
+
+// Upper bound for backoff delays, including those requested via Retry-After.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    let settings = settings_manager().child("metaprovider");
+    Client::builder()
+        .connect_timeout(Duration::from_secs(settings.uint("connect-timeout-s") as u64))
+        .build()
+        .expect("Could not create HTTP client")
+});
+
+static BUCKETS: Lazy<Mutex<FxHashMap<String, TokenBucket>>> = Lazy::new(|| Mutex::new(FxHashMap::default()));
+
+// Set by the cache controller once the database is open. Until then, requests are
+// simply made unconditionally.
+static DOC_CACHE: OnceLock<Arc<RwLock<polodb_core::Database>>> = OnceLock::new();
+
+/// Cached body & validators of a previous response, used to make conditional requests.
+#[derive(Serialize, Deserialize, Debug)]
+struct HttpCacheRecord {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+    body: String
+}
+
+struct TokenBucket {
+    // Tokens per second
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        // Allow bursting up to one second's worth of requests
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now()
+        }
+    }
+
+    /// Take a token if one is available, else return how long to wait for one.
+    fn try_take(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last_refill).as_secs_f64() * self.rate).min(self.capacity);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        }
+        else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+/// Get the bucket name & rate (requests per second) for the given host. Configured
+/// limits also apply to subdomains, so "wikipedia.org" covers "en.wikipedia.org".
+/// Hosts without a configured limit fall back to delay-between-requests-s.
+fn host_limit(host: &str) -> (String, f64) {
+    let settings = settings_manager().child("metaprovider");
+    let limits: HashMap<String, f64> = settings.get("host-rate-limits");
+    let matched = limits
+        .iter()
+        .filter(|(name, rate)| {
+            **rate > 0.0 && (host == name.as_str() || host.ends_with(&format!(".{name}")))
+        })
+        .max_by_key(|(name, _)| name.len());
+    if let Some((name, rate)) = matched {
+        (name.clone(), *rate)
+    }
+    else {
+        let delay = settings.double("delay-between-requests-s");
+        (host.to_owned(), if delay > 0.0 { 1.0 / delay } else { f64::INFINITY })
+    }
+}
+
+/// Block until a request to the given host is allowed. Only needs to be called
+/// directly for requests made through other libraries, such as musicbrainz_rs.
+pub fn throttle(host: &str) {
+    let (name, rate) = host_limit(host);
+    if !rate.is_finite() {
+        return;
+    }
+    loop {
+        let wait = BUCKETS
+            .lock()
+            .expect("Poisoned Mutex")
+            .entry(name.clone())
+            .or_insert_with(|| TokenBucket::new(rate))
+            .try_take();
+        match wait {
+            Some(duration) => thread::sleep(duration),
+            None => return
+        }
+    }
+}
+
+/// Let the scheduler store validators & bodies of cacheable responses.
+pub fn set_doc_cache(doc_cache: Arc<RwLock<polodb_core::Database>>) {
+    let _ = DOC_CACHE.set(doc_cache);
+}
+
+fn load_cache_record(url: &str) -> Option<HttpCacheRecord> {
+    DOC_CACHE.get()?
+        .read()
+        .unwrap()
+        .collection::<HttpCacheRecord>("http_cache")
+        .find_one(bson::doc! {"url": url})
+        .ok()
+        .flatten()
+}
+
+fn store_cache_record(record: HttpCacheRecord) {
+    if let Some(doc_cache) = DOC_CACHE.get() {
+        let db = doc_cache.write().unwrap();
+        let coll = db.collection::<HttpCacheRecord>("http_cache");
+        let _ = coll.delete_many(bson::doc! {"url": &record.url});
+        let _ = coll.insert_one(record);
+    }
+}
+
+/// Parse a Retry-After header given in seconds. HTTP dates are not supported &
+/// fall back to exponential backoff.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// A fully-read response. Bodies are read eagerly so that they can be cached.
+pub struct HttpResponse {
+    status: StatusCode,
+    body: Vec<u8>
+}
+
+impl HttpResponse {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn bytes(self) -> Vec<u8> {
+        self.body
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+/// GET the given URL, waiting for the host's rate limit & retrying on 429/503 or
+/// transient network errors. If conditional is true, validators from the previous
+/// response are sent along & a 304 is transparently turned into the cached response.
+/// Only use conditional for textual responses such as API calls, not for images.
+pub fn get(url: Url, conditional: bool) -> reqwest::Result<HttpResponse> {
+    let settings = settings_manager().child("metaprovider");
+    let max_retries = settings.uint("max-retries");
+    let timeout = Duration::from_secs(settings.uint("read-timeout-s") as u64);
+    let host = url.host_str().unwrap_or_default().to_owned();
+    let cached = if conditional { load_cache_record(url.as_str()) } else { None };
+
+    let mut attempt: u32 = 0;
+    loop {
+        throttle(&host);
+        let mut req = CLIENT
+            .get(url.clone())
+            .header(USER_AGENT, APPLICATION_USER_AGENT)
+            .timeout(timeout);
+        if let Some(record) = cached.as_ref() {
+            if let Some(etag) = record.etag.as_ref() {
+                req = req.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = record.last_modified.as_ref() {
+                req = req.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let backoff = Duration::from_secs(1 << attempt.min(6)).min(MAX_BACKOFF);
+        match req.send() {
+            Ok(resp) => {
+                let status = resp.status();
+                if (status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE)
+                    && attempt < max_retries
+                {
+                    let delay = retry_after(resp.headers()).unwrap_or(backoff).min(MAX_BACKOFF);
+                    println!("[HTTP] {} returned {}, retrying in {}s", &host, status, delay.as_secs());
+                    thread::sleep(delay);
+                    attempt += 1;
+                    continue;
+                }
+                if status == StatusCode::NOT_MODIFIED {
+                    if let Some(record) = cached {
+                        println!("[HTTP] {} not modified, using cached response", url.as_str());
+                        return Ok(HttpResponse { status: StatusCode::OK, body: record.body.into_bytes() });
+                    }
+                }
+                let etag = resp.headers().get(ETAG).and_then(|val| val.to_str().ok()).map(str::to_owned);
+                let last_modified = resp.headers().get(LAST_MODIFIED).and_then(|val| val.to_str().ok()).map(str::to_owned);
+                let body = resp.bytes()?.to_vec();
+                if conditional && status == StatusCode::OK && (etag.is_some() || last_modified.is_some()) {
+                    if let Ok(text) = String::from_utf8(body.clone()) {
+                        store_cache_record(HttpCacheRecord {
+                            url: url.to_string(),
+                            etag,
+                            last_modified,
+                            body: text
+                        });
+                    }
+                }
+                return Ok(HttpResponse { status, body });
+            }
+            Err(e) if (e.is_timeout() || e.is_connect()) && attempt < max_retries => {
+                println!("[HTTP] Request to {} failed ({}), retrying in {}s", &host, e, backoff.as_secs());
+                thread::sleep(backoff);
+                attempt += 1;
+            }
+            Err(e) => return Err(e)
+        }
+    }
+}
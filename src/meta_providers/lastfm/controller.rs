@@ -2,26 +2,23 @@ extern crate bson;
 
 use std::sync::RwLock;
 
-use reqwest::{
-    blocking::{Client, Response},
-    header::USER_AGENT
-};
+use reqwest::Url;
 use gtk::prelude::*;
 
-use crate::{
-    config::APPLICATION_USER_AGENT,
-    utils::meta_provider_settings
-};
+use crate::utils::meta_provider_settings;
 
 use super::{super::{
-    models, prelude::*, MetadataProvider
+    http::{self, HttpResponse}, models, prelude::*, MetadataProvider
 }, PROVIDER_KEY};
-use super::models::{LastfmAlbumResponse, LastfmArtistResponse};
+use super::models::{
+    LastfmAlbumResponse, LastfmAlbumSearchResponse, LastfmArtistResponse, LastfmArtistSearchResponse
+};
 
 pub const API_ROOT: &str = "http://ws.audioscrobbler.com/2.0";
+// How many search results to offer when the user picks a match manually
+const MAX_CANDIDATES: usize = 10;
 
 pub struct LastfmWrapper {
-    client: Client,
     priority: RwLock<u32>
 }
 
@@ -30,26 +27,29 @@ impl LastfmWrapper {
         &self,
         method: &str,
         params: &[(&str, String)]
-    ) -> Option<Response> {
+    ) -> Option<HttpResponse> {
         let settings = meta_provider_settings(PROVIDER_KEY);
         let key = settings.string("api-key").to_string();
         // Return None if there is no API key specified.
         if !key.is_empty() {
             println!("Last.fm: calling `{}` with query {:?}", method, params);
-            let resp = self.client
-                .get(API_ROOT)
-                .query(&[
+            let url = Url::parse_with_params(
+                API_ROOT,
+                [
                     ("format", "json"),
                     ("method", method),
                     ("api_key", key.as_ref())
-                ])
-                .query(params)
-                .header(USER_AGENT, APPLICATION_USER_AGENT)
-                .send();
-            if let Ok(res) = resp {
-                return Some(res);
+                ]
+                    .into_iter()
+                    .chain(params.iter().map(|(k, v)| (*k, v.as_str())))
+            ).ok()?;
+            match http::get(url, true) {
+                Ok(res) => return Some(res),
+                Err(e) => {
+                    println!("[Last.fm] Request failed: {:?}", e);
+                    return None;
+                }
             }
-            return None;
         }
         None
     }
@@ -58,7 +58,6 @@ impl LastfmWrapper {
 impl MetadataProvider for LastfmWrapper {
     fn new(prio: u32) -> Self {
         Self {
-            client: Client::new(),
             priority: RwLock::new(prio)
         }
     }
@@ -85,7 +84,10 @@ impl MetadataProvider for LastfmWrapper {
     ) -> Option<models::AlbumMeta> {
         if meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
             // Will panic if key document is not a simple map of String to String
-            let params: Vec<(&str, String)> = key.iter().map(
+            // Skip the folder URI & matching hints, which Last.fm doesn't understand.
+            let params: Vec<(&str, String)> = key.iter().filter(
+                |kv| matches!(kv.0.as_str(), "name" | "artist" | "mbid")
+            ).map(
                 |kv: (&String, &bson::Bson)| {
                     // Last.fm wants "album" in query param but will return "name".
                     // Our bson key follows the returned result schema so it'll have to be renamed here.
@@ -215,4 +217,50 @@ impl MetadataProvider for LastfmWrapper {
             existing
         }
     }
+    fn search_album_candidates(&self, key: bson::Document) -> Vec<models::MatchCandidate> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return Vec::new();
+        }
+        let Ok(name) = key.get_str("name") else {
+            return Vec::new();
+        };
+        // album.search only matches titles. Candidates will list their artists.
+        let Some(resp) = self.get_lastfm("album.search", &[("album", name.to_owned())]) else {
+            return Vec::new();
+        };
+        match resp.json::<LastfmAlbumSearchResponse>() {
+            Ok(parsed) => parsed.results.albummatches.album
+                .into_iter()
+                .take(MAX_CANDIDATES)
+                .map(models::MatchCandidate::from)
+                .collect(),
+            Err(err) => {
+                println!("[Last.fm] search_album_candidates: {}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    fn search_artist_candidates(&self, key: bson::Document) -> Vec<models::MatchCandidate> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return Vec::new();
+        }
+        let Ok(name) = key.get_str("name") else {
+            return Vec::new();
+        };
+        let Some(resp) = self.get_lastfm("artist.search", &[("artist", name.to_owned())]) else {
+            return Vec::new();
+        };
+        match resp.json::<LastfmArtistSearchResponse>() {
+            Ok(parsed) => parsed.results.artistmatches.artist
+                .into_iter()
+                .take(MAX_CANDIDATES)
+                .map(models::MatchCandidate::from)
+                .collect(),
+            Err(err) => {
+                println!("[Last.fm] search_artist_candidates: {}", err);
+                Vec::new()
+            }
+        }
+    }
 }
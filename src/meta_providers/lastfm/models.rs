@@ -1,9 +1,10 @@
+use std::collections::BTreeMap;
 use gtk::prelude::SettingsExt;
 use musicbrainz_rs::entity::artist::ArtistType;
 use serde::Deserialize;
 use crate::utils::meta_provider_settings;
 
-use super::{super::models::{AlbumMeta, ArtistMeta, ImageMeta, ImageSize, Tag, Wiki}, PROVIDER_KEY};
+use super::{super::models::{AlbumMeta, ArtistMeta, ImageMeta, ImageSize, MatchCandidate, Tag, Wiki}, PROVIDER_KEY};
 // Last.fm JSON structs, for deserialising API responses only.
 // Widgets should use the standard structs defined in the supercrate's models.rs.
 
@@ -151,8 +152,15 @@ impl From<LastfmAlbum> for AlbumMeta {
              mbid: lfm.mbid,
              tags,
              image,
+             artwork: Vec::with_capacity(0),
              url: Some(lfm.url),
-             wiki
+             wiki,
+             release_type: None, // not provided
+             release_group_mbid: None,
+             wikidata_id: None,
+             wikipedia_url: None,
+             match_score: None,
+             sources: BTreeMap::new()
         }
     }
 }
@@ -189,7 +197,11 @@ impl Into<ArtistMeta> for LastfmSimilarArtist {
             gender: None,
             begin_date: None,
             end_date: None,
-            country: None
+            country: None,
+            wikidata_id: None,
+            wikipedia_url: None,
+            match_score: None,
+            sources: BTreeMap::new()
         }
     }
 }
@@ -244,7 +256,11 @@ impl Into<ArtistMeta> for LastfmArtist {
             gender: None,
             begin_date: None,
             end_date: None,
-            country: None
+            country: None,
+            wikidata_id: None,
+            wikipedia_url: None,
+            match_score: None,
+            sources: BTreeMap::new()
         }
     }
 }
@@ -255,3 +271,107 @@ impl Into<ArtistMeta> for LastfmArtist {
 pub struct LastfmArtistResponse {
     pub artist: LastfmArtist
 }
+
+// Search results, used to let the user pick the right album or artist manually.
+// Matches are nested as results.albummatches.album & results.artistmatches.artist.
+#[derive(Deserialize, Debug)]
+pub struct LastfmAlbumMatch {
+    pub name: String,
+    pub artist: String,
+    #[serde(default)]
+    pub mbid: String,
+    #[serde(default)]
+    pub image: Vec<LastfmImage>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LastfmAlbumMatches {
+    pub album: Vec<LastfmAlbumMatch>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LastfmAlbumSearchResults {
+    pub albummatches: LastfmAlbumMatches
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LastfmAlbumSearchResponse {
+    pub results: LastfmAlbumSearchResults
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LastfmArtistMatch {
+    pub name: String,
+    #[serde(default)]
+    pub mbid: String,
+    // Returned as a string
+    #[serde(default)]
+    pub listeners: String
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LastfmArtistMatches {
+    pub artist: Vec<LastfmArtistMatch>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LastfmArtistSearchResults {
+    pub artistmatches: LastfmArtistMatches
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LastfmArtistSearchResponse {
+    pub results: LastfmArtistSearchResults
+}
+
+impl From<LastfmAlbumMatch> for MatchCandidate {
+    fn from(lfm: LastfmAlbumMatch) -> Self {
+        // Without an MBID, pin the names Last.fm knows this album by instead.
+        let pin = if lfm.mbid.is_empty() {
+            bson::doc! {"name": lfm.name.clone(), "artist": lfm.artist.clone()}
+        }
+        else {
+            bson::doc! {"mbid": lfm.mbid.clone()}
+        };
+        Self {
+            provider: PROVIDER_KEY.to_owned(),
+            thumbnail: lfm.image
+                .into_iter()
+                .find(|img| img.size == "large" && !img.url.is_empty())
+                .map(|img| img.url),
+            title: lfm.name,
+            artist: Some(lfm.artist),
+            details: Vec::new(),
+            score: None,
+            pin
+        }
+    }
+}
+
+impl From<LastfmArtistMatch> for MatchCandidate {
+    fn from(lfm: LastfmArtistMatch) -> Self {
+        let pin = if lfm.mbid.is_empty() {
+            bson::doc! {"name": lfm.name.clone()}
+        }
+        else {
+            bson::doc! {"mbid": lfm.mbid.clone()}
+        };
+        // TODO: translatable
+        let details = if lfm.listeners.is_empty() {
+            Vec::new()
+        }
+        else {
+            vec![format!("{} listeners", lfm.listeners)]
+        };
+        Self {
+            provider: PROVIDER_KEY.to_owned(),
+            // Last.fm no longer serves artist images, only placeholders.
+            thumbnail: None,
+            title: lfm.name,
+            artist: None,
+            details,
+            score: None,
+            pin
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use gtk::{glib, prelude::*};
+extern crate bson;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock
+};
+
+use crate::utils::meta_provider_settings;
+
+use super::{super::{
+    models::{self, ImageMeta, ImageSize}, prelude::*, MetadataProvider
+}, nfo::{read_album_nfo, read_artist_nfo}, PROVIDER_KEY};
+
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "webp", "gif"];
+// In order of preference
+const ALBUM_ART_NAMES: [&str; 3] = ["cover", "folder", "front"];
+const ARTIST_IMAGE_NAMES: [&str; 3] = ["artist", "folder", "fanart"];
+
+/// Get the configured music root, which should be the same folder MPD reads from.
+pub fn music_root() -> Option<PathBuf> {
+    let root = meta_provider_settings(PROVIDER_KEY).string("music-root");
+    if root.is_empty() {
+        return None;
+    }
+    let path = PathBuf::from(root.as_str());
+    if path.is_dir() {
+        Some(path)
+    }
+    else {
+        println!("[Local] Music root {:?} is not a folder", &path);
+        None
+    }
+}
+
+/// Whether a folder is one disc of a multi-disc album, like "CD1" or "Disc 2".
+fn is_disc_folder(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| {
+            let name = name.to_lowercase();
+            let rest = name
+                .strip_prefix("cd")
+                .or_else(|| name.strip_prefix("disc"))
+                .or_else(|| name.strip_prefix("disk"));
+            rest.is_some_and(|rest| {
+                let rest = rest.trim();
+                !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit())
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Find image files in a folder whose stems match one of the given names (case-insensitively),
+/// then add them with the given size. Images are added in the order of the given names, which
+/// is also the order get_best_image will try them in.
+fn add_images(folder: &Path, names: &[&str], size: ImageSize, res: &mut Vec<ImageMeta>) {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return;
+    };
+    let files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    for name in names.iter() {
+        for path in files.iter() {
+            let matches = path.file_stem().and_then(|stem| stem.to_str()).is_some_and(|stem| stem.eq_ignore_ascii_case(name))
+                && path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| {
+                    IMAGE_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known))
+                });
+            if matches {
+                if let Ok(url) = glib::filename_to_uri(path, None) {
+                    res.push(ImageMeta { size, url: url.to_string() });
+                }
+            }
+        }
+    }
+}
+
+/// Reads artwork & Kodi-style NFO files stored alongside the music itself. Only useful
+/// when the music folder is accessible from this machine, such as when MPD runs locally.
+pub struct LocalWrapper {
+    priority: RwLock<u32>
+}
+
+impl MetadataProvider for LocalWrapper {
+    fn new(prio: u32) -> Self {
+        Self {
+            priority: RwLock::new(prio)
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        PROVIDER_KEY
+    }
+
+    fn priority(&self) -> u32 {
+        *self.priority.read().expect("Poisoned RwLock")
+    }
+
+    fn set_priority(&self, prio: u32) {
+        let mut this_prio = self.priority.write().expect("Poisoned RwLock");
+        *this_prio = prio;
+    }
+
+    /// Read album.nfo & album art from the album's folder. Requires the folder URI
+    /// to be in the key document.
+    fn get_album_meta(
+        &self,
+        key: bson::Document,
+        existing: Option<models::AlbumMeta>
+    ) -> Option<models::AlbumMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        let (Some(root), Ok(uri)) = (music_root(), key.get_str("uri")) else {
+            return existing;
+        };
+        let folder = root.join(uri);
+        if !folder.is_dir() {
+            println!("[Local] Album folder {:?} not found", &folder);
+            return existing;
+        }
+        // Multi-disc albums usually keep album-level files in the parent folder.
+        let album_folder = if is_disc_folder(&folder) {
+            folder.parent().map(Path::to_path_buf).unwrap_or(folder.clone())
+        }
+        else {
+            folder.clone()
+        };
+
+        let mut new = models::AlbumMeta::from_key(&key);
+        if let Ok(artist) = key.get_str("artist") {
+            new.artist = Some(artist.to_owned());
+        }
+        let has_nfo = read_album_nfo(&album_folder.join("album.nfo"), &mut new);
+        if new.mbid.is_none() {
+            new.mbid = key.get_str("mbid").ok().map(str::to_owned);
+        }
+        // Prefer album-level art over disc-specific art
+        add_images(&album_folder, &ALBUM_ART_NAMES, ImageSize::Mega, &mut new.image);
+        if album_folder != folder {
+            add_images(&folder, &ALBUM_ART_NAMES, ImageSize::Mega, &mut new.image);
+        }
+        if !has_nfo && new.image.is_empty() {
+            return existing;
+        }
+        println!("[Local] Found {} album art(s) in {:?}", new.image.len(), &album_folder);
+
+        if let Some(old) = existing {
+            Some(old.merge(new))
+        }
+        else {
+            Some(new)
+        }
+    }
+
+    /// Read artist.nfo & artist images from the artist's folder, which must be named
+    /// after the artist & placed directly under the music root.
+    fn get_artist_meta(
+        &self,
+        key: bson::Document,
+        existing: Option<models::ArtistMeta>
+    ) -> Option<models::ArtistMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        let (Some(root), Ok(name)) = (music_root(), key.get_str("name")) else {
+            return existing;
+        };
+        // Slashes can't be part of folder names, so Kodi replaces them with underscores.
+        let folder_name = name.replace('/', "_");
+        if folder_name == "." || folder_name == ".." {
+            return existing;
+        }
+        let folder = root.join(folder_name);
+        if !folder.is_dir() {
+            return existing;
+        }
+
+        let mut new = models::ArtistMeta::from_key(&key);
+        let has_nfo = read_artist_nfo(&folder.join("artist.nfo"), &mut new);
+        if new.mbid.is_none() {
+            new.mbid = key.get_str("mbid").ok().map(str::to_owned);
+        }
+        add_images(&folder, &ARTIST_IMAGE_NAMES, ImageSize::Mega, &mut new.image);
+        if !has_nfo && new.image.is_empty() {
+            return existing;
+        }
+        println!("[Local] Found {} artist image(s) in {:?}", new.image.len(), &folder);
+
+        if let Some(old) = existing {
+            Some(old.merge(new))
+        }
+        else {
+            Some(new)
+        }
+    }
+}
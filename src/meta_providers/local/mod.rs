@@ -0,0 +1,6 @@
+static PROVIDER_KEY: &str = "local";
+
+mod controller;
+mod nfo;
+
+pub use controller::{music_root, LocalWrapper};
@@ -0,0 +1,112 @@
+// Minimal reader for Kodi-style album.nfo & artist.nfo files. These are flat XML
+// documents, so we only pick out the few elements we need instead of parsing them
+// fully. Nested elements of the same name (such as track titles in album.nfo)
+// come after the top-level ones, so single-valued fields use the first match.
+use std::path::Path;
+use chrono::NaiveDate;
+use musicbrainz_rs::entity::artist::ArtistType;
+use regex::Regex;
+
+use crate::common::ReleaseType;
+
+use super::super::models::{AlbumMeta, ArtistMeta, Tag, Wiki};
+
+const ATTRIBUTION: &str = "From a local NFO file";
+
+/// Get the contents of all elements with the given name.
+fn values(xml: &str, tag: &str) -> Vec<String> {
+    let re = Regex::new(&format!(r"(?is)<{tag}(?:\s[^>]*)?>(.*?)</{tag}\s*>")).unwrap();
+    re.captures_iter(xml)
+        .map(|caps| {
+            let raw = caps[1].trim();
+            let raw = raw
+                .strip_prefix("<![CDATA[")
+                .and_then(|inner| inner.strip_suffix("]]>"))
+                .unwrap_or(raw);
+            html_escape::decode_html_entities(raw.trim()).into_owned()
+        })
+        .filter(|val| !val.is_empty())
+        .collect()
+}
+
+fn value(xml: &str, tag: &str) -> Option<String> {
+    values(xml, tag).into_iter().next()
+}
+
+/// Genres, styles & moods are all treated as tags.
+fn tags(xml: &str) -> Vec<Tag> {
+    ["genre", "style", "mood"]
+        .iter()
+        .flat_map(|tag| values(xml, tag))
+        .map(|name| Tag { name, count: None, url: None })
+        .collect()
+}
+
+/// Kodi writes dates as YYYY-MM-DD but users often only fill in the year.
+fn date(xml: &str, tag: &str) -> Option<NaiveDate> {
+    let val = value(xml, tag)?;
+    NaiveDate::parse_from_str(&val, "%Y-%m-%d")
+        .ok()
+        .or_else(|| val.parse::<i32>().ok().and_then(|year| NaiveDate::from_ymd_opt(year, 1, 1)))
+}
+
+fn wiki(content: Option<String>) -> Option<Wiki> {
+    content.map(|content| Wiki {
+        content,
+        url: None,
+        attribution: ATTRIBUTION.to_owned()
+    })
+}
+
+fn read(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+    match std::fs::read_to_string(path) {
+        Ok(xml) => Some(xml),
+        Err(e) => {
+            println!("[Local] Could not read {:?}: {:?}", path, e);
+            None
+        }
+    }
+}
+
+/// Read an album.nfo file into the given AlbumMeta. The album name & artist are
+/// left untouched as they're used to look the result back up from the cache.
+/// Returns false if there is no readable NFO file at the given path.
+pub fn read_album_nfo(path: &Path, meta: &mut AlbumMeta) -> bool {
+    if let Some(xml) = read(path) {
+        // Older Kodi versions used the release MBID as the album ID.
+        meta.mbid = value(&xml, "musicbrainzalbumid").or_else(|| value(&xml, "musicbrainzreleaseid"));
+        meta.release_group_mbid = value(&xml, "musicbrainzreleasegroupid");
+        meta.release_type = value(&xml, "releasetype").and_then(|val| ReleaseType::from_tag_values(&[val]));
+        meta.tags = tags(&xml);
+        meta.wiki = wiki(value(&xml, "review"));
+        return true;
+    }
+    false
+}
+
+/// Read an artist.nfo file into the given ArtistMeta. Returns false if there is no
+/// readable NFO file at the given path.
+pub fn read_artist_nfo(path: &Path, meta: &mut ArtistMeta) -> bool {
+    if let Some(xml) = read(path) {
+        meta.mbid = value(&xml, "musicbrainzartistid");
+        meta.tags = tags(&xml);
+        meta.bio = wiki(value(&xml, "biography"));
+        meta.artist_type = match value(&xml, "type").map(|val| val.to_lowercase()).as_deref() {
+            Some("person") => ArtistType::Person,
+            Some("group") => ArtistType::Group,
+            Some("orchestra") => ArtistType::Orchestra,
+            Some("choir") => ArtistType::Choir,
+            Some("character") => ArtistType::Character,
+            _ => ArtistType::Other
+        };
+        meta.gender = value(&xml, "gender").map(|val| val.to_lowercase());
+        // Persons are born & die. Groups are formed & disbanded.
+        meta.begin_date = date(&xml, "born").or_else(|| date(&xml, "formed"));
+        meta.end_date = date(&xml, "died").or_else(|| date(&xml, "disbanded"));
+        return true;
+    }
+    false
+}
@@ -1,16 +1,26 @@
 mod base;
 mod chain;
+pub mod http;
 pub mod models;
+pub mod external;
 pub mod lastfm;
+pub mod local;
 pub mod musicbrainz;
+pub mod wikipedia;
 
 pub use chain::{
     MetadataChain,
-    get_provider_with_priority
+    ProviderResult,
+    get_provider_with_priority,
+    get_provider_order,
+    get_provider_name,
+    get_field_preferences,
+    set_field_preference,
+    PREFERABLE_FIELDS
 };
 pub use base::{MetadataProvider, Metadata, utils};
 
 pub mod prelude {
-    pub use super::base::{MetadataProvider, sleep_after_request};
+    pub use super::base::MetadataProvider;
     pub use super::models::{Tagged, HasImage, Merge};
 }
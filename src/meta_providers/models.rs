@@ -1,7 +1,12 @@
+use std::collections::BTreeMap;
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use musicbrainz_rs::entity::artist::ArtistType;
 
+use crate::common::ReleaseType;
+
+use super::get_provider_name;
+
 // Common building blocks that can be shared between different providers
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Tag {
@@ -30,6 +35,13 @@ pub trait Merge {
             this
         }
     }
+
+    /// Convenience provided function: add provenance of fields we don't know the source of yet.
+    fn merge_sources(this: &mut BTreeMap<String, FieldSource>, that: BTreeMap<String, FieldSource>) {
+        for (field, source) in that.into_iter() {
+            this.entry(field).or_insert(source);
+        }
+    }
 }
 
 pub trait HasImage {
@@ -54,6 +66,36 @@ pub struct ImageMeta {
     pub url: String
 }
 
+/// A piece of album artwork beyond the front cover, such as back covers or booklet pages.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArtworkMeta {
+    // As named by the provider, e.g. "Front", "Back", "Booklet"
+    pub types: Vec<String>,
+    // Full-resolution image
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>
+}
+
+/// Where a field of an AlbumMeta or ArtistMeta came from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FieldSource {
+    // Keys of the providers that filled this field. List-type fields such as tags
+    // may be filled by several.
+    pub providers: Vec<String>,
+    // UNIX timestamp
+    pub fetched: i64
+}
+
+impl FieldSource {
+    /// Short attribution for display next to the field, such as "from Wikipedia".
+    pub fn describe(&self) -> String {
+        let names: Vec<String> = self.providers.iter().map(|key| get_provider_name(key)).collect();
+        // TODO: translatable
+        format!("from {}", names.join(", "))
+    }
+}
+
 // Album
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[non_exhaustive]
@@ -76,10 +118,30 @@ pub struct AlbumMeta {
     pub mbid: Option<String>,
     pub tags: Vec<Tag>,
     pub image: Vec<ImageMeta>,
+    // For browsing only. Album arts are downloaded from the image field above.
+    #[serde(default)]
+    pub artwork: Vec<ArtworkMeta>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub wiki: Option<Wiki>
+    pub wiki: Option<Wiki>,
+    // Release group type, for albums whose tags do not specify one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_type: Option<ReleaseType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_group_mbid: Option<String>,
+    // Wikidata item ID (e.g. Q42) & Wikipedia article URL, for use by the Wikipedia provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wikidata_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wikipedia_url: Option<String>,
+    // Confidence (0 to 1) of the match when the MBID was found by searching instead
+    // of being read from tags. None for exact matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_score: Option<f32>,
+    // Provenance of each field, keyed by field name. Filled in by MetadataChain.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, FieldSource>
 }
 
 impl AlbumMeta {
@@ -99,20 +161,42 @@ impl AlbumMeta {
             artist: None,
             tags: Vec::with_capacity(0),
             image: Vec::with_capacity(0),
+            artwork: Vec::with_capacity(0),
             url: None,
-            wiki: None
+            wiki: None,
+            release_type: None,
+            release_group_mbid: None,
+            wikidata_id: None,
+            wikipedia_url: None,
+            match_score: None,
+            sources: BTreeMap::new()
         }
     }
+
+    /// Where the given field came from, if known.
+    pub fn source_of(&self, field: &str) -> Option<&FieldSource> {
+        self.sources.get(field)
+    }
 }
 
 impl Merge for AlbumMeta {
-    fn merge(mut self, AlbumMeta { mbid, artist, mut tags, mut image, url, wiki, .. }: Self) -> Self {
+    fn merge(
+        mut self,
+        AlbumMeta { mbid, artist, mut tags, mut image, mut artwork, url, wiki, release_type, release_group_mbid, wikidata_id, wikipedia_url, match_score, sources, .. }: Self
+    ) -> Self {
         self.tags.append(&mut tags);
         self.image.append(&mut image);
+        self.artwork.append(&mut artwork);
         self.mbid = Self::merge_option(self.mbid, mbid);
         self.artist = Self::merge_option(self.artist, artist);
         self.url = Self::merge_option(self.url, url);
         self.wiki = Self::merge_option(self.wiki, wiki);
+        self.release_type = Self::merge_option(self.release_type, release_type);
+        self.release_group_mbid = Self::merge_option(self.release_group_mbid, release_group_mbid);
+        self.wikidata_id = Self::merge_option(self.wikidata_id, wikidata_id);
+        self.wikipedia_url = Self::merge_option(self.wikipedia_url, wikipedia_url);
+        self.match_score = Self::merge_option(self.match_score, match_score);
+        Self::merge_sources(&mut self.sources, sources);
         self
     }
 }
@@ -129,6 +213,18 @@ impl HasImage for AlbumMeta {
     }
 }
 
+/// A release group from an artist's full discography. Release groups bundle all
+/// editions of the same album, so they're what we compare local albums against.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReleaseGroupMeta {
+    pub mbid: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_type: Option<ReleaseType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_release_date: Option<NaiveDate>
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[non_exhaustive]
 pub struct ArtistMeta {
@@ -154,7 +250,17 @@ pub struct ArtistMeta {
     pub end_date: Option<NaiveDate>,
     // Two-letter country code, such as US, AU, UK, JP, etc.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub country: Option<String>
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wikidata_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wikipedia_url: Option<String>,
+    // See AlbumMeta::match_score
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_score: Option<f32>,
+    // See AlbumMeta::sources
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, FieldSource>
 }
 
 impl ArtistMeta {
@@ -180,9 +286,18 @@ impl ArtistMeta {
             gender: None,
             begin_date: None,
             end_date: None,
-            country: None
+            country: None,
+            wikidata_id: None,
+            wikipedia_url: None,
+            match_score: None,
+            sources: BTreeMap::new()
         }
     }
+
+    /// Where the given field came from, if known.
+    pub fn source_of(&self, field: &str) -> Option<&FieldSource> {
+        self.sources.get(field)
+    }
 }
 
 impl Merge for ArtistMeta {
@@ -203,6 +318,10 @@ impl Merge for ArtistMeta {
             begin_date,
             end_date,
             country,
+            wikidata_id,
+            wikipedia_url,
+            match_score,
+            sources,
             ..
         }: Self
     ) -> Self {
@@ -220,6 +339,10 @@ impl Merge for ArtistMeta {
         self.begin_date = Self::merge_option(self.begin_date, begin_date);
         self.end_date = Self::merge_option(self.end_date, end_date);
         self.country = Self::merge_option(self.country, country);
+        self.wikidata_id = Self::merge_option(self.wikidata_id, wikidata_id);
+        self.wikipedia_url = Self::merge_option(self.wikipedia_url, wikipedia_url);
+        self.match_score = Self::merge_option(self.match_score, match_score);
+        Self::merge_sources(&mut self.sources, sources);
         self
     }
 }
@@ -235,3 +358,22 @@ impl HasImage for ArtistMeta {
         &self.image
     }
 }
+
+/// A possible match for a local album or artist found by a provider, offered to the
+/// user when automatic matching picked the wrong one.
+#[derive(Debug, Clone)]
+pub struct MatchCandidate {
+    // Key of the provider that found this candidate
+    pub provider: String,
+    pub title: String,
+    pub artist: Option<String>,
+    // Short facts to tell similar candidates apart, such as release year, country
+    // or track count
+    pub details: Vec<String>,
+    pub thumbnail: Option<String>,
+    // Confidence as computed by the provider, if it scores its results
+    pub score: Option<f32>,
+    // Fields to override in the key given to providers when this candidate is picked,
+    // usually just the MBID
+    pub pin: bson::Document
+}
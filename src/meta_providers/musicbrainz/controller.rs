@@ -7,20 +7,106 @@ use musicbrainz_rs::{
     prelude::*,
     entity::{
         artist::*,
-        release::*
+        release::*,
+        release_group::*
     }
 };
 
 use crate::utils::meta_provider_settings;
 
 use super::{super::{
-    models, prelude::*, MetadataProvider
-}, PROVIDER_KEY};
+    http, models, prelude::*, MetadataProvider
+}, coverart::fetch_release_artwork, matching, models::{artist_candidate, release_candidate}, PROVIDER_KEY};
+
+// Maximum page size allowed by the MusicBrainz browse API
+const BROWSE_LIMIT: u8 = 100;
+// musicbrainz_rs makes its own requests, so we can only throttle them beforehand.
+const MB_HOST: &str = "musicbrainz.org";
+// How many search results to offer when the user picks a match manually
+const MAX_CANDIDATES: usize = 10;
 
 pub struct MusicBrainzWrapper {
     priority: RwLock<u32>
 }
 
+impl MusicBrainzWrapper {
+    /// Fill in front cover URLs & the artwork list of a release from the Cover Art Archive.
+    fn add_cover_art(&self, mut meta: models::AlbumMeta) -> models::AlbumMeta {
+        if let Some(mbid) = meta.mbid.as_ref() {
+            if let Some((front, artwork)) = fetch_release_artwork(mbid) {
+                if meta_provider_settings(PROVIDER_KEY).boolean("download-album-art") {
+                    meta.image = front;
+                }
+                meta.artwork = artwork;
+            }
+        }
+        meta
+    }
+
+    /// Search releases by title & artist, then score them from best to worst match.
+    fn search_releases(&self, clues: &matching::AlbumClues) -> Option<Vec<(f32, Release)>> {
+        println!("[MusicBrainz] Searching release with title = {} and artist = {}", clues.title, clues.artist);
+        http::throttle(MB_HOST);
+        let res = Release::search(
+            ReleaseSearchQuery::query_builder()
+                .release(clues.title)
+                .artist(clues.artist)
+                .build()
+        )
+            .with_artist_credits()
+            .execute();
+        match res {
+            Ok(found) => Some(matching::rank_candidates(found.entities, |release, rank, n_results| {
+                matching::score_release(clues, release, rank, n_results)
+            })),
+            Err(e) => {
+                println!("[MusicBrainz] Could not search releases: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Search artists by name, then score them from best to worst match.
+    fn search_artists(&self, name: &str) -> Option<Vec<(f32, Artist)>> {
+        println!("[MusicBrainz] Searching artist with name = {}", name);
+        http::throttle(MB_HOST);
+        let res = Artist::search(
+            ArtistSearchQuery::query_builder()
+                .artist(name)
+                .build()
+        )
+            .with_url_relations()
+            .execute();
+        match res {
+            Ok(found) => Some(matching::rank_candidates(found.entities, |artist, rank, n_results| {
+                matching::score_artist(name, artist, rank, n_results)
+            })),
+            Err(e) => {
+                println!("[MusicBrainz] Could not search artists: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Take the best-scoring search result, unless it falls below the match-threshold
+    /// setting. Candidates must be sorted from best to worst.
+    fn pick_best<T, F>(&self, candidates: Vec<(f32, T)>, describe: F) -> Option<(f32, T)>
+    where
+        F: Fn(&T) -> &str
+    {
+        let threshold = meta_provider_settings(PROVIDER_KEY).double("match-threshold") as f32;
+        for (score, candidate) in candidates.iter().take(3) {
+            println!("[MusicBrainz] Candidate {:?} scored {:.2}", describe(candidate), score);
+        }
+        let (score, best) = candidates.into_iter().next()?;
+        if score < threshold {
+            println!("[MusicBrainz] Best candidate {:?} scored {:.2}, below threshold of {:.2}", describe(&best), score, threshold);
+            return None;
+        }
+        Some((score, best))
+    }
+}
+
 impl MetadataProvider for MusicBrainzWrapper {
     fn new(prio: u32) -> Self {
         Self {
@@ -51,12 +137,15 @@ impl MetadataProvider for MusicBrainzWrapper {
         if meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
             if let Some(mbid) = key.get("mbid") {
                 println!("[MusicBrainz] Fetching release by MBID: {}", &mbid);
+                http::throttle(MB_HOST);
                 let res = Release::fetch()
                     .id(mbid.as_str().unwrap())
                     .with_artist_credits()
+                    .with_release_groups()
+                    .with_url_relations()
                     .execute();
                 if let Ok(release) = res {
-                    let new: models::AlbumMeta = release.into();
+                    let new = self.add_cover_art(release.into());
                     println!("{:?}", &new);
                     // If there is existing data, merge new data to it
                     if let Some(old) = existing {
@@ -70,22 +159,11 @@ impl MetadataProvider for MusicBrainzWrapper {
                 }
             }
             // Else there must be an artist tag before we can search reliably
-            else if let (Some(title), Some(artist)) = (key.get("name"), key.get("artist")) {
-                // Ensure linkages match those on MusicBrainz.
-                // TODO: use multiple ORed artist clauses instead.
-                println!("[MusicBrainz] Searching release with title = {title} and artist = {artist}");
-                let res = Release::search(
-                    ReleaseSearchQuery::query_builder()
-                        .release(title.as_str().unwrap())
-                        .artist(artist.as_str().unwrap())
-                        .build()
-                )
-                    .with_artist_credits()
-                    .execute();
-
-                if let Ok(found) = res {
-                    if let Some(first) = found.entities.into_iter().nth(0) {
-                        let new: models::AlbumMeta = first.into();
+            else if let Some(clues) = matching::AlbumClues::from_key(&key) {
+                if let Some(candidates) = self.search_releases(&clues) {
+                    if let Some((score, best)) = self.pick_best(candidates, |release| &release.title) {
+                        let mut new: models::AlbumMeta = self.add_cover_art(best.into());
+                        new.match_score = Some(score);
                         // If there is existing data, merge new data to it
                         if let Some(old) = existing {
                             return Some(old.merge(new));
@@ -93,12 +171,11 @@ impl MetadataProvider for MusicBrainzWrapper {
                         return Some(new);
                     }
                     else {
-                        println!("[MusicBrainz] No release found for artist & album title");
+                        println!("[MusicBrainz] No confident release match for artist & album title");
                         return existing;
                     }
                 }
                 else {
-                    println!("[MusicBrainz] Could not fetch album metadata: {:?}", res.err());
                     return existing;
                 }
             }
@@ -124,6 +201,7 @@ impl MetadataProvider for MusicBrainzWrapper {
         if meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
             if let Some(mbid) = key.get("mbid") {
                 println!("[MusicBrainz] Fetching artist by MBID: {}", &mbid);
+                http::throttle(MB_HOST);
                 let res = Artist::fetch()
                     .id(mbid.as_str().unwrap())
                     .with_url_relations()
@@ -144,19 +222,11 @@ impl MetadataProvider for MusicBrainzWrapper {
             }
             // If MBID is not available we'll need to search solely by artist name.
             // TODO: add some more clues, such as a song or album name.
-            else if let Some(name) = key.get("name") {
-                println!("[MusicBrainz] Fetching artist with name = {}", &name);
-                let res = Artist::search(
-                    ArtistSearchQuery::query_builder()
-                        .artist(name.as_str().unwrap())
-                        .build()
-                )
-                    .with_url_relations()
-                    .execute();
-
-                if let Ok(found) = res {
-                    if let Some(first) = found.entities.into_iter().nth(0) {
-                        let new: models::ArtistMeta = first.into();
+            else if let Ok(name) = key.get_str("name") {
+                if let Some(candidates) = self.search_artists(name) {
+                    if let Some((score, best)) = self.pick_best(candidates, |artist| &artist.name) {
+                        let mut new: models::ArtistMeta = best.into();
+                        new.match_score = Some(score);
                         println!("{:?}", &new);
                         // If there is existing data, merge new data to it
                         if let Some(old) = existing {
@@ -165,12 +235,11 @@ impl MetadataProvider for MusicBrainzWrapper {
                         return Some(new);
                     }
                     else {
-                        println!("[MusicBrainz] No artist metadata found for {name}");
+                        println!("[MusicBrainz] No confident artist match for {name}");
                         return existing;
                     }
                 }
                 else {
-                    println!("[MusicBrainz] Could not fetch artist metadata: {:?}", res.err());
                     return existing;
                 }
             }
@@ -183,4 +252,66 @@ impl MetadataProvider for MusicBrainzWrapper {
             return existing;
         }
     }
+
+    /// Browse all release groups credited to an artist, one page at a time.
+    fn get_artist_discography(&self, mbid: &str) -> Option<Vec<models::ReleaseGroupMeta>> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return None;
+        }
+        println!("[MusicBrainz] Browsing release groups of artist {mbid}");
+        let mut res: Vec<models::ReleaseGroupMeta> = Vec::new();
+        loop {
+            http::throttle(MB_HOST);
+            let page = ReleaseGroup::browse()
+                .by_artist(mbid)
+                .limit(BROWSE_LIMIT)
+                .offset(res.len() as u16)
+                .execute();
+            match page {
+                Ok(page) => {
+                    let n_fetched = page.entities.len();
+                    res.extend(page.entities.into_iter().map(models::ReleaseGroupMeta::from));
+                    if n_fetched == 0 || res.len() >= page.count as usize {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    println!("[MusicBrainz] Could not browse release groups: {:?}", e);
+                    // A partial discography would make albums look missing when they aren't.
+                    return None;
+                }
+            }
+        }
+        Some(res)
+    }
+
+    fn search_album_candidates(&self, key: bson::Document) -> Vec<models::MatchCandidate> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return Vec::new();
+        }
+        let Some(clues) = matching::AlbumClues::from_key(&key) else {
+            return Vec::new();
+        };
+        self.search_releases(&clues)
+            .unwrap_or_default()
+            .into_iter()
+            .take(MAX_CANDIDATES)
+            .map(|(score, release)| release_candidate(score, release))
+            .collect()
+    }
+
+    fn search_artist_candidates(&self, key: bson::Document) -> Vec<models::MatchCandidate> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return Vec::new();
+        }
+        let Ok(name) = key.get_str("name") else {
+            return Vec::new();
+        };
+        self.search_artists(name)
+            .unwrap_or_default()
+            .into_iter()
+            .take(MAX_CANDIDATES)
+            .map(|(score, artist)| artist_candidate(score, artist))
+            .collect()
+    }
 }
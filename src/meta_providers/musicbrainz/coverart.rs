@@ -0,0 +1,107 @@
+// Cover Art Archive client. The CAA hosts artwork for MusicBrainz releases and
+// release groups, so it can only be queried once we have their MBIDs.
+use gtk::prelude::*;
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+
+use crate::utils::meta_provider_settings;
+
+use super::{super::{http, models::{ArtworkMeta, ImageMeta, ImageSize}}, PROVIDER_KEY};
+
+#[derive(Deserialize, Debug)]
+struct CaaThumbnails {
+    #[serde(rename = "250")]
+    small: Option<String>,
+    #[serde(rename = "500")]
+    large: Option<String>,
+    #[serde(rename = "1200")]
+    huge: Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+struct CaaImage {
+    types: Vec<String>,
+    front: bool,
+    image: String,
+    thumbnails: CaaThumbnails
+}
+
+#[derive(Deserialize, Debug)]
+struct CaaResponse {
+    images: Vec<CaaImage>
+}
+
+/// Base URL of the Cover Art Archive, without trailing slash. Configurable so
+/// that mirrors or a local stub server can be used instead.
+fn endpoint() -> String {
+    meta_provider_settings(PROVIDER_KEY)
+        .string("coverart-endpoint")
+        .trim_end_matches('/')
+        .to_owned()
+}
+
+/// URL of the 250px front cover thumbnail of a release group.
+pub fn release_group_front_url(mbid: &str) -> String {
+    format!("{}/release-group/{}/front-250", endpoint(), mbid)
+}
+
+/// URL of the 250px front cover thumbnail of a release.
+pub fn release_front_url(mbid: &str) -> String {
+    format!("{}/release/{}/front-250", endpoint(), mbid)
+}
+
+/// Get all artwork of a release. Returns the front cover in all available sizes
+/// (for get_best_image) and every image including the front cover (for browsing).
+pub fn fetch_release_artwork(mbid: &str) -> Option<(Vec<ImageMeta>, Vec<ArtworkMeta>)> {
+    let url = match Url::parse(&format!("{}/release/{}", endpoint(), mbid)) {
+        Ok(url) => url,
+        Err(e) => {
+            println!("[MusicBrainz] Invalid Cover Art Archive URL: {:?}", e);
+            return None;
+        }
+    };
+    println!("[MusicBrainz] Fetching artwork list from {url}");
+    let caa: CaaResponse = match http::get(url, true) {
+        // The CAA returns 404 for releases without any artwork.
+        Ok(res) if res.status() == StatusCode::NOT_FOUND => {
+            return Some((Vec::with_capacity(0), Vec::with_capacity(0)));
+        }
+        Ok(res) => match res.json() {
+            Ok(caa) => caa,
+            Err(e) => {
+                println!("[MusicBrainz] Could not parse Cover Art Archive response: {:?}", e);
+                return None;
+            }
+        },
+        Err(e) => {
+            println!("[MusicBrainz] Could not reach the Cover Art Archive: {:?}", e);
+            return None;
+        }
+    };
+
+    let mut front: Vec<ImageMeta> = Vec::new();
+    let mut artwork: Vec<ArtworkMeta> = Vec::with_capacity(caa.images.len());
+    for image in caa.images.into_iter() {
+        // Only use the first front cover. get_best_image tries the first-added
+        // among the largest ones first, so add the 1200px thumbnail before the
+        // original to avoid downloading huge scans unless needed.
+        if image.front && front.is_empty() {
+            if let Some(url) = image.thumbnails.huge.as_ref() {
+                front.push(ImageMeta { size: ImageSize::Mega, url: url.clone() });
+            }
+            if let Some(url) = image.thumbnails.large.as_ref() {
+                front.push(ImageMeta { size: ImageSize::Mega, url: url.clone() });
+            }
+            front.push(ImageMeta { size: ImageSize::Mega, url: image.image.clone() });
+            if let Some(url) = image.thumbnails.small.as_ref() {
+                front.push(ImageMeta { size: ImageSize::ExtraLarge, url: url.clone() });
+            }
+        }
+        artwork.push(ArtworkMeta {
+            types: image.types,
+            url: image.image,
+            thumbnail: image.thumbnails.small.or(image.thumbnails.large)
+        });
+    }
+    Some((front, artwork))
+}
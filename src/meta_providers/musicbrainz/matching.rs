@@ -0,0 +1,255 @@
+// Confidence scoring of MusicBrainz search results against what we know locally.
+// Searches are fuzzy & return namesakes, reissues and unrelated releases with similar
+// titles, so the first result is often not the one we want.
+use chrono::Datelike;
+use musicbrainz_rs::entity::{artist::Artist, release::Release};
+
+use crate::common::parse_mb_artist_tag;
+
+use super::models::join_artist_credits;
+
+// Relative weights of each clue. Clues we don't have locally (such as the track count
+// of an album whose songs haven't been counted yet) are left out of the average.
+const TITLE_WEIGHT: f32 = 0.4;
+const ARTIST_WEIGHT: f32 = 0.3;
+const TRACK_COUNT_WEIGHT: f32 = 0.15;
+const YEAR_WEIGHT: f32 = 0.05;
+const RANK_WEIGHT: f32 = 0.1;
+const ARTIST_NAME_WEIGHT: f32 = 0.8;
+// Names at least this similar are considered the same artist when comparing credits.
+const SAME_NAME_THRESHOLD: f32 = 0.85;
+
+/// What we know about a local album, taken from its key & hints.
+pub struct AlbumClues<'a> {
+    pub title: &'a str,
+    pub artist: &'a str,
+    pub track_count: Option<u32>,
+    pub year: Option<i32>
+}
+
+impl<'a> AlbumClues<'a> {
+    pub fn from_key(key: &'a bson::Document) -> Option<Self> {
+        Some(Self {
+            title: key.get_str("name").ok()?,
+            artist: key.get_str("artist").ok()?,
+            track_count: key.get_i32("track_count").ok().map(|count| count as u32),
+            year: key.get_i32("year").ok()
+        })
+    }
+}
+
+/// Lowercase, turn "&" into "and", drop punctuation & collapse whitespace.
+fn normalise(input: &str) -> String {
+    input
+        .to_lowercase()
+        .replace('&', " and ")
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Remove trailing bracketed parts such as "(Remastered)" or "[Deluxe Edition]", which
+/// local tags & MusicBrainz titles often disagree on.
+fn strip_qualifiers(input: &str) -> &str {
+    let mut res = input.trim_end();
+    while let Some(pos) = res.rfind(['(', '[']) {
+        if pos == 0 || !(res.ends_with(')') || res.ends_with(']')) {
+            break;
+        }
+        res = res[..pos].trim_end();
+    }
+    res
+}
+
+/// Normalised Levenshtein similarity between 0 (nothing in common) and 1 (identical).
+fn levenshtein_similarity(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr: Vec<usize> = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    1.0 - prev[b.len()] as f32 / longest as f32
+}
+
+/// Similarity of two titles or names, ignoring case, punctuation & bracketed qualifiers.
+pub fn similarity(a: &str, b: &str) -> f32 {
+    let full = levenshtein_similarity(&normalise(a), &normalise(b));
+    let stripped = levenshtein_similarity(
+        &normalise(strip_qualifiers(a)),
+        &normalise(strip_qualifiers(b))
+    );
+    full.max(stripped)
+}
+
+/// Score for a search result's position. Results come sorted by MusicBrainz's own
+/// relevance score, so use their rank as a proxy for it.
+fn rank_score(rank: usize, n_results: usize) -> f32 {
+    if n_results <= 1 {
+        1.0
+    }
+    else {
+        1.0 - rank as f32 / n_results as f32
+    }
+}
+
+/// Fraction of local artists credited on the release & vice versa.
+fn artist_overlap(local: &str, release: &Release) -> f32 {
+    let Some(credits) = release.artist_credit.as_ref() else {
+        return 0.0;
+    };
+    let mut local_names: Vec<&str> = parse_mb_artist_tag(local);
+    if local_names.is_empty() {
+        local_names.push(local);
+    }
+    let credited: Vec<&str> = credits.iter().map(|credit| credit.name.as_str()).collect();
+    if credited.is_empty() {
+        return 0.0;
+    }
+    let joined = join_artist_credits(Some(credits)).unwrap_or_default();
+    let found = local_names
+        .iter()
+        .filter(|name| credited.iter().any(|other| similarity(name, other) >= SAME_NAME_THRESHOLD))
+        .count();
+    let overlap = (2 * found) as f32 / (local_names.len() + credited.len()) as f32;
+    // Whole-credit comparison covers tags that our delimiter rules split differently.
+    overlap.max(similarity(local, &joined))
+}
+
+fn track_count_score(local: u32, release: &Release) -> Option<f32> {
+    let remote: u32 = release.media.as_ref()?.iter().map(|medium| medium.track_count).sum();
+    if remote == 0 {
+        return None;
+    }
+    let diff = local.abs_diff(remote);
+    Some(match diff {
+        0 => 1.0,
+        // Bonus tracks, hidden tracks & missing songs
+        1..=2 => 0.5,
+        _ => 0.0
+    })
+}
+
+fn year_score(local: i32, release: &Release) -> Option<f32> {
+    let remote = release.date.as_ref()?.year();
+    // Reissues & regional releases often come out a while after the original.
+    Some(match (local - remote).abs() {
+        0 => 1.0,
+        1 => 0.5,
+        _ => 0.0
+    })
+}
+
+/// Score a release found by searching, between 0 and 1.
+pub fn score_release(clues: &AlbumClues, release: &Release, rank: usize, n_results: usize) -> f32 {
+    let mut total = TITLE_WEIGHT * similarity(clues.title, &release.title)
+        + ARTIST_WEIGHT * artist_overlap(clues.artist, release)
+        + RANK_WEIGHT * rank_score(rank, n_results);
+    let mut weights = TITLE_WEIGHT + ARTIST_WEIGHT + RANK_WEIGHT;
+    if let Some(score) = clues.track_count.and_then(|count| track_count_score(count, release)) {
+        total += TRACK_COUNT_WEIGHT * score;
+        weights += TRACK_COUNT_WEIGHT;
+    }
+    if let Some(score) = clues.year.and_then(|year| year_score(year, release)) {
+        total += YEAR_WEIGHT * score;
+        weights += YEAR_WEIGHT;
+    }
+    total / weights
+}
+
+/// Score an artist found by searching, between 0 and 1.
+pub fn score_artist(name: &str, artist: &Artist, rank: usize, n_results: usize) -> f32 {
+    let name_score = similarity(name, &artist.name).max(similarity(name, &artist.sort_name));
+    (ARTIST_NAME_WEIGHT * name_score + RANK_WEIGHT * rank_score(rank, n_results))
+        / (ARTIST_NAME_WEIGHT + RANK_WEIGHT)
+}
+
+/// Score all results & sort them from best to worst match.
+pub fn rank_candidates<T, F>(results: Vec<T>, score: F) -> Vec<(f32, T)>
+where
+    F: Fn(&T, usize, usize) -> f32
+{
+    let n_results = results.len();
+    let mut scored: Vec<(f32, T)> = results
+        .into_iter()
+        .enumerate()
+        .map(|(rank, item)| (score(&item, rank, n_results), item))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(title: &str, artist: &str, date: &str, track_count: u32) -> Release {
+        serde_json::from_value(serde_json::json!({
+            "id": format!("{title}-{artist}-{date}"),
+            "title": title,
+            "date": date,
+            "artist-credit": [{
+                "name": artist,
+                "joinphrase": "",
+                "artist": {
+                    "id": artist,
+                    "name": artist,
+                    "sort-name": artist,
+                    "disambiguation": ""
+                }
+            }],
+            "media": [{"position": 1, "track-count": track_count}]
+        })).expect("Invalid test release")
+    }
+
+    #[test]
+    fn strip_qualifiers_removes_trailing_brackets() {
+        assert_eq!(strip_qualifiers("Abbey Road (Remastered) [Deluxe Edition]"), "Abbey Road");
+        assert_eq!(strip_qualifiers("Weezer (Blue Album)"), "Weezer");
+    }
+
+    #[test]
+    fn strip_qualifiers_keeps_titles_made_of_brackets() {
+        assert_eq!(strip_qualifiers("(What's the Story) Morning Glory?"), "(What's the Story) Morning Glory?");
+        assert_eq!(strip_qualifiers("(Untitled)"), "(Untitled)");
+        assert_eq!(strip_qualifiers("Live (Unfinished"), "Live (Unfinished");
+    }
+
+    #[test]
+    fn similarity_ignores_case_punctuation_and_qualifiers() {
+        assert_eq!(similarity("AC/DC", "ac dc"), 1.0);
+        assert_eq!(similarity("Simon & Garfunkel", "Simon and Garfunkel"), 1.0);
+        assert_eq!(similarity("OK Computer", "OK Computer (Remastered)"), 1.0);
+        assert!(similarity("OK Computer", "Kid A") < 0.5);
+    }
+
+    #[test]
+    fn score_release_prefers_the_right_artist_over_a_namesake() {
+        let clues = AlbumClues {title: "Greatest Hits", artist: "Queen", track_count: None, year: None};
+        let namesake = release("Greatest Hits", "Queens of the Stone Age", "2005-01-01", 17);
+        let right = release("Greatest Hits", "Queen", "1981-10-26", 17);
+        // MusicBrainz ranks the namesake first.
+        assert!(score_release(&clues, &right, 1, 2) > score_release(&clues, &namesake, 0, 2));
+    }
+
+    #[test]
+    fn score_release_prefers_the_original_over_a_reissue() {
+        let clues = AlbumClues {title: "OK Computer", artist: "Radiohead", track_count: Some(12), year: Some(1997)};
+        let reissue = release("OK Computer (Collector's Edition)", "Radiohead", "2009-03-24", 29);
+        let original = release("OK Computer", "Radiohead", "1997-05-21", 12);
+        assert!(score_release(&clues, &original, 1, 2) > score_release(&clues, &reissue, 0, 2));
+    }
+}
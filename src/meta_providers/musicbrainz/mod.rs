@@ -2,4 +2,7 @@ static PROVIDER_KEY: &str = "musicbrainz";
 
 mod controller;
 mod models;
+mod coverart;
+mod matching;
 pub use controller::MusicBrainzWrapper;
+pub use coverart::release_group_front_url;
@@ -1,16 +1,22 @@
-use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use chrono::{Datelike, NaiveDate};
 use gtk::prelude::SettingsExt;
 use musicbrainz_rs::{
     entity::{
-        artist::{Artist, ArtistType, Gender}, relations::RelationContent, release::Release, tag::Tag
+        artist::{Artist, ArtistType, Gender},
+        artist_credit::ArtistCredit,
+        relations::{Relation, RelationContent},
+        release::Release,
+        release_group::{ReleaseGroup, ReleaseGroupPrimaryType, ReleaseGroupSecondaryType},
+        tag::Tag
     }, prelude::*
 };
-use crate::{meta_providers::models::{ImageMeta, ImageSize}, utils::meta_provider_settings};
+use crate::{common::ReleaseType, meta_providers::models::{ImageMeta, ImageSize}, utils::meta_provider_settings};
 
 use super::{super::{
     models,
     prelude::*
-}, PROVIDER_KEY};
+}, coverart::release_front_url, PROVIDER_KEY};
 
 fn transform_wikimedia_url(url: &str) -> Option<String> {
     // MusicBrainz relations cannot contain direct links, so we'll have to extract one ourselves.
@@ -26,6 +32,46 @@ fn transform_wikimedia_url(url: &str) -> Option<String> {
     None
 }
 
+/// Get the Wikidata item ID & Wikipedia article URL listed in an entity's URL relations,
+/// for use by the Wikipedia provider further down the chain.
+fn wiki_links(relations: Option<&Vec<Relation>>) -> (Option<String>, Option<String>) {
+    let mut wikidata_id: Option<String> = None;
+    let mut wikipedia_url: Option<String> = None;
+    for relation in relations.into_iter().flatten() {
+        if let RelationContent::Url(url) = &relation.content {
+            match relation.relation_type.as_str() {
+                "wikidata" if wikidata_id.is_none() => {
+                    // Resource is in the form of https://www.wikidata.org/wiki/Q42
+                    wikidata_id = url.resource
+                        .rsplit('/')
+                        .next()
+                        .filter(|id| id.starts_with('Q'))
+                        .map(str::to_owned);
+                }
+                "wikipedia" if wikipedia_url.is_none() => {
+                    wikipedia_url = Some(url.resource.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+    (wikidata_id, wikipedia_url)
+}
+
+/// Join an artist credit back into a single tag-like string.
+pub fn join_artist_credits(credits: Option<&Vec<ArtistCredit>>) -> Option<String> {
+    let artists = credits?;
+    let mut content = String::new();
+    for artist in artists.iter() {
+        content.push_str(&artist.name);
+        // Spaces should already be included.
+        // Last artist should not have one.
+        if let Some(delim) = &artist.joinphrase {
+            content.push_str(delim);
+        }
+    }
+    Some(content)
+}
 
 impl From<Tag> for models::Tag {
     fn from(mbtag: Tag) -> Self {
@@ -47,25 +93,25 @@ pub fn mb_gender_to_str(g: Gender) -> Option<String> {
     }
 }
 
+fn mb_release_group_type(group: &ReleaseGroup) -> Option<ReleaseType> {
+    if group.secondary_types.contains(&ReleaseGroupSecondaryType::Compilation) {
+        return Some(ReleaseType::Compilation);
+    }
+    if group.secondary_types.contains(&ReleaseGroupSecondaryType::Live) {
+        return Some(ReleaseType::Live);
+    }
+    match group.primary_type.as_ref() {
+        Some(ReleaseGroupPrimaryType::Album) => Some(ReleaseType::Album),
+        Some(ReleaseGroupPrimaryType::Ep) => Some(ReleaseType::EP),
+        Some(ReleaseGroupPrimaryType::Single) => Some(ReleaseType::Single),
+        _ => None
+    }
+}
+
 impl From<Release> for models::AlbumMeta {
     fn from(rel: Release) -> Self {
         // TODO: Keep more of the data in Release.
-        let artist_tag: Option<String>;
-        if let Some(artists) = rel.artist_credit {
-            let mut content = String::new();
-            for artist in artists.iter() {
-                content.push_str(&artist.name);
-                // Spaces should already be included.
-                // Last artist should not have one.
-                if let Some(delim) = &artist.joinphrase {
-                    content.push_str(delim);
-                }
-            }
-            artist_tag = Some(content);
-        }
-        else {
-            artist_tag = None;
-        }
+        let artist_tag = join_artist_credits(rel.artist_credit.as_ref());
         let tags: Vec<models::Tag>;
         if let Some(mbtags) = rel.tags {
             tags = mbtags.into_iter().map(models::Tag::from).collect();
@@ -73,6 +119,7 @@ impl From<Release> for models::AlbumMeta {
         else {
             tags = Vec::new();
         }
+        let (wikidata_id, wikipedia_url) = wiki_links(rel.relations.as_ref());
 
         Self {
             name: rel.title,
@@ -80,8 +127,79 @@ impl From<Release> for models::AlbumMeta {
             mbid: Some(rel.id.clone()),
             tags,
             image: Vec::new(), // acquired separately
+            artwork: Vec::new(), // acquired separately
             url: Some(format!("https://musicbrainz.org/release/{}", rel.id)),
-            wiki: None // not provided
+            wiki: None, // not provided
+            release_type: rel.release_group.as_ref().and_then(mb_release_group_type),
+            release_group_mbid: rel.release_group.map(|group| group.id),
+            wikidata_id,
+            wikipedia_url,
+            match_score: None,
+            sources: BTreeMap::new()
+        }
+    }
+}
+
+/// Describe a release found by searching, for the user to pick from.
+pub fn release_candidate(score: f32, rel: Release) -> models::MatchCandidate {
+    let mut details: Vec<String> = Vec::new();
+    if let Some(date) = rel.date.as_ref() {
+        details.push(date.year().to_string());
+    }
+    if let Some(country) = rel.country.as_ref() {
+        details.push(country.clone());
+    }
+    if let Some(media) = rel.media.as_ref() {
+        let formats: Vec<&str> = media.iter().filter_map(|medium| medium.format.as_deref()).collect();
+        if !formats.is_empty() {
+            details.push(formats.join(" + "));
+        }
+        // TODO: translatable
+        details.push(format!("{} tracks", media.iter().map(|medium| medium.track_count).sum::<u32>()));
+    }
+    let artist = join_artist_credits(rel.artist_credit.as_ref());
+    models::MatchCandidate {
+        provider: PROVIDER_KEY.to_owned(),
+        thumbnail: Some(release_front_url(&rel.id)),
+        pin: bson::doc! {"mbid": rel.id.clone()},
+        title: rel.title,
+        artist,
+        details,
+        score: Some(score)
+    }
+}
+
+/// Describe an artist found by searching, for the user to pick from.
+pub fn artist_candidate(score: f32, artist: Artist) -> models::MatchCandidate {
+    let mut details: Vec<String> = Vec::new();
+    if let Some(artist_type) = artist.artist_type.as_ref() {
+        details.push(format!("{:?}", artist_type));
+    }
+    if let Some(country) = artist.country.as_ref() {
+        details.push(country.clone());
+    }
+    if let Some(begin) = artist.life_span.as_ref().and_then(|lifespan| lifespan.begin.as_ref()) {
+        details.push(begin.year().to_string());
+    }
+    models::MatchCandidate {
+        provider: PROVIDER_KEY.to_owned(),
+        // Avatars need another request per artist, so don't show any here.
+        thumbnail: None,
+        pin: bson::doc! {"mbid": artist.id.clone()},
+        title: artist.name,
+        artist: None,
+        details,
+        score: Some(score)
+    }
+}
+
+impl From<ReleaseGroup> for models::ReleaseGroupMeta {
+    fn from(group: ReleaseGroup) -> Self {
+        Self {
+            release_type: mb_release_group_type(&group),
+            mbid: group.id,
+            title: group.title,
+            first_release_date: group.first_release_date
         }
     }
 }
@@ -112,6 +230,7 @@ impl From<Artist> for models::ArtistMeta {
             begin_date = None;
             end_date = None;
         }
+        let (wikidata_id, wikipedia_url) = wiki_links(artist.relations.as_ref());
         // Currently we only support downloading images via the "image" relation type.
         let mut image: Vec<ImageMeta>;
         if meta_provider_settings(PROVIDER_KEY).boolean("download-artist-avatar") {
@@ -150,7 +269,11 @@ impl From<Artist> for models::ArtistMeta {
             gender: mb_gender_to_str(artist.gender.unwrap_or(Gender::NotApplicable)),
             begin_date,
             end_date,
-            country: artist.country
+            country: artist.country,
+            wikidata_id,
+            wikipedia_url,
+            match_score: None,
+            sources: BTreeMap::new()
         }
     }
 }
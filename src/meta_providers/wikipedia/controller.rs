@@ -0,0 +1,254 @@
+use gtk::{glib, prelude::*};
+extern crate bson;
+
+use std::sync::RwLock;
+
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+
+use crate::utils::meta_provider_settings;
+
+use super::{super::{
+    http, models, prelude::*, MetadataProvider
+}, models::*, PROVIDER_KEY};
+
+const WIKIDATA_API: &str = "https://www.wikidata.org/w/api.php";
+// Wikidata properties holding MusicBrainz IDs
+const WIKIDATA_MB_ARTIST: &str = "P434";
+const WIKIDATA_MB_RELEASE_GROUP: &str = "P436";
+const WIKIDATA_MB_RELEASE: &str = "P5813";
+// Article text is licensed under CC BY-SA, which requires attribution.
+const ATTRIBUTION: &str = "Text from Wikipedia, available under the Creative Commons Attribution-ShareAlike 4.0 License.";
+
+/// Get Wikipedia language codes to try, in order. Uses the configured language
+/// if any, else the user's locale, then falls back to English.
+fn languages() -> Vec<String> {
+    let mut res: Vec<String> = Vec::new();
+    let configured = meta_provider_settings(PROVIDER_KEY).string("language");
+    if !configured.trim().is_empty() {
+        res.push(configured.trim().to_lowercase());
+    }
+    else {
+        // Locale names are in the form of language_TERRITORY.codeset@modifier.
+        // Skip the C & POSIX pseudo-locales.
+        if let Some(lang) = glib::language_names()
+            .iter()
+            .map(|name| name.split(['_', '.', '@']).next().unwrap_or_default().to_lowercase())
+            .find(|lang| !lang.is_empty() && lang != "c" && lang != "posix")
+        {
+            res.push(lang);
+        }
+    }
+    if !res.iter().any(|lang| lang == "en") {
+        res.push("en".to_owned());
+    }
+    res
+}
+
+/// Split a Wikipedia article URL into its language code & title.
+fn parse_article_url(url: &str) -> Option<(String, String)> {
+    let parsed = Url::parse(url).ok()?;
+    let lang = parsed.host_str()?.strip_suffix(".wikipedia.org")?.to_owned();
+    let title = glib::Uri::unescape_string(parsed.path().strip_prefix("/wiki/")?, None::<&str>)?;
+    Some((lang, title.replace('_', " ")))
+}
+
+pub struct WikipediaWrapper {
+    priority: RwLock<u32>
+}
+
+impl WikipediaWrapper {
+    fn get_json<T: DeserializeOwned>(&self, url: Url) -> Option<T> {
+        match http::get(url, true) {
+            Ok(res) if res.status().is_success() => match res.json::<T>() {
+                Ok(parsed) => Some(parsed),
+                Err(e) => {
+                    println!("[Wikipedia] Could not parse response: {:?}", e);
+                    None
+                }
+            },
+            Ok(res) => {
+                println!("[Wikipedia] Request failed with status {}", res.status());
+                None
+            }
+            Err(e) => {
+                println!("[Wikipedia] Could not send request: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Find the Wikidata item holding the given MusicBrainz ID under the given property.
+    fn find_item(&self, property: &str, mbid: &str) -> Option<String> {
+        println!("[Wikipedia] Searching Wikidata for {property} = {mbid}");
+        let url = Url::parse_with_params(WIKIDATA_API, &[
+            ("action", "query"),
+            ("list", "search"),
+            ("format", "json"),
+            ("srlimit", "1"),
+            ("srsearch", &format!("haswbstatement:{property}={mbid}"))
+        ]).ok()?;
+        let resp: WikidataSearchResponse = self.get_json(url)?;
+        resp.query.search.into_iter().next().map(|item| item.title)
+    }
+
+    /// Get the item's links to Wikipedia articles in all languages.
+    fn get_sitelinks(&self, item: &str) -> Option<WikidataEntity> {
+        let url = Url::parse_with_params(WIKIDATA_API, &[
+            ("action", "wbgetentities"),
+            ("format", "json"),
+            ("props", "sitelinks"),
+            ("ids", item)
+        ]).ok()?;
+        let mut resp: WikidataEntitiesResponse = self.get_json(url)?;
+        resp.entities.remove(item)
+    }
+
+    fn get_summary(&self, lang: &str, title: &str) -> Option<models::Wiki> {
+        let mut url = Url::parse(&format!("https://{lang}.wikipedia.org/api/rest_v1/page/summary")).ok()?;
+        url.path_segments_mut().ok()?.push(&title.replace(' ', "_"));
+        println!("[Wikipedia] Fetching article summary from {url}");
+        let summary: WikipediaSummary = self.get_json(url)?;
+        if summary.page_type == "disambiguation" || summary.extract.trim().is_empty() {
+            println!("[Wikipedia] No usable intro in article {}", &summary.title);
+            return None;
+        }
+        Some(models::Wiki {
+            content: summary.extract,
+            url: Some(summary.content_urls.desktop.page),
+            attribution: ATTRIBUTION.to_owned()
+        })
+    }
+
+    /// Get the article intro in the first available preferred language. Articles
+    /// are located via the Wikidata item if known, else the linked article itself
+    /// (which is only used if it is in one of our languages).
+    fn get_intro(&self, item: Option<&str>, article_url: Option<&str>) -> Option<models::Wiki> {
+        let linked = article_url.and_then(parse_article_url);
+        let entity = item.and_then(|item| self.get_sitelinks(item));
+        for lang in languages().iter() {
+            let title = entity
+                .as_ref()
+                .and_then(|entity| entity.sitelinks.get(&format!("{lang}wiki")))
+                .map(|link| link.title.clone())
+                .or_else(|| linked
+                    .as_ref()
+                    .filter(|(linked_lang, _)| linked_lang == lang)
+                    .map(|(_, title)| title.clone())
+                );
+            if let Some(title) = title {
+                if let Some(wiki) = self.get_summary(lang, &title) {
+                    return Some(wiki);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl MetadataProvider for WikipediaWrapper {
+    fn new(prio: u32) -> Self {
+        Self {
+            priority: RwLock::new(prio)
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        PROVIDER_KEY
+    }
+
+    fn priority(&self) -> u32 {
+        *self.priority.read().expect("Poisoned RwLock")
+    }
+
+    fn set_priority(&self, prio: u32) {
+        let mut this_prio = self.priority.write().expect("Poisoned RwLock");
+        *this_prio = prio;
+    }
+
+    /// Articles are found through the Wikidata items & links that MusicBrainz provides.
+    fn builds_on_earlier(&self) -> bool {
+        true
+    }
+
+    /// Get the album's Wikipedia article intro as its wiki. Requires either a Wikidata
+    /// item or Wikipedia link from upstream providers, or a MusicBrainz ID to look
+    /// the item up with.
+    fn get_album_meta(
+        &self,
+        key: bson::Document,
+        existing: Option<models::AlbumMeta>
+    ) -> Option<models::AlbumMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        // Don't bother if an earlier provider already has a wiki for this album.
+        if existing.as_ref().is_some_and(|meta| meta.wiki.is_some()) {
+            return existing;
+        }
+        let mut item = existing.as_ref().and_then(|meta| meta.wikidata_id.clone());
+        let article_url = existing.as_ref().and_then(|meta| meta.wikipedia_url.clone());
+        if item.is_none() && article_url.is_none() {
+            // Wikidata items usually describe release groups rather than individual releases.
+            if let Some(group) = existing.as_ref().and_then(|meta| meta.release_group_mbid.as_ref()) {
+                item = self.find_item(WIKIDATA_MB_RELEASE_GROUP, group);
+            }
+            if item.is_none() {
+                if let Ok(mbid) = key.get_str("mbid") {
+                    item = self.find_item(WIKIDATA_MB_RELEASE, mbid);
+                }
+            }
+        }
+        if item.is_none() && article_url.is_none() {
+            println!("[Wikipedia] No Wikidata item or article found for album");
+            return existing;
+        }
+
+        if let Some(wiki) = self.get_intro(item.as_deref(), article_url.as_deref()) {
+            let mut new = models::AlbumMeta::from_key(&key);
+            new.wiki = Some(wiki);
+            new.wikidata_id = item;
+            if let Some(old) = existing {
+                return Some(old.merge(new));
+            }
+            return Some(new);
+        }
+        existing
+    }
+
+    /// Get the artist's Wikipedia article intro as their bio.
+    fn get_artist_meta(
+        &self,
+        key: bson::Document,
+        existing: Option<models::ArtistMeta>
+    ) -> Option<models::ArtistMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        if existing.as_ref().is_some_and(|meta| meta.bio.is_some()) {
+            return existing;
+        }
+        let mut item = existing.as_ref().and_then(|meta| meta.wikidata_id.clone());
+        let article_url = existing.as_ref().and_then(|meta| meta.wikipedia_url.clone());
+        if item.is_none() && article_url.is_none() {
+            if let Ok(mbid) = key.get_str("mbid") {
+                item = self.find_item(WIKIDATA_MB_ARTIST, mbid);
+            }
+        }
+        if item.is_none() && article_url.is_none() {
+            println!("[Wikipedia] No Wikidata item or article found for artist");
+            return existing;
+        }
+
+        if let Some(bio) = self.get_intro(item.as_deref(), article_url.as_deref()) {
+            let mut new = models::ArtistMeta::from_key(&key);
+            new.bio = Some(bio);
+            new.wikidata_id = item;
+            if let Some(old) = existing {
+                return Some(old.merge(new));
+            }
+            return Some(new);
+        }
+        existing
+    }
+}
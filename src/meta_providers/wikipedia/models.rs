@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+
+// Wikidata & Wikipedia JSON structs, for deserialising API responses only.
+// Widgets should use the standard structs defined in the supercrate's models.rs.
+
+#[derive(Deserialize, Debug)]
+pub struct WikidataSearchResult {
+    // The item ID, such as Q42
+    pub title: String
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WikidataSearchQuery {
+    pub search: Vec<WikidataSearchResult>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WikidataSearchResponse {
+    pub query: WikidataSearchQuery
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WikidataSitelink {
+    pub title: String
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WikidataEntity {
+    // Keyed by site ID, such as "enwiki" or "jawiki"
+    #[serde(default)]
+    pub sitelinks: HashMap<String, WikidataSitelink>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WikidataEntitiesResponse {
+    pub entities: HashMap<String, WikidataEntity>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WikipediaPageUrls {
+    pub page: String
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WikipediaContentUrls {
+    pub desktop: WikipediaPageUrls
+}
+
+/// Response of the REST page summary endpoint. The extract is the plain-text
+/// intro of the article.
+#[derive(Deserialize, Debug)]
+pub struct WikipediaSummary {
+    // "standard" for normal articles, "disambiguation" for disambiguation pages, etc.
+    #[serde(rename = "type")]
+    pub page_type: String,
+    pub title: String,
+    #[serde(default)]
+    pub extract: String,
+    pub content_urls: WikipediaContentUrls
+}
@@ -0,0 +1,236 @@
+use std::{
+    cell::{Cell, RefCell},
+    time::{Duration, Instant}
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use gtk::glib;
+
+use crate::common::SmartPlaylist;
+
+// Don't let candidate pools pile up while following a long chain of artists or genres.
+// The oldest one is evicted to make room for a new one.
+const MAX_POOLS: usize = 8;
+// Pools are fetched again after this long, to pick up library changes.
+const POOL_LIFETIME: Duration = Duration::from_secs(30 * 60);
+// MPD does not report failed additions or searches back to us, so stop waiting for
+// them after this long & try again.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+// Maximum number of similar artists to look up songs of.
+const MAX_SIMILAR_ARTISTS: usize = 20;
+
+/// Where auto-DJ draws new songs from. Mirrors the autodjsource GSettings enum.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum AutoDjSource {
+    #[default]
+    Random,
+    SameArtist,
+    SameGenre,
+    SimilarArtists,
+    SmartPlaylist
+}
+
+impl From<i32> for AutoDjSource {
+    fn from(val: i32) -> Self {
+        match val {
+            1 => Self::SameArtist,
+            2 => Self::SameGenre,
+            3 => Self::SimilarArtists,
+            4 => Self::SmartPlaylist,
+            _ => Self::Random
+        }
+    }
+}
+
+/// How to fill a candidate pool.
+pub enum PoolRequest {
+    // One MPD search per list of (tag, value) pairs. Results are merged.
+    // An empty list of pairs matches the whole library.
+    Find(Vec<Vec<(String, String)>>),
+    Smart(SmartPlaylist)
+}
+
+impl PoolRequest {
+    pub fn random() -> (String, Self) {
+        ("autodj:random".to_owned(), Self::Find(vec![Vec::new()]))
+    }
+
+    pub fn same_artist(artist: &str) -> (String, Self) {
+        (
+            format!("autodj:artist:{artist}"),
+            Self::Find(vec![vec![("artist".to_owned(), artist.to_owned())]])
+        )
+    }
+
+    pub fn same_genre(genre: &str) -> (String, Self) {
+        (
+            format!("autodj:genre:{genre}"),
+            Self::Find(vec![vec![("genre".to_owned(), genre.to_owned())]])
+        )
+    }
+
+    pub fn similar_artists(artist: &str, similar: &[String]) -> (String, Self) {
+        (
+            format!("autodj:similar:{artist}"),
+            Self::Find(
+                similar
+                    .iter()
+                    .take(MAX_SIMILAR_ARTISTS)
+                    .map(|name| vec![("artist".to_owned(), name.clone())])
+                    .collect()
+            )
+        )
+    }
+
+    pub fn smart(playlist: SmartPlaylist) -> (String, Self) {
+        (format!("autodj:smart:{}", &playlist.name), Self::Smart(playlist))
+    }
+}
+
+/// Candidate song URIs for one pool, along with when they were fetched.
+#[derive(Debug)]
+struct Pool {
+    // When the last request for this pool returned. None while still fetching.
+    fetched: Option<Instant>,
+    // Number of requests sent to MPD for this pool that have yet to return.
+    pending: u32,
+    uris: Vec<String>
+}
+
+/// Bookkeeping for the Player's auto-DJ mode: candidate song URIs fetched
+/// so far, plus flags to avoid topping up twice for the same shortfall.
+#[derive(Debug, Default)]
+pub struct AutoDj {
+    pools: RefCell<FxHashMap<String, Pool>>,
+    // Songs have been added but the resulting queue change has not arrived yet.
+    adding: Cell<bool>,
+    // When we last started waiting on MPD for either of the above.
+    busy_since: Cell<Option<Instant>>,
+    // Smart playlist whose content we are waiting for, if any.
+    pending_smart: RefCell<Option<String>>
+}
+
+impl AutoDj {
+    /// Whether we are still waiting on MPD. Gives up on requests that have taken too
+    /// long, as they most likely failed.
+    pub fn is_busy(&self) -> bool {
+        let fetching = self.pools.borrow().values().any(|pool| pool.fetched.is_none());
+        if !fetching && !self.adding.get() {
+            return false;
+        }
+        if self.busy_since.get().is_some_and(|since| since.elapsed() >= BUSY_TIMEOUT) {
+            println!("Auto-DJ: MPD did not respond in time, trying again");
+            self.reset();
+            return false;
+        }
+        true
+    }
+
+    /// Whether a pool has been fetched recently enough to draw from.
+    pub fn has_pool(&self, key: &str) -> bool {
+        self.pools
+            .borrow()
+            .get(key)
+            .and_then(|pool| pool.fetched)
+            .is_some_and(|fetched| fetched.elapsed() < POOL_LIFETIME)
+    }
+
+    /// Register a pool as being fetched with the given number of requests, replacing
+    /// any older version of it.
+    pub fn begin_pool(&self, key: &str, n_requests: u32) {
+        let mut pools = self.pools.borrow_mut();
+        if pools.len() >= MAX_POOLS && !pools.contains_key(key) {
+            // Keep pools that are still being fetched.
+            let oldest = pools
+                .iter()
+                .min_by_key(|(_, pool)| (pool.fetched.is_none(), pool.fetched))
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                pools.remove(&oldest);
+            }
+        }
+        pools.insert(key.to_owned(), Pool {fetched: None, pending: n_requests, uris: Vec::new()});
+        self.busy_since.set(Some(Instant::now()));
+    }
+
+    pub fn begin_smart_pool(&self, key: &str, name: &str) {
+        self.begin_pool(key, 1);
+        let _ = self.pending_smart.replace(Some(name.to_owned()));
+    }
+
+    /// Take note of the smart playlist we're waiting for, if its name matches.
+    pub fn take_pending_smart(&self, name: &str) -> bool {
+        let matches = self.pending_smart.borrow().as_deref() == Some(name);
+        if matches {
+            let _ = self.pending_smart.take();
+        }
+        matches
+    }
+
+    /// Merge search results into a pool. Returns true once the pool has been fully
+    /// fetched. Results for pools we have stopped waiting on are ignored.
+    pub fn on_songs_found(&self, key: &str, uris: &[String]) -> bool {
+        let mut pools = self.pools.borrow_mut();
+        let Some(pool) = pools.get_mut(key).filter(|pool| pool.fetched.is_none()) else {
+            return false;
+        };
+        pool.uris.extend_from_slice(uris);
+        pool.pending = pool.pending.saturating_sub(1);
+        if pool.pending == 0 {
+            pool.fetched = Some(Instant::now());
+            return true;
+        }
+        false
+    }
+
+    pub fn set_adding(&self, adding: bool) {
+        self.adding.set(adding);
+        if adding {
+            self.busy_since.set(Some(Instant::now()));
+        }
+    }
+
+    /// Forget about requests that may never be answered (for example due to a reconnection).
+    pub fn reset(&self) {
+        // Partially-fetched pools would otherwise be drawn from as if complete.
+        self.pools.borrow_mut().retain(|_, pool| pool.fetched.is_some());
+        self.adding.set(false);
+        self.busy_since.set(None);
+        let _ = self.pending_smart.take();
+    }
+
+    /// Randomly pick up to count songs from a pool, skipping those already queued
+    /// and those played recently. If the no-repeat window rules out the whole pool,
+    /// fall back to only skipping queued songs.
+    pub fn pick(
+        &self,
+        key: &str,
+        count: usize,
+        queued: &FxHashSet<String>,
+        recent: &FxHashSet<String>
+    ) -> Vec<String> {
+        let pools = self.pools.borrow();
+        let Some(pool) = pools.get(key).map(|pool| &pool.uris) else {
+            return Vec::new();
+        };
+        // Pools merged from several searches may contain duplicates.
+        let mut seen: FxHashSet<&str> = FxHashSet::default();
+        let mut candidates: Vec<&String> = pool
+            .iter()
+            .filter(|uri| !queued.contains(*uri) && !recent.contains(*uri) && seen.insert(uri.as_str()))
+            .collect();
+        if candidates.is_empty() {
+            seen.clear();
+            candidates = pool
+                .iter()
+                .filter(|uri| !queued.contains(*uri) && seen.insert(uri.as_str()))
+                .collect();
+        }
+        // Partial Fisher-Yates shuffle
+        let n = count.min(candidates.len());
+        for i in 0..n {
+            let j = glib::random_int_range(i as i32, candidates.len() as i32) as usize;
+            candidates.swap(i, j);
+        }
+        candidates.into_iter().take(n).cloned().collect()
+    }
+}
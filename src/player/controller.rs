@@ -3,16 +3,20 @@ use crate::{
     application::EuphonicaApplication,
     cache::Cache,
     client::{ClientState, MpdMessage},
-    common::{AlbumInfo, QualityGrade, Song},
+    common::{song::{FAVORITE_STICKER, MAX_RATING, RATING_STICKER}, AlbumInfo, QualityGrade, Song, SongStickers},
     utils::{prettify_audio_format, settings_manager}
 };
+use super::auto_dj::{AutoDj, AutoDjSource, PoolRequest};
+use rustc_hash::FxHashSet;
 use async_lock::OnceCell as AsyncOnceCell;
 use image::DynamicImage;
 use mpris_server::{
-    zbus::{self, fdo},
-    LocalPlayerInterface, LocalRootInterface, LocalServer,
+    zbus::{self, fdo, zvariant::ObjectPath},
+    LocalPlayerInterface, LocalPlaylistsInterface, LocalRootInterface, LocalServer,
     LoopStatus, Metadata as MprisMetadata, PlaybackRate,
     PlaybackStatus as MprisPlaybackStatus,
+    Playlist as MprisPlaylist, PlaylistId, PlaylistOrdering,
+    PlaylistsProperty, PlaylistsSignal,
     Property, Signal as MprisSignal, Time, TrackId, Volume,
 };
 
@@ -21,11 +25,28 @@ use async_channel::Sender;
 use glib::{closure_local, subclass::Signal, BoxedAnyObject};
 use gtk::{gdk::Texture, glib::clone};
 use gtk::{gio, glib, prelude::*};
-use mpd::{status::{AudioFormat, State, Status}, ReplayGain};
+use mpd::{playlist::Playlist, status::{AudioFormat, State, Status}, ReplayGain};
 use std::{
     cell::{Cell, OnceCell, RefCell}, path::PathBuf, rc::Rc, sync::OnceLock, vec::Vec
 };
 
+// Beyond this many folders, queued songs' stickers are fetched for the whole library at once.
+const MAX_STICKER_FOLDERS: usize = 8;
+// Like Last.fm scrobbling, a song counts as played once half of it (but at most 4 minutes)
+// has been listened to.
+const MAX_PLAY_THRESHOLD_S: f64 = 240.0;
+// Position changes bigger than this between two polls are seeks, not listening.
+const MAX_POLL_GAP_S: f64 = 5.0;
+
+/// Seconds of a song to listen to before it counts as played.
+fn play_threshold(song: &Song) -> f64 {
+    match song.get_duration() {
+        // Streams & songs of unknown length
+        0 => MAX_PLAY_THRESHOLD_S,
+        duration => (duration as f64 / 2.0).min(MAX_PLAY_THRESHOLD_S)
+    }
+}
+
 #[derive(Clone, Copy, Debug, glib::Enum, PartialEq, Default)]
 #[enum_type(name = "EuphonicaPlaybackState")]
 pub enum PlaybackState {
@@ -147,6 +168,38 @@ fn get_replaygain_icon_name(mode: ReplayGain) -> &'static str {
     }
 }
 
+/// D-Bus object paths only allow [A-Za-z0-9_] in their elements, while stored
+/// playlist names can contain just about anything. Hex-encode the name so we can
+/// map MPRIS playlist IDs back to MPD playlist names losslessly:
+/// /org/euphonica/Euphonica/playlist/<hex-encoded name>
+fn playlist_name_to_id(name: &str) -> PlaylistId {
+    let mut path = String::from("/org/euphonica/Euphonica/playlist/_");
+    for byte in name.as_bytes() {
+        path.push_str(&format!("{:02x}", byte));
+    }
+    ObjectPath::from_string_unchecked(path).into()
+}
+
+fn playlist_id_to_name(id: &PlaylistId) -> Option<String> {
+    let hex = id.as_str().strip_prefix("/org/euphonica/Euphonica/playlist/_")?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let bytes: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect();
+    String::from_utf8(bytes?).ok()
+}
+
+fn playlist_to_mpris(playlist: &Playlist) -> MprisPlaylist {
+    MprisPlaylist {
+        id: playlist_name_to_id(&playlist.name),
+        name: playlist.name.clone(),
+        icon: String::new()
+    }
+}
+
 mod imp {
     use crate::application::EuphonicaApplication;
 
@@ -159,6 +212,10 @@ mod imp {
     pub struct Player {
         pub state: Cell<PlaybackState>,
         pub position: Cell<f64>,
+        // Seconds of the current song listened to so far, not counting seeks
+        pub listened: Cell<f64>,
+        // Whether the current song's play has already been recorded
+        pub play_counted: Cell<bool>,
         pub queue: gio::ListStore,
         pub current_song: RefCell<Option<Song>>,
         pub format: RefCell<Option<AudioFormat>>,
@@ -186,6 +243,12 @@ mod imp {
         pub poll_blocked: Cell<bool>,
         pub mpris_server: AsyncOnceCell<LocalServer<super::Player>>,
         pub mpris_enabled: Cell<bool>,
+        // Stored playlists as last reported by MPD. Kept here so MPRIS
+        // can answer GetPlaylists without a round trip to the daemon.
+        pub playlists: RefCell<Vec<Playlist>>,
+        // Name of the stored playlist last loaded via MPRIS, if any.
+        pub active_playlist: RefCell<Option<String>>,
+        pub auto_dj: AutoDj,
         pub app: OnceCell<EuphonicaApplication>
     }
 
@@ -198,6 +261,8 @@ mod imp {
             Self {
                 state: Cell::new(PlaybackState::Stopped),
                 position: Cell::new(0.0),
+                listened: Cell::new(0.0),
+                play_counted: Cell::new(false),
                 random: Cell::new(false),
                 consume: Cell::new(false),
                 replaygain: Cell::new(ReplayGain::Off),
@@ -215,6 +280,9 @@ mod imp {
                 poll_blocked: Cell::new(false),
                 mpris_server: AsyncOnceCell::new(),
                 mpris_enabled: Cell::new(false),
+                playlists: RefCell::new(Vec::new()),
+                active_playlist: RefCell::new(None),
+                auto_dj: AutoDj::default(),
                 app: OnceCell::new()
             }
         }
@@ -249,6 +317,8 @@ mod imp {
                         .read_only()
                         .build(),
                     ParamSpecString::builder("format-desc").read_only().build(),
+                    ParamSpecUInt::builder("rating").maximum(MAX_RATING).read_only().build(),
+                    ParamSpecBoolean::builder("favorite").read_only().build(),
                 ]
             });
             PROPERTIES.as_ref()
@@ -275,6 +345,8 @@ mod imp {
                 "queue-id" => obj.queue_id().to_value(),
                 "quality-grade" => obj.quality_grade().to_value(),
                 "format-desc" => obj.format_desc().to_value(),
+                "rating" => obj.rating().to_value(),
+                "favorite" => obj.is_favorite().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -353,7 +425,7 @@ impl Player {
     /// by the initial call to update_status().
     async fn get_mpris(&self) -> zbus::Result<&LocalServer<Self>> {
         self.imp().mpris_server.get_or_try_init(|| async {
-            let server = LocalServer::new("org.euphonica.Euphonica", self.clone())
+            let server = LocalServer::new_with_playlists("org.euphonica.Euphonica", self.clone())
                 .await?;
             glib::spawn_future_local(server.run());
             Ok(server)
@@ -416,8 +488,80 @@ impl Player {
                 }
             ),
         );
+        client_state.connect_closure(
+            "playlists-changed",
+            false,
+            closure_local!(
+                #[strong(rename_to = this)]
+                self,
+                move |_: ClientState, boxed: BoxedAnyObject| {
+                    this.update_playlists(boxed.borrow::<Vec<Playlist>>().as_ref());
+                }
+            ),
+        );
+        client_state.connect_closure(
+            "sticker-downloaded",
+            false,
+            closure_local!(
+                #[strong(rename_to = this)]
+                self,
+                move |_: ClientState, typ: String, uri: String, name: String, value: String| {
+                    if typ == "song" {
+                        this.update_sticker(&uri, &name, &value);
+                    }
+                }
+            ),
+        );
+        client_state.connect_closure(
+            "song-stickers-downloaded",
+            false,
+            closure_local!(
+                #[strong(rename_to = this)]
+                self,
+                move |_: ClientState, base_uri: String, boxed: BoxedAnyObject| {
+                    this.update_stickers(&base_uri, &boxed.borrow::<SongStickers>());
+                }
+            ),
+        );
+
+        client_state.connect_closure(
+            "songs-found",
+            false,
+            closure_local!(
+                #[strong(rename_to = this)]
+                self,
+                move |_: ClientState, key: String, boxed: BoxedAnyObject| {
+                    if key.starts_with("autodj:") {
+                        this.on_auto_dj_songs_found(&key, boxed.borrow::<Vec<String>>().as_ref());
+                    }
+                }
+            ),
+        );
+        client_state.connect_closure(
+            "smart-playlist-songs-downloaded",
+            false,
+            closure_local!(
+                #[strong(rename_to = this)]
+                self,
+                move |_: ClientState, name: String, boxed: BoxedAnyObject| {
+                    if this.imp().auto_dj.take_pending_smart(&name) {
+                        this.on_auto_dj_smart_playlist_downloaded(&name, boxed.borrow::<Vec<Song>>().as_ref());
+                    }
+                }
+            ),
+        );
 
         let settings = settings_manager().child("player");
+        settings.connect_changed(
+            Some("autodj-enabled"),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _| {
+                    this.maybe_top_up_queue();
+                }
+            )
+        );
         let _ = self.imp().mpris_enabled.replace(settings.boolean("enable-mpris"));
         settings.connect_changed(
             Some("enable-mpris"),
@@ -459,6 +603,36 @@ impl Player {
         );
     }
 
+    fn update_mpris_playlists(&self, properties: Vec<PlaylistsProperty>, changed: Vec<MprisPlaylist>) {
+        glib::spawn_future_local(
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    match this.get_mpris().await {
+                        Ok(mpris) => {
+                            for playlist in changed.into_iter() {
+                                if let Err(err) = mpris.playlists_emit(
+                                    PlaylistsSignal::PlaylistChanged { playlist }
+                                ).await {
+                                    println!("{:?}", err);
+                                }
+                            }
+                            if !properties.is_empty() {
+                                if let Err(err) = mpris.playlists_properties_changed(properties).await {
+                                    println!("{:?}", err);
+                                }
+                            }
+                        }
+                        Err(err) => {
+                            println!("No MPRIS server: {:?}", err);
+                        }
+                    }
+                }
+            ),
+        );
+    }
+
     fn seek_mpris(&self, position: f64) {
         glib::spawn_future_local(
             clone!(
@@ -613,6 +787,10 @@ impl Player {
             if new != old && self.imp().mpris_enabled.get() {
                 self.seek_mpris(new);
             }
+            let listened = new - old;
+            if listened > 0.0 && listened <= MAX_POLL_GAP_S {
+                self.imp().listened.set(self.imp().listened.get() + listened);
+            }
         } else {
             self.set_position(0.0);
         }
@@ -654,12 +832,20 @@ impl Player {
                 if let Some(song) = &old_song {
                     song.set_is_playing(false);
                 }
+                // A song already past the threshold when first seen (on startup or after
+                // reconnecting) has most likely been counted already.
+                self.imp().listened.set(0.0);
+                self.imp().play_counted.set(previously_none && status.elapsed.is_some_and(
+                    |elapsed| elapsed.as_secs_f64() >= play_threshold(&new_song)
+                ));
                 // Remove playing status
                 self.notify("title");
                 self.notify("artist");
                 self.notify("duration");
                 self.notify("quality-grade");
                 self.notify("format-desc");
+                self.notify("rating");
+                self.notify("favorite");
                 // Avoid needlessly changing album art as background blur updates are expensive.
                 if (old_song.is_none()) || (new_song.get_album_title() != old_song.unwrap().get_album_title()) {
                     self.notify("album");
@@ -687,6 +873,8 @@ impl Player {
                 self.notify("album");
                 self.notify("album-art");
                 self.notify("duration");
+                self.notify("rating");
+                self.notify("favorite");
                 // Update MPRIS side
                 if self.imp().mpris_enabled.get() {
                     mpris_changes.push(Property::Metadata(
@@ -698,6 +886,16 @@ impl Player {
             }
         }
 
+        self.maybe_record_play();
+
+        // An emptied queue no longer reflects the last activated playlist.
+        if status.queue_len == 0 && self.imp().active_playlist.borrow().is_some() {
+            let _ = self.imp().active_playlist.take();
+            if self.imp().mpris_enabled.get() {
+                self.update_mpris_playlists(vec![PlaylistsProperty::ActivePlaylist(None)], Vec::new());
+            }
+        }
+
         // If new queue is shorter, truncate current queue.
         // This is because update_queue would be called before update_status, which means
         // the new length was not available to update_queue.
@@ -708,6 +906,7 @@ impl Player {
         }
 
         self.update_mpris_properties(mpris_changes);
+        self.maybe_top_up_queue();
     }
 
     /// Update the queue, optionally with diffs or an entirely new queue.
@@ -724,6 +923,13 @@ impl Player {
     /// If an MPRIS server is running, it will also emit property change signals.
     pub fn update_queue(&self, songs: &[Song], replace: bool) {
         let queue = &self.imp().queue;
+        if replace {
+            // Possibly a reconnection. Pending auto-DJ requests may never return.
+            self.imp().auto_dj.reset();
+        }
+        else {
+            self.imp().auto_dj.set_adding(false);
+        }
         if replace {
             if songs.len() == 0 {
                 queue.remove_all();
@@ -782,13 +988,294 @@ impl Player {
             // Might queue downloads, depending on user settings
             cache.ensure_cached_album_arts(&infos);
         }
+        // Songs fresh from MPD don't carry their stickers.
+        self.fetch_stickers_of(songs);
         // Downstream widgets should now receive an item-changed signal.
     }
 
+    /// Fetch ratings & favourite flags of the given songs, one search per folder they are
+    /// in. Songs from too many folders are looked up across the whole library in one go
+    /// instead, as each search takes several round trips.
+    fn fetch_stickers_of(&self, songs: &[Song]) {
+        let folders: FxHashSet<&str> = songs
+            .iter()
+            .map(|song| song.get_uri().rsplit_once('/').map_or("", |(folder, _)| folder))
+            .collect();
+        if folders.len() > MAX_STICKER_FOLDERS || folders.contains("") {
+            self.send(MpdMessage::SongStickers(String::new())).ok();
+        }
+        else {
+            for folder in folders.into_iter() {
+                self.send(MpdMessage::SongStickers(folder.to_owned())).ok();
+            }
+        }
+    }
+
+    /// MPD keeps no play history, so count plays ourselves. Skipped songs don't count.
+    fn maybe_record_play(&self) {
+        if self.imp().play_counted.get() {
+            return;
+        }
+        let Some(song) = self.imp().current_song.borrow().clone() else {
+            return;
+        };
+        if self.imp().listened.get() >= play_threshold(&song) {
+            self.imp().play_counted.set(true);
+            if let Some(cache) = self.imp().cache.get() {
+                cache.record_play(song.get_uri());
+            }
+        }
+    }
+
+    /// Auto-DJ: add more songs once the queue is about to run out.
+    ///
+    /// Remaining songs are counted from the queue itself, so consume mode works as-is.
+    /// Songs are drawn from a pool fetched according to the configured source and seeded
+    /// by the current song (or the last queued one). If that pool has nothing new left,
+    /// the whole library is used instead.
+    fn maybe_top_up_queue(&self) {
+        let settings = settings_manager().child("player");
+        let auto_dj = &self.imp().auto_dj;
+        // Repeat modes never run out, and Single mode stops after each song anyway.
+        if !settings.boolean("autodj-enabled") || self.imp().flow.get() != PlaybackFlow::Sequential || auto_dj.is_busy() {
+            return;
+        }
+        let queue = &self.imp().queue;
+        let n_items = queue.n_items();
+        let remaining = match self.imp().current_song.borrow().as_ref() {
+            Some(song) => n_items.saturating_sub(song.get_queue_pos() + 1),
+            None => n_items
+        };
+        if remaining >= settings.uint("autodj-min-remaining") {
+            return;
+        }
+
+        let seed: Option<Song> = self.imp().current_song.borrow().clone().or_else(
+            || if n_items > 0 { queue.item(n_items - 1).and_downcast::<Song>() } else { None }
+        );
+        let mut requests: Vec<(String, PoolRequest)> = Vec::with_capacity(2);
+        if let Some(primary) = self.auto_dj_primary_pool(AutoDjSource::from(settings.enum_("autodj-source")), seed.as_ref()) {
+            requests.push(primary);
+        }
+        requests.push(PoolRequest::random());
+
+        let queued: FxHashSet<String> = queue
+            .iter::<Song>()
+            .flatten()
+            .map(|song| song.get_uri().to_owned())
+            .collect();
+        let recent: FxHashSet<String> = self.imp().cache.get()
+            .map(|cache| cache.load_recent_plays(settings.uint("autodj-no-repeat-window") as usize))
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        let count = settings.uint("autodj-batch-size").max(1) as usize;
+        for (key, request) in requests.into_iter() {
+            if !auto_dj.has_pool(&key) {
+                // Come back once it has been fetched.
+                self.request_auto_dj_pool(&key, request);
+                return;
+            }
+            let picked = auto_dj.pick(&key, count, &queued, &recent);
+            if !picked.is_empty() {
+                println!("Auto-DJ: adding {} songs from {}", picked.len(), &key);
+                auto_dj.set_adding(true);
+                for uri in picked.into_iter() {
+                    self.send(MpdMessage::Add(uri, false)).ok();
+                }
+                return;
+            }
+        }
+    }
+
+    fn auto_dj_primary_pool(&self, source: AutoDjSource, seed: Option<&Song>) -> Option<(String, PoolRequest)> {
+        match source {
+            AutoDjSource::Random => None,
+            AutoDjSource::SameArtist => seed
+                .and_then(|song| song.get_artists().first())
+                .map(|artist| PoolRequest::same_artist(&artist.name)),
+            AutoDjSource::SameGenre => seed
+                .and_then(|song| song.get_genre())
+                .map(PoolRequest::same_genre),
+            AutoDjSource::SimilarArtists => {
+                let artist = seed.and_then(|song| song.get_artists().first())?;
+                // Only use what has already been downloaded. Artists not in the
+                // library simply yield no songs.
+                let similar: Vec<String> = self.imp().cache.get()
+                    .and_then(|cache| cache.load_cached_artist_meta(artist))
+                    .map(|meta| meta.similar.into_iter().map(|similar| similar.name).collect())
+                    .unwrap_or_default();
+                if similar.is_empty() {
+                    Some(PoolRequest::same_artist(&artist.name))
+                }
+                else {
+                    Some(PoolRequest::similar_artists(&artist.name, &similar))
+                }
+            }
+            AutoDjSource::SmartPlaylist => {
+                let name = settings_manager().child("player").string("autodj-smart-playlist");
+                self.imp().cache.get()?
+                    .load_smart_playlists()
+                    .into_iter()
+                    .find(|playlist| playlist.name == name.as_str())
+                    .map(PoolRequest::smart)
+            }
+        }
+    }
+
+    fn request_auto_dj_pool(&self, key: &str, request: PoolRequest) {
+        let auto_dj = &self.imp().auto_dj;
+        match request {
+            PoolRequest::Find(searches) => {
+                auto_dj.begin_pool(key, searches.len() as u32);
+                for terms in searches.into_iter() {
+                    self.send(MpdMessage::FindSongs(key.to_owned(), terms)).ok();
+                }
+            }
+            PoolRequest::Smart(playlist) => {
+                auto_dj.begin_smart_pool(key, &playlist.name);
+                self.send(MpdMessage::SmartPlaylistContent(playlist)).ok();
+            }
+        }
+    }
+
+    fn on_auto_dj_songs_found(&self, key: &str, uris: &[String]) {
+        if self.imp().auto_dj.on_songs_found(key, uris) {
+            self.maybe_top_up_queue();
+        }
+    }
+
+    fn on_auto_dj_smart_playlist_downloaded(&self, name: &str, candidates: &[Song]) {
+        let playlist = self.imp().cache.get().and_then(|cache| cache
+            .load_smart_playlists()
+            .into_iter()
+            .find(|playlist| playlist.name == name)
+        );
+        let uris: Vec<String> = if let (Some(playlist), Some(cache)) = (playlist, self.imp().cache.get()) {
+            playlist
+                .filter(candidates, &cache.load_play_counts())
+                .iter()
+                .map(|song| song.get_uri().to_owned())
+                .collect()
+        }
+        else {
+            Vec::new()
+        };
+        self.on_auto_dj_songs_found(&format!("autodj:smart:{name}"), &uris);
+    }
+
+    /// Apply a single changed sticker to matching songs in the queue.
+    fn update_sticker(&self, uri: &str, name: &str, value: &str) {
+        for song in self.imp().queue.iter::<Song>().flatten() {
+            if song.get_uri() == uri {
+                song.apply_sticker(name, value);
+            }
+        }
+        let is_current = self.imp().current_song.borrow().as_ref().is_some_and(|song| song.get_uri() == uri);
+        if is_current {
+            self.on_current_song_stickers_changed();
+        }
+    }
+
+    /// Apply sticker search results to all songs in the queue under the given folder.
+    fn update_stickers(&self, base_uri: &str, stickers: &SongStickers) {
+        for song in self.imp().queue.iter::<Song>().flatten() {
+            song.apply_stickers(base_uri, stickers);
+        }
+        if self.imp().current_song.borrow().is_some() {
+            self.on_current_song_stickers_changed();
+        }
+    }
+
+    fn on_current_song_stickers_changed(&self) {
+        self.notify("rating");
+        self.notify("favorite");
+        if self.imp().mpris_enabled.get() {
+            let meta = self.imp().current_song.borrow().as_ref().map(
+                |song| song.get_mpris_metadata(self.imp().cache.get().unwrap().clone())
+            );
+            if let Some(meta) = meta {
+                self.update_mpris_properties(vec![Property::Metadata(meta)]);
+            }
+        }
+    }
+
+    /// Rate a song (0 to 5 stars). Stored as a sticker so other clients can see it too.
+    pub fn set_song_rating(&self, uri: &str, rating: u32) {
+        self.send(MpdMessage::SetSticker(
+            "song".to_owned(), uri.to_owned(), RATING_STICKER.to_owned(), rating.min(MAX_RATING).to_string()
+        )).ok();
+    }
+
+    /// Mark or unmark a song as favourite. Stored as a sticker so other clients can see it too.
+    pub fn set_song_favorite(&self, uri: &str, favorite: bool) {
+        self.send(MpdMessage::SetSticker(
+            "song".to_owned(), uri.to_owned(), FAVORITE_STICKER.to_owned(),
+            (if favorite { "1" } else { "0" }).to_owned()
+        )).ok();
+    }
+
+    /// Rate the currently-playing song, if any.
+    pub fn rate_current_song(&self, rating: u32) {
+        let uri = self.imp().current_song.borrow().as_ref().map(|song| song.get_uri().to_owned());
+        if let Some(uri) = uri {
+            self.set_song_rating(&uri, rating);
+        }
+    }
+
+    /// Toggle the favourite flag of the currently-playing song, if any.
+    pub fn toggle_current_song_favorite(&self) {
+        let target = self.imp().current_song.borrow().as_ref().map(
+            |song| (song.get_uri().to_owned(), !song.is_favorite())
+        );
+        if let Some((uri, favorite)) = target {
+            self.set_song_favorite(&uri, favorite);
+        }
+    }
+
     fn update_outputs(&self, outputs: BoxedAnyObject) {
         self.emit_by_name::<()>("outputs-changed", &[&outputs]);
     }
 
+    /// Replace our list of stored playlists. Playlists that are new or whose
+    /// modification time has changed will be announced over MPRIS.
+    fn update_playlists(&self, playlists: &[Playlist]) {
+        let old = self.imp().playlists.replace(playlists.to_vec());
+        if !self.imp().mpris_enabled.get() {
+            return;
+        }
+        let changed: Vec<MprisPlaylist> = playlists
+            .iter()
+            .filter(|new| !old.iter().any(|o| o.name == new.name && o.last_mod == new.last_mod))
+            .map(playlist_to_mpris)
+            .collect();
+        let mut properties: Vec<PlaylistsProperty> = Vec::new();
+        if old.len() != playlists.len() {
+            properties.push(PlaylistsProperty::PlaylistCount(playlists.len() as u32));
+        }
+        // The active playlist might have been deleted or renamed by another client.
+        let active_gone = self.imp().active_playlist.borrow().as_ref().is_some_and(
+            |name| !playlists.iter().any(|p| &p.name == name)
+        );
+        if active_gone {
+            let _ = self.imp().active_playlist.take();
+            properties.push(PlaylistsProperty::ActivePlaylist(None));
+        }
+        self.update_mpris_playlists(properties, changed);
+    }
+
+    /// Replace the queue with the stored playlist of the given name and start playing it.
+    pub fn replace_queue_with_playlist(&self, name: &str) {
+        self.send(MpdMessage::Clear).ok();
+        self.send(MpdMessage::LoadPlaylist(name.to_owned())).ok();
+        self.send(MpdMessage::PlayPos(0)).ok();
+        let _ = self.imp().active_playlist.replace(Some(name.to_owned()));
+        if self.imp().mpris_enabled.get() {
+            let active = self.imp().playlists.borrow().iter().find(|p| p.name == name).map(playlist_to_mpris);
+            self.update_mpris_playlists(vec![PlaylistsProperty::ActivePlaylist(active)], Vec::new());
+        }
+    }
+
     pub fn set_output(&self, id: u32, state: bool) {
         self.send(MpdMessage::Output(id, state)).ok();
     }
@@ -895,6 +1382,20 @@ impl Player {
         QualityGrade::Unknown
     }
 
+    pub fn rating(&self) -> u32 {
+        if let Some(song) = &*self.imp().current_song.borrow() {
+            return song.get_rating();
+        }
+        0
+    }
+
+    pub fn is_favorite(&self) -> bool {
+        if let Some(song) = &*self.imp().current_song.borrow() {
+            return song.is_favorite();
+        }
+        false
+    }
+
     pub fn format_desc(&self) -> Option<String> {
         if let Some(format) = &*self.imp().format.borrow() {
             return Some(prettify_audio_format(format));
@@ -1260,3 +1761,58 @@ impl LocalPlayerInterface for Player {
         Ok(self.imp().mpris_enabled.get())
     }
 }
+
+impl LocalPlaylistsInterface for Player {
+    async fn activate_playlist(&self, playlist_id: PlaylistId) -> fdo::Result<()> {
+        if let Some(name) = playlist_id_to_name(&playlist_id) {
+            if self.imp().playlists.borrow().iter().any(|p| p.name == name) {
+                self.replace_queue_with_playlist(&name);
+                return Ok(());
+            }
+        }
+        Err(fdo::Error::InvalidArgs("No such playlist".to_owned()))
+    }
+
+    async fn get_playlists(
+        &self,
+        index: u32,
+        max_count: u32,
+        order: PlaylistOrdering,
+        reverse_order: bool
+    ) -> fdo::Result<Vec<MprisPlaylist>> {
+        let mut playlists: Vec<Playlist> = self.imp().playlists.borrow().clone();
+        match order {
+            // MPD's last-modified timestamps are ISO 8601, so they sort correctly as strings.
+            PlaylistOrdering::ModifiedDate => playlists.sort_by(|a, b| a.last_mod.cmp(&b.last_mod)),
+            _ => playlists.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        }
+        if reverse_order {
+            playlists.reverse();
+        }
+        Ok(
+            playlists
+                .iter()
+                .skip(index as usize)
+                .take(max_count as usize)
+                .map(playlist_to_mpris)
+                .collect()
+        )
+    }
+
+    async fn playlist_count(&self) -> fdo::Result<u32> {
+        Ok(self.imp().playlists.borrow().len() as u32)
+    }
+
+    async fn orderings(&self) -> fdo::Result<Vec<PlaylistOrdering>> {
+        Ok(vec![PlaylistOrdering::Alphabetical, PlaylistOrdering::ModifiedDate])
+    }
+
+    async fn active_playlist(&self) -> fdo::Result<Option<MprisPlaylist>> {
+        if let Some(name) = self.imp().active_playlist.borrow().as_ref() {
+            return Ok(
+                self.imp().playlists.borrow().iter().find(|p| &p.name == name).map(playlist_to_mpris)
+            );
+        }
+        Ok(None)
+    }
+}
@@ -1,4 +1,5 @@
 mod controller;
+mod auto_dj;
 mod queue_row;
 mod queue_view;
 mod bar;
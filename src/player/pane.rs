@@ -14,7 +14,7 @@ use glib::{
 use mpd::output::Output;
 
 use crate::{
-    cache::placeholders::ALBUMART_PLACEHOLDER, common::{paintables::FadePaintable, QualityGrade}, utils::settings_manager
+    cache::placeholders::ALBUMART_PLACEHOLDER, common::{paintables::FadePaintable, QualityGrade, Rating}, utils::settings_manager
 };
 
 use super::{
@@ -40,6 +40,10 @@ mod imp {
         pub artist: TemplateChild<gtk::Label>,
         #[template_child]
         pub album: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub rating: TemplateChild<Rating>,
+        #[template_child]
+        pub favorite: TemplateChild<gtk::Button>,
 
         // TODO: Time-synced lyrics
 
@@ -367,6 +371,52 @@ impl PlayerPane {
             .sync_create()
             .build();
 
+        let rating = imp.rating.get();
+        player
+            .bind_property(
+                "rating",
+                &rating,
+                "value"
+            )
+            .sync_create()
+            .build();
+        rating.connect_closure(
+            "rated",
+            false,
+            closure_local!(
+                #[weak]
+                player,
+                move |_: Rating, val: u32| {
+                    player.rate_current_song(val);
+                }
+            )
+        );
+
+        let favorite = imp.favorite.get();
+        player
+            .bind_property(
+                "favorite",
+                &favorite,
+                "icon-name"
+            )
+            .transform_to(|_, favorite: bool| {
+                if favorite {
+                    Some("heart-filled-symbolic")
+                }
+                else {
+                    Some("heart-outline-symbolic")
+                }
+            })
+            .sync_create()
+            .build();
+        favorite.connect_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.toggle_current_song_favorite();
+            }
+        ));
+
         let quality_grade = imp.quality_grade.get();
         player
             .bind_property(
@@ -6,6 +6,7 @@ use gtk::{
     glib::{self, clone}, prelude::*, subclass::prelude::*, CompositeTemplate, Image, Label
 };
 use glib::{
+    closure,
     closure_local,
     Object,
     signal::SignalHandlerId
@@ -16,7 +17,7 @@ use crate::{
         placeholders::ALBUMART_PLACEHOLDER,
         Cache, CacheState
     },
-    common::{AlbumInfo, Song}
+    common::{AlbumInfo, Rating, Song}
 };
 
 use super::{controller::SwapDirection, Player};
@@ -54,6 +55,10 @@ mod imp {
         pub quality_grade: TemplateChild<gtk::Image>,
         #[template_child]
         pub remove: TemplateChild<Button>,
+        #[template_child]
+        pub rating: TemplateChild<Rating>,
+        #[template_child]
+        pub favorite: TemplateChild<Button>,
         pub queue_id: Cell<u32>,
         pub queue_pos: Cell<u32>,
         pub thumbnail_signal_id: RefCell<Option<SignalHandlerId>>,
@@ -218,6 +223,34 @@ impl QueueRow {
             }
         ));
 
+        self.imp().rating.connect_closure(
+            "rated",
+            false,
+            closure_local!(
+                #[weak]
+                item,
+                #[weak]
+                player,
+                move |_: Rating, rating: u32| {
+                    if let Some(song) = item.item().and_downcast::<Song>() {
+                        player.set_song_rating(song.get_uri(), rating);
+                    }
+                }
+            )
+        );
+
+        self.imp().favorite.connect_clicked(clone!(
+            #[weak]
+            item,
+            #[weak]
+            player,
+            move |_| {
+                if let Some(song) = item.item().and_downcast::<Song>() {
+                    player.set_song_favorite(song.get_uri(), !song.is_favorite());
+                }
+            }
+        ));
+
         item
             .property_expression("item")
             .chain_property::<Song>("name")
@@ -260,6 +293,19 @@ impl QueueRow {
             .chain_property::<Song>("queue-pos")
             .bind(self, "queue-pos", gtk::Widget::NONE);
 
+        item
+            .property_expression("item")
+            .chain_property::<Song>("rating")
+            .bind(&self.imp().rating.get(), "value", gtk::Widget::NONE);
+
+        item
+            .property_expression("item")
+            .chain_property::<Song>("favorite")
+            .chain_closure::<String>(closure!(|_: Option<Object>, favorite: bool| {
+                if favorite { "heart-filled-symbolic" } else { "heart-outline-symbolic" }
+            }))
+            .bind(&self.imp().favorite.get(), "icon-name", gtk::Widget::NONE);
+
         // Bind marquee controller only once here
         // Run only while hovered
         let hover_ctl = gtk::EventControllerMotion::new();
@@ -15,7 +15,8 @@ use super::PlayerPane;
 
 use crate::{
     cache::Cache,
-    common::Song
+    common::Song,
+    utils::settings_manager
 };
 
 use super::{
@@ -43,6 +44,8 @@ mod imp {
         #[template_child]
         pub player_pane: TemplateChild<PlayerPane>,
         #[template_child]
+        pub auto_dj: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
         pub consume: TemplateChild<gtk::ToggleButton>,
         #[template_child]
         pub clear_queue: TemplateChild<gtk::Button>,
@@ -269,6 +272,15 @@ impl QueueView {
             .sync_create()
             .build();
 
+        settings_manager()
+            .child("player")
+            .bind(
+                "autodj-enabled",
+                &self.imp().auto_dj.get(),
+                "active"
+            )
+            .build();
+
         clear_queue_btn.connect_clicked(clone!(#[weak] player, move |_| {
             player.clear_queue();
         }));
@@ -0,0 +1,100 @@
+use std::rc::Rc;
+use adw::subclass::prelude::*;
+use gtk::{
+    glib,
+    prelude::*,
+    CompositeTemplate
+};
+
+use crate::cache::Cache;
+
+// How often to update statistics while the page is shown
+const REFRESH_INTERVAL_S: u32 = 2;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/org/euphonica/Euphonica/gtk/preferences/diagnostics.ui")]
+    pub struct DiagnosticsPreferences {
+        #[template_child]
+        pub image_cache_used: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub image_cache_entries: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub image_cache_hits: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub image_cache_misses: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub image_cache_evictions: TemplateChild<gtk::Label>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DiagnosticsPreferences {
+        const NAME: &'static str = "EuphonicaDiagnosticsPreferences";
+        type Type = super::DiagnosticsPreferences;
+        type ParentType = adw::PreferencesPage;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+            klass.set_layout_manager_type::<gtk::BinLayout>();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DiagnosticsPreferences {}
+    impl WidgetImpl for DiagnosticsPreferences {}
+    impl PreferencesPageImpl for DiagnosticsPreferences {}
+}
+
+glib::wrapper! {
+    pub struct DiagnosticsPreferences(ObjectSubclass<imp::DiagnosticsPreferences>)
+        @extends adw::PreferencesPage,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Widget;
+}
+
+impl Default for DiagnosticsPreferences {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl DiagnosticsPreferences {
+    pub fn setup(&self, cache: Rc<Cache>) {
+        self.update(&cache);
+        let this = self.downgrade();
+        glib::timeout_add_seconds_local(REFRESH_INTERVAL_S, move || {
+            let Some(this) = this.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            if this.is_mapped() {
+                this.update(&cache);
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    fn update(&self, cache: &Cache) {
+        let imp = self.imp();
+        let stats = cache.get_image_cache_stats();
+        // TODO: translatable
+        imp.image_cache_used.set_label(&format!(
+            "{} of {}",
+            glib::format_size(stats.used),
+            glib::format_size(stats.budget)
+        ));
+        imp.image_cache_entries.set_label(&stats.entries.to_string());
+        let lookups = stats.hits + stats.misses;
+        imp.image_cache_hits.set_label(&if lookups > 0 {
+            format!("{} ({:.0}%)", stats.hits, stats.hits as f64 * 100.0 / lookups as f64)
+        }
+        else {
+            stats.hits.to_string()
+        });
+        imp.image_cache_misses.set_label(&stats.misses.to_string());
+        imp.image_cache_evictions.set_label(&stats.evictions.to_string());
+    }
+}
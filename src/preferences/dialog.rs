@@ -13,6 +13,7 @@ use crate::{cache::Cache, client::{ClientState, MpdMessage}};
 
 use super::{
     ClientPreferences,
+    DiagnosticsPreferences,
     IntegrationsPreferences,
     LibraryPreferences,
     PlayerPreferences
@@ -34,7 +35,10 @@ mod imp {
         pub library_tab: TemplateChild<LibraryPreferences>,
 
         #[template_child]
-        pub player_tab: TemplateChild<PlayerPreferences>
+        pub player_tab: TemplateChild<PlayerPreferences>,
+
+        #[template_child]
+        pub diagnostics_tab: TemplateChild<DiagnosticsPreferences>
     }
 
     #[glib::object_subclass]
@@ -78,8 +82,9 @@ impl Preferences {
         let res = Self::default();
 
         res.imp().client_tab.get().setup(sender, client_state);
-        res.imp().library_tab.get().setup();
+        res.imp().library_tab.get().setup(cache.clone());
         res.imp().player_tab.get().setup();
+        res.imp().diagnostics_tab.get().setup(cache.clone());
         res.imp().integrations_tab.get().setup(cache);
 
         res
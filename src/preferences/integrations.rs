@@ -1,12 +1,16 @@
 use std::rc::Rc;
-use std::cell::OnceCell;
+use std::cell::{OnceCell, RefCell};
 use adw::subclass::prelude::*;
 use adw::prelude::*;
 use gtk::{
-    glib, CompositeTemplate
+    glib::{self, clone}, CompositeTemplate
 };
 
-use crate::{cache::Cache, utils};
+use crate::{
+    cache::Cache,
+    meta_providers::{external::get_commands, get_field_preferences, get_provider_name, get_provider_order, set_field_preference, PREFERABLE_FIELDS},
+    utils
+};
 
 use super::ProviderRow;
 
@@ -17,6 +21,22 @@ mod imp {
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(resource = "/org/euphonica/Euphonica/gtk/preferences/integrations.ui")]
     pub struct IntegrationsPreferences {
+        #[template_child]
+        pub connect_timeout: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub read_timeout: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub max_retries: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub worker_count: TemplateChild<adw::SpinRow>,
+
+        #[template_child]
+        pub positive_ttl: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub negative_ttl: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub background_refresh: TemplateChild<adw::SwitchRow>,
+
         #[template_child]
         pub lastfm_key: TemplateChild<adw::EntryRow>,
         #[template_child]
@@ -26,9 +46,34 @@ mod imp {
         pub musicbrainz_download_album_art: TemplateChild<adw::SwitchRow>,
         #[template_child]
         pub musicbrainz_download_artist_avatar: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub musicbrainz_coverart_endpoint: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub musicbrainz_show_missing_albums: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub musicbrainz_discography_ttl: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub musicbrainz_match_threshold: TemplateChild<adw::SpinRow>,
+
+        #[template_child]
+        pub wikipedia_language: TemplateChild<adw::EntryRow>,
+
+        #[template_child]
+        pub local_music_root: TemplateChild<adw::EntryRow>,
+
+        #[template_child]
+        pub external_commands: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub external_new_command: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub external_timeout: TemplateChild<adw::SpinRow>,
+        // One per configured command, above the entry row
+        pub external_command_rows: RefCell<Vec<adw::ActionRow>>,
 
         #[template_child]
         pub order_box: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub field_sources: TemplateChild<adw::PreferencesGroup>,
         pub cache: OnceCell<Rc<Cache>>
     }
 
@@ -70,7 +115,65 @@ impl IntegrationsPreferences {
         let _ = self.imp().cache.set(cache);
         let imp = self.imp();
         // Populate with current gsettings values
-        let settings = utils::settings_manager();
+        // Set up request settings
+        let provider_settings = utils::settings_manager().child("metaprovider");
+        provider_settings
+            .bind(
+                "connect-timeout-s",
+                &imp.connect_timeout.adjustment(),
+                "value"
+            )
+            .build();
+
+        provider_settings
+            .bind(
+                "read-timeout-s",
+                &imp.read_timeout.adjustment(),
+                "value"
+            )
+            .build();
+
+        provider_settings
+            .bind(
+                "max-retries",
+                &imp.max_retries.adjustment(),
+                "value"
+            )
+            .build();
+
+        provider_settings
+            .bind(
+                "worker-count",
+                &imp.worker_count.adjustment(),
+                "value"
+            )
+            .build();
+
+        // Set up metadata expiry settings
+        provider_settings
+            .bind(
+                "positive-ttl-days",
+                &imp.positive_ttl.adjustment(),
+                "value"
+            )
+            .build();
+
+        provider_settings
+            .bind(
+                "negative-ttl-days",
+                &imp.negative_ttl.adjustment(),
+                "value"
+            )
+            .build();
+
+        provider_settings
+            .bind(
+                "background-refresh",
+                &imp.background_refresh.get(),
+                "active"
+            )
+            .build();
+
 
         // Set up Last.fm settings
         let lastfm_settings = utils::meta_provider_settings("lastfm");
@@ -115,14 +218,93 @@ impl IntegrationsPreferences {
             )
             .build();
 
+        mb_settings
+            .bind(
+                "coverart-endpoint",
+                &imp.musicbrainz_coverart_endpoint.get(),
+                "text"
+            )
+            .build();
+
+        mb_settings
+            .bind(
+                "show-missing-albums",
+                &imp.musicbrainz_show_missing_albums.get(),
+                "active"
+            )
+            .build();
+
+        mb_settings
+            .bind(
+                "discography-ttl-days",
+                &imp.musicbrainz_discography_ttl.adjustment(),
+                "value"
+            )
+            .build();
+
+        mb_settings
+            .bind(
+                "match-threshold",
+                &imp.musicbrainz_match_threshold.adjustment(),
+                "value"
+            )
+            .build();
+
+        // Set up Wikipedia settings
+        utils::meta_provider_settings("wikipedia")
+            .bind(
+                "language",
+                &imp.wikipedia_language.get(),
+                "text"
+            )
+            .build();
+
+        // Set up local file settings
+        utils::meta_provider_settings("local")
+            .bind(
+                "music-root",
+                &imp.local_music_root.get(),
+                "text"
+            )
+            .build();
+
+        // Set up external command settings
+        let external_settings = utils::meta_provider_settings("external");
+        // Move the single executable of older versions into the list.
+        if !external_settings.string("executable").is_empty() {
+            let commands = get_commands();
+            let _ = external_settings.set_strv("commands", commands.as_slice());
+            let _ = external_settings.reset("executable");
+        }
+        self.update_external_commands();
+        imp.external_new_command.connect_apply(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                let command = entry.text().trim().to_owned();
+                if command.is_empty() {
+                    return;
+                }
+                let mut commands = get_commands();
+                commands.push(command);
+                this.set_external_commands(&commands);
+                entry.set_text("");
+            }
+        ));
+
+        external_settings
+            .bind(
+                "timeout-s",
+                &imp.external_timeout.adjustment(),
+                "value"
+            )
+            .build();
+
         // Set up priority settings
         let order_box = self.imp().order_box.get();
 
-        for row in settings
-            .child("metaprovider")
-            .value("order")
-            .array_iter_str()
-            .unwrap()
+        for row in get_provider_order()
+            .iter()
             .enumerate()
             .map(
                 |(prio, key)| ProviderRow::new(&self, key, prio as i32)
@@ -148,6 +330,79 @@ impl IntegrationsPreferences {
                 gtk::Ordering::Equal
             }
         });
+
+        self.setup_field_sources();
+    }
+
+    fn set_external_commands(&self, commands: &[String]) {
+        let _ = utils::meta_provider_settings("external").set_strv("commands", commands);
+        self.update_external_commands();
+    }
+
+    /// List configured external commands above the entry row, each with a button to
+    /// remove it.
+    fn update_external_commands(&self) {
+        let list = self.imp().external_commands.get();
+        for row in self.imp().external_command_rows.take() {
+            list.remove(&row);
+        }
+        let commands = get_commands();
+        let mut rows = Vec::with_capacity(commands.len());
+        for (idx, command) in commands.iter().enumerate() {
+            let row = adw::ActionRow::new();
+            row.set_title(&glib::markup_escape_text(command));
+            row.add_css_class("monospace");
+            let remove = gtk::Button::from_icon_name("user-trash-symbolic");
+            // TODO: translatable
+            remove.set_tooltip_text(Some("Remove command"));
+            remove.set_valign(gtk::Align::Center);
+            remove.add_css_class("flat");
+            remove.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    let mut commands = get_commands();
+                    if idx < commands.len() {
+                        commands.remove(idx);
+                    }
+                    this.set_external_commands(&commands);
+                }
+            ));
+            row.add_suffix(&remove);
+            list.insert(&row, idx as i32);
+            rows.push(row);
+        }
+        self.imp().external_command_rows.replace(rows);
+    }
+
+    fn setup_field_sources(&self) {
+        let keys = get_provider_order();
+        // TODO: translatable
+        let mut names: Vec<String> = vec!["Follow priority".to_owned()];
+        names.extend(keys.iter().map(|key| get_provider_name(key)));
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let group = self.imp().field_sources.get();
+        for (kind, field) in PREFERABLE_FIELDS {
+            let row = adw::ComboRow::new();
+            row.set_title(field_title(kind, field));
+            row.set_model(Some(&gtk::StringList::new(&names)));
+            // Index 0 means no preference
+            let current = get_field_preferences(kind)
+                .get(field)
+                .and_then(|provider| keys.iter().position(|key| key == provider))
+                .map_or(0, |idx| idx + 1);
+            row.set_selected(current as u32);
+            let keys = keys.clone();
+            row.connect_selected_notify(move |row| {
+                let selected = row.selected() as usize;
+                set_field_preference(
+                    kind,
+                    field,
+                    if selected == 0 {None} else {keys.get(selected - 1).map(String::as_str)}
+                );
+            });
+            group.add(&row);
+        }
     }
 
     fn regen_provider_list(&self) {
@@ -213,3 +468,16 @@ impl IntegrationsPreferences {
         }
     }
 }
+
+// TODO: translatable
+fn field_title(kind: &str, field: &str) -> &'static str {
+    match (kind, field) {
+        ("album", "wiki") => "Album descriptions",
+        ("album", "tags") => "Album tags",
+        ("album", "image") => "Album art",
+        ("artist", "bio") => "Artist biographies",
+        ("artist", "tags") => "Artist tags",
+        ("artist", "image") => "Artist avatars",
+        _ => "Other"
+    }
+}
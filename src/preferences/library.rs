@@ -1,13 +1,18 @@
+use std::{cell::OnceCell, rc::Rc};
 use adw::subclass::prelude::*;
 use adw::prelude::*;
 use gtk::{
+    gio,
     glib,
     CompositeTemplate
 };
 
 use glib::clone;
 
-use crate::utils;
+use crate::{
+    cache::{Cache, CacheCategory},
+    utils
+};
 
 mod imp {
     use super::*;
@@ -30,6 +35,37 @@ mod imp {
         pub artist_excepts: TemplateChild<gtk::TextView>,
         #[template_child]
         pub artist_excepts_apply: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub hires_image_size: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub thumbnail_image_size: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub hidpi_thumbnails: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub keep_original_images: TemplateChild<adw::SwitchRow>,
+
+        #[template_child]
+        pub image_cache_mb: TemplateChild<adw::SpinRow>,
+
+        #[template_child]
+        pub disk_cache_mb: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub album_art_usage: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub clear_album_art: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub avatars_usage: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub clear_avatars: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub thumbnails_usage: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub metadata_usage: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub clear_metadata: TemplateChild<gtk::Button>,
+
+        pub cache: OnceCell<Rc<Cache>>
     }
 
     #[glib::object_subclass]
@@ -66,8 +102,9 @@ impl Default for LibraryPreferences {
 }
 
 impl LibraryPreferences {
-    pub fn setup(&self) {
+    pub fn setup(&self, cache: Rc<Cache>) {
         let imp = self.imp();
+        let _ = imp.cache.set(cache);
 
         // Populate with current gsettings values
         let settings = utils::settings_manager();
@@ -98,6 +135,71 @@ impl LibraryPreferences {
             )
             .build();
 
+        // Set up images section
+        library_settings
+            .bind(
+                "hires-image-size",
+                &imp.hires_image_size.adjustment(),
+                "value"
+            )
+            .build();
+        library_settings
+            .bind(
+                "thumbnail-image-size",
+                &imp.thumbnail_image_size.adjustment(),
+                "value"
+            )
+            .build();
+        library_settings
+            .bind(
+                "hidpi-thumbnails",
+                &imp.hidpi_thumbnails.get(),
+                "active"
+            )
+            .build();
+        library_settings
+            .bind(
+                "keep-original-images",
+                &imp.keep_original_images.get(),
+                "active"
+            )
+            .build();
+
+        // Set up memory section
+        library_settings
+            .bind(
+                "image-cache-mb",
+                &imp.image_cache_mb.adjustment(),
+                "value"
+            )
+            .build();
+
+        // Set up disk section. Usage is only computed when this page is shown, as
+        // it requires walking the whole cache folder.
+        library_settings
+            .bind(
+                "disk-cache-mb",
+                &imp.disk_cache_mb.adjustment(),
+                "value"
+            )
+            .build();
+        self.connect_map(|this| {
+            this.update_disk_usage();
+        });
+        for (btn, category) in [
+            (imp.clear_album_art.get(), CacheCategory::AlbumArt),
+            (imp.clear_avatars.get(), CacheCategory::Avatars),
+            (imp.clear_metadata.get(), CacheCategory::Metadata)
+        ] {
+            btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |btn| {
+                    this.clear_disk_cache(btn, category);
+                }
+            ));
+        }
+
         // Setup artist section
         let artist_delims_buf = imp.artist_delims.buffer();
         let artist_delims_apply = imp.artist_delims_apply.get();
@@ -184,4 +286,43 @@ impl LibraryPreferences {
             }
         ));
     }
+
+    fn update_disk_usage(&self) {
+        let Some(cache) = self.imp().cache.get() else {
+            return;
+        };
+        let disk_cache = cache.get_disk_cache();
+        glib::MainContext::default().spawn_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                if let Ok(usage) = gio::spawn_blocking(move || disk_cache.usage()).await {
+                    let imp = this.imp();
+                    imp.album_art_usage.set_subtitle(&glib::format_size(usage.album_art));
+                    imp.avatars_usage.set_subtitle(&glib::format_size(usage.avatars));
+                    imp.thumbnails_usage.set_subtitle(&glib::format_size(usage.thumbnails));
+                    imp.metadata_usage.set_subtitle(&glib::format_size(usage.metadata));
+                }
+            }
+        ));
+    }
+
+    fn clear_disk_cache(&self, btn: &gtk::Button, category: CacheCategory) {
+        let Some(cache) = self.imp().cache.get() else {
+            return;
+        };
+        btn.set_sensitive(false);
+        let handle = cache.clear_disk_cache(category);
+        glib::MainContext::default().spawn_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            btn,
+            async move {
+                let _ = handle.await;
+                btn.set_sensitive(true);
+                this.update_disk_usage();
+            }
+        ));
+    }
 }
@@ -3,6 +3,7 @@ mod client;
 mod library;
 mod player;
 mod integrations;
+mod diagnostics;
 mod provider_row;
 
 pub use provider_row::ProviderRow;
@@ -10,4 +11,5 @@ pub use player::PlayerPreferences;
 pub use client::ClientPreferences;
 pub use integrations::IntegrationsPreferences;
 pub use library::LibraryPreferences;
+pub use diagnostics::DiagnosticsPreferences;
 pub use dialog::Preferences;
@@ -31,6 +31,18 @@ mod imp {
         #[template_child]
         pub vol_knob_sensitivity: TemplateChild<adw::SpinRow>,
         #[template_child]
+        pub autodj_enabled: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub autodj_source: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub autodj_smart_playlist: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub autodj_batch_size: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub autodj_min_remaining: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub autodj_no_repeat_window: TemplateChild<adw::SpinRow>,
+        #[template_child]
         pub enable_mpris: TemplateChild<adw::SwitchRow>,
     }
 
@@ -153,6 +165,88 @@ impl PlayerPreferences {
             )
             .build();
 
+        let autodj_enabled = imp.autodj_enabled.get();
+        let autodj_source = imp.autodj_source.get();
+        let autodj_smart_playlist = imp.autodj_smart_playlist.get();
+        player_settings
+            .bind(
+                "autodj-enabled",
+                &autodj_enabled,
+                "active"
+            )
+            .build();
+
+        player_settings
+            .bind(
+                "autodj-source",
+                &autodj_source,
+                "selected"
+            )
+            .mapping(
+                |v: &Variant, _| { match v.get::<String>().unwrap().as_str() {
+                    "random" => Some(0.to_value()),
+                    "same-artist" => Some(1.to_value()),
+                    "same-genre" => Some(2.to_value()),
+                    "similar-artists" => Some(3.to_value()),
+                    "smart-playlist" => Some(4.to_value()),
+                    _ => unreachable!()
+                }}
+            )
+            .set_mapping(
+                |v: &Value, _| { match v.get::<u32>().ok() {
+                    Some(0) => Some("random".to_variant()),
+                    Some(1) => Some("same-artist".to_variant()),
+                    Some(2) => Some("same-genre".to_variant()),
+                    Some(3) => Some("similar-artists".to_variant()),
+                    Some(4) => Some("smart-playlist".to_variant()),
+                    _ => unreachable!()
+                }}
+            )
+            .build();
+
+        // Only relevant when drawing from a smart playlist
+        autodj_source
+            .bind_property(
+                "selected",
+                &autodj_smart_playlist,
+                "sensitive"
+            )
+            .transform_to(|_, selected: u32| Some(selected == 4))
+            .sync_create()
+            .build();
+
+        player_settings
+            .bind(
+                "autodj-smart-playlist",
+                &autodj_smart_playlist,
+                "text"
+            )
+            .build();
+
+        player_settings
+            .bind(
+                "autodj-batch-size",
+                &imp.autodj_batch_size.adjustment(),
+                "value"
+            )
+            .build();
+
+        player_settings
+            .bind(
+                "autodj-min-remaining",
+                &imp.autodj_min_remaining.adjustment(),
+                "value"
+            )
+            .build();
+
+        player_settings
+            .bind(
+                "autodj-no-repeat-window",
+                &imp.autodj_no_repeat_window.adjustment(),
+                "value"
+            )
+            .build();
+
         let enable_mpris = self.imp().enable_mpris.get();
         player_settings
             .bind(
@@ -22,6 +22,8 @@ mod imp {
         #[template_child]
         pub folders_btn: TemplateChild<SidebarButton>,
         #[template_child]
+        pub smart_playlists_btn: TemplateChild<SidebarButton>,
+        #[template_child]
         pub queue_btn: TemplateChild<gtk::ToggleButton>,
         #[template_child]
         pub queue_len: TemplateChild<gtk::Label>,
@@ -118,6 +120,15 @@ impl Sidebar {
             }
         }));
 
+        self.imp().smart_playlists_btn.connect_toggled(clone!(
+            #[weak]
+            stack,
+            move |btn| {
+            if btn.is_active() {
+                stack.set_visible_child_name("smart_playlists");
+            }
+        }));
+
         self.imp().queue_btn.connect_toggled(clone!(
             #[weak]
             stack,
@@ -138,7 +149,8 @@ impl Sidebar {
         for btn in [
             &self.imp().albums_btn.get(),
             &self.imp().artists_btn.get(),
-            &self.imp().folders_btn.get()
+            &self.imp().folders_btn.get(),
+            &self.imp().smart_playlists_btn.get()
         ] {
             btn.upcast_ref::<gtk::ToggleButton>().upcast_ref::<gtk::Button>().connect_clicked(clone!(
                 #[weak]
@@ -158,14 +170,4 @@ impl Sidebar {
               .transform_to(|_, size: u32| {Some(size.to_string())})
               .build();
     }
-
-    pub fn set_view(&self, view_name: &str) {
-        // TODO: something less dumb than this
-        match view_name {
-            "albums" => self.imp().albums_btn.set_active(true),
-            "artists" => self.imp().artists_btn.set_active(true),
-            "queue" => self.imp().queue_btn.set_active(true),
-            _ => unimplemented!()
-        };
-    }
 }
@@ -1,6 +1,6 @@
 use once_cell::sync::Lazy;
 use std::{
-    sync::RwLock,
+    sync::{atomic::{AtomicU32, Ordering as AtomicOrdering}, RwLock},
     hash::Hash,
     io::Cursor
 };
@@ -163,7 +163,7 @@ pub fn strip_filename_linux(path: &str) -> &str {
     path
 }
 
-pub fn read_image_from_bytes(bytes: Vec<u8>) -> Option<DynamicImage> {
+pub fn read_image_from_bytes<B: AsRef<[u8]>>(bytes: B) -> Option<DynamicImage> {
     if let Ok(reader) = ImageReader::new(Cursor::new(bytes)).with_guessed_format() {
         if let Ok(dyn_img) = reader.decode() {
             return Some(dyn_img);
@@ -173,17 +173,44 @@ pub fn read_image_from_bytes(bytes: Vec<u8>) -> Option<DynamicImage> {
     None
 }
 
+// Scale factor of the main window's display in percent. Images are resized on child
+// threads, which cannot query GDK themselves, so the window keeps this updated instead.
+static DISPLAY_SCALE: AtomicU32 = AtomicU32::new(100);
+
+/// Remember the main window's scale factor. Returns true if it changed.
+pub fn set_display_scale(scale: f64) -> bool {
+    let percent = (scale * 100.0).round().max(100.0) as u32;
+    DISPLAY_SCALE.swap(percent, AtomicOrdering::Relaxed) != percent
+}
+
+/// Major axis resolutions set by the user, as (high-resolution, thumbnail), before
+/// thumbnails are scaled for the display.
+pub fn unscaled_image_sizes() -> (u32, u32) {
+    let settings = settings_manager().child("library");
+    let hires_size = settings.uint("hires-image-size");
+    (hires_size, settings.uint("thumbnail-image-size").min(hires_size))
+}
+
+/// Major axis resolutions to resize cached images to, as (high-resolution, thumbnail).
+/// Thumbnails are scaled up by the display's scale factor unless hidpi-thumbnails is off,
+/// so that they stay sharp on HiDPI screens.
+pub fn cached_image_sizes() -> (u32, u32) {
+    let (hires_size, mut thumbnail_size) = unscaled_image_sizes();
+    if settings_manager().child("library").boolean("hidpi-thumbnails") {
+        thumbnail_size = thumbnail_size * DISPLAY_SCALE.load(AtomicOrdering::Relaxed) / 100;
+    }
+    (hires_size, thumbnail_size.min(hires_size))
+}
+
 /// Automatically resize & based on user settings, then convert to RGB8.
 /// All providers should use this function on their child threads to resize applicable images
 /// before returning the images to the main thread.
 /// Two images will be returned: a high-resolution version and a thumbnail version.
-/// Their major axis's resolution is determined by the keys hires-image-size and
-/// thumbnail-image-size in the gschema respectively.
+/// Their major axis's resolution is determined by cached_image_sizes.
 pub fn resize_convert_image(dyn_img: DynamicImage) -> (RgbImage, RgbImage) {
-    let settings = settings_manager().child("library");
+    let (hires_size, thumbnail_size) = cached_image_sizes();
     // Avoid resizing to larger than the original image.
-    let hires_size = settings.uint("hires-image-size").min(dyn_img.width().max(dyn_img.height()));
-    let thumbnail_size = settings.uint("thumbnail-image-size");
+    let hires_size = hires_size.min(dyn_img.width().max(dyn_img.height()));
     (
         dyn_img.resize(hires_size, hires_size, FilterType::Triangle).into_rgb8(),
         dyn_img.thumbnail(thumbnail_size, thumbnail_size).into_rgb8()
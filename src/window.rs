@@ -30,7 +30,7 @@ use glib::signal::SignalHandlerId;
 use image::{imageops::FilterType, DynamicImage};
 use libblur::{stack_blur, FastBlurChannels, ThreadingPolicy};
 use crate::{
-    application::EuphonicaApplication, client::ConnectionState, common::Album, library::{AlbumView, ArtistContentView, ArtistView}, player::{PlayerBar, QueueView}, sidebar::Sidebar, utils::{self, settings_manager}
+    application::EuphonicaApplication, cache::CacheState, client::ConnectionState, library::{AlbumView, ArtistView}, player::{PlayerBar, QueueView}, sidebar::Sidebar, utils::{self, settings_manager}
 };
 
 #[derive(Debug)]
@@ -96,7 +96,7 @@ mod imp {
     use image::io::Reader;
     use utils::settings_manager;
 
-    use crate::{common::paintables::FadePaintable, library::FolderView, player::Player};
+    use crate::{common::paintables::FadePaintable, library::{FolderView, SmartPlaylistView}, player::Player};
 
     use super::*;
 
@@ -106,6 +106,8 @@ mod imp {
     pub struct EuphonicaWindow {
         // Top level widgets
         #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
         pub split_view: TemplateChild<adw::NavigationSplitView>,
         #[template_child]
         pub content: TemplateChild<gtk::Box>,
@@ -117,6 +119,8 @@ mod imp {
         #[template_child]
         pub folder_view: TemplateChild<FolderView>,
         #[template_child]
+        pub smart_playlist_view: TemplateChild<SmartPlaylistView>,
+        #[template_child]
         pub queue_view: TemplateChild<QueueView>,
 
         // Content view stack
@@ -142,6 +146,9 @@ mod imp {
         pub notify_position_id: RefCell<Option<SignalHandlerId>>,
         pub notify_playback_state_id: RefCell<Option<SignalHandlerId>>,
         pub notify_duration_id: RefCell<Option<SignalHandlerId>>,
+        // Toast for the latest metadata provider error, reused while still shown so that
+        // a provider failing for every album doesn't flood the window.
+        pub provider_error_toast: RefCell<Option<adw::Toast>>,
 
         #[property(get, set)]
         pub use_album_art_bg: Cell<bool>,
@@ -460,6 +467,8 @@ impl EuphonicaWindow {
         let _ = win.imp().player.set(player);
 
         win.restore_window_state();
+        win.setup_display_scale();
+        win.setup_provider_errors();
         win.imp().queue_view.setup(
             app.get_player(),
             app.get_cache()
@@ -479,6 +488,11 @@ impl EuphonicaWindow {
             app.get_cache(),
             app.get_client().get_client_state()
         );
+        win.imp().smart_playlist_view.setup(
+            app.get_library(),
+            app.get_cache(),
+            app.get_client().get_client_state()
+        );
         win.imp().sidebar.setup(
             win.imp().stack.get(),
             win.imp().split_view.get(),
@@ -500,18 +514,6 @@ impl EuphonicaWindow {
             )
         );
 
-        win.imp().artist_view.get_content_view().connect_closure(
-            "album-clicked",
-            false,
-            closure_local!(
-                #[weak(rename_to = this)]
-                win,
-                move |_: ArtistContentView, album: Album| {
-                    this.goto_album(&album);
-                }
-            )
-        );
-
         win.bind_state();
         win.setup_signals();
         win
@@ -539,15 +541,6 @@ impl EuphonicaWindow {
         self.imp().queue_view.set_show_content(true);
     }
 
-    pub fn goto_album(&self, album: &Album) {
-        self.imp().album_view.on_album_clicked(album);
-        // self.imp().stack.set_visible_child_name("albums");
-        self.imp().sidebar.set_view("albums");
-        if !self.imp().split_view.shows_content() {
-            self.imp().split_view.set_show_content(true);
-        }
-    }
-
     /// Set blurred background to a new image, if enabled. Use thumbnail version to
     /// minimise disk read time.
     fn queue_new_background(&self) {
@@ -593,6 +586,53 @@ impl EuphonicaWindow {
         self.set_default_size(width, height);
     }
 
+    /// Size thumbnails for the display this window is on, including when it is moved to
+    /// another one with a different scale factor.
+    fn setup_display_scale(&self) {
+        self.connect_realize(|this| {
+            if let Some(surface) = this.surface() {
+                let cache = this.downcast_application().get_cache();
+                cache.set_display_scale(surface.scale());
+                surface.connect_scale_notify(move |surface| {
+                    cache.set_display_scale(surface.scale());
+                });
+            }
+        });
+    }
+
+    /// Tell the user when a metadata provider fails, such as an external command
+    /// crashing.
+    fn setup_provider_errors(&self) {
+        let state = self.downcast_application().get_cache().get_cache_state();
+        state.connect_closure(
+            "provider-error",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: CacheState, provider: String, message: String| {
+                    // TODO: translatable
+                    let title = glib::markup_escape_text(&format!("{}: {}", provider, message));
+                    if let Some(toast) = this.imp().provider_error_toast.borrow().as_ref() {
+                        toast.set_title(&title);
+                        return;
+                    }
+                    let toast = adw::Toast::new(&title);
+                    toast.set_timeout(10);
+                    toast.connect_dismissed(clone!(
+                        #[weak]
+                        this,
+                        move |_| {
+                            this.imp().provider_error_toast.take();
+                        }
+                    ));
+                    this.imp().toast_overlay.add_toast(toast.clone());
+                    this.imp().provider_error_toast.replace(Some(toast));
+                }
+            )
+        );
+    }
+
     fn downcast_application(&self) -> EuphonicaApplication {
         self.application()
             .unwrap()